@@ -1,17 +1,35 @@
+pub mod command;
 pub mod config;
+pub mod daemon;
 pub mod instruction;
 pub mod network;
 pub mod server;
 pub mod ui;
-
+pub mod verification;
+
+#[cfg(feature = "tray")]
+use command::{AppCommand, CommandChannel, CommandError, CommandOutcome, CommandResult};
+#[cfg(feature = "tray")]
+use config::change::ConfigChange;
+#[cfg(feature = "tray")]
+use instruction::history::{ContextAuthor, ContextRevision};
+#[cfg(feature = "tray")]
 use std::sync::{Arc, Mutex};
+#[cfg(feature = "tray")]
 use tokio::runtime::Runtime;
+#[cfg(feature = "tray")]
 use tokio::sync::Mutex as TokioMutex;
 
+/// How many recent context changes the tray offers to roll back to
+#[cfg(feature = "tray")]
+const TRAY_REVISIONS: usize = 10;
+
 pub fn version() -> &'static str {
     env!("CARGO_PKG_VERSION")
 }
 
+/// The tray app: the menu bar icon over the server pool
+#[cfg(feature = "tray")]
 pub struct App {
     pub name: String,
     pub version: String,
@@ -22,8 +40,21 @@ pub struct App {
     pub runtime: Runtime,
     pub context_manager: Arc<Mutex<instruction::manager::ContextManager>>,
     pub server_pool: Arc<TokioMutex<server::pool::ServerPool>>,
+
+    // Number of sessions shown in the tray the last time it was rebuilt
+    tray_session_count: usize,
+
+    // PID and runtime files, written once `run` starts serving, so `chase` edits the
+    // running app rather than network.toml under it
+    runtime_files: Option<daemon::RuntimeFiles>,
+    started_at: chrono::DateTime<chrono::Utc>,
+
+    // Commands from the tray and the admin API, run on the tray's thread
+    commands: CommandChannel,
+    command_queue: tokio::sync::mpsc::UnboundedReceiver<command::CommandRequest>,
 }
 
+#[cfg(feature = "tray")]
 impl App {
     pub fn new() -> Self {
         let config = config::network_config::NetworkConfig::load().unwrap_or_default();
@@ -38,9 +69,10 @@ impl App {
             }),
         ));
 
-        let server_pool = Arc::new(TokioMutex::new(server::pool::ServerPool::new(
-            context_manager.clone(),
-        )));
+        let (commands, command_queue) = CommandChannel::new();
+        let mut pool = server::pool::ServerPool::new(context_manager.clone());
+        pool.set_admin(commands.clone());
+        let server_pool = Arc::new(TokioMutex::new(pool));
 
         Self {
             name: "ChaseAI".to_string(),
@@ -50,16 +82,23 @@ impl App {
             runtime,
             context_manager,
             server_pool,
+            tray_session_count: 0,
+            runtime_files: None,
+            started_at: chrono::Utc::now(),
+            commands,
+            command_queue,
         }
     }
 }
 
+#[cfg(feature = "tray")]
 impl Default for App {
     fn default() -> Self {
         Self::new()
     }
 }
 
+#[cfg(feature = "tray")]
 impl App {
     pub fn run(&mut self) -> anyhow::Result<()> {
         // Set a flag or something if we want to avoid actual side effects in some environments
@@ -68,6 +107,13 @@ impl App {
         println!("Current network mode: {:?}", self.config.default_interface);
         println!("Active port bindings: {}", self.config.port_bindings.len());
 
+        let files = daemon::RuntimeFiles::new()?;
+        if let Some(pid) = files.running_pid() {
+            if pid != std::process::id() {
+                anyhow::bail!("ChaseAI is already running (pid {})", pid);
+            }
+        }
+
         // Start services
         let pool = self.server_pool.clone();
         let config_clone = self.config.clone();
@@ -78,121 +124,118 @@ impl App {
                 eprintln!("Failed to start servers: {}", e);
             }
         });
+        self.runtime_files = Some(files);
+        self.write_runtime_files();
 
-        self.tray.setup(&self.config)?;
+        let state = self.tray_state();
+        self.tray_session_count = state.sessions.len();
+        self.tray.setup(&self.config, &state)?;
 
         println!("System ready for controlled execution.");
         Ok(())
     }
-    pub fn handle_menu_event(&mut self, event: tray_icon::menu::MenuEvent) {
-        if self.process_menu_event(event.id.as_ref()) {
-            std::process::exit(0);
-        }
+    /// Queue of commands into this app; the tray and the admin API submit through it
+    pub fn commands(&self) -> &CommandChannel {
+        &self.commands
     }
 
-    pub fn process_menu_event(&mut self, id: &str) -> bool {
-        println!("Processing menu event: {}", id);
+    /// Run the queued commands, answering each before the servers restart, then refresh
+    /// the tray and servers once. Returns whether one of them asked to quit.
+    pub fn process_commands(&mut self) -> bool {
         let mut changed = false;
-        let mut should_exit = false;
-
-        if id == "quit" {
-            println!("Quit requested, exiting...");
-            should_exit = true;
-        } else if let Some(port_str) = id.strip_prefix("port:") {
-            if let Ok(port) = port_str.parse::<u16>() {
-                if let Some(binding) = self
-                    .config
-                    .port_bindings
-                    .iter_mut()
-                    .find(|p| p.port == port)
-                {
-                    binding.enabled = !binding.enabled;
-                    changed = true;
-                }
+        let mut exit = false;
+        while let Ok(request) = self.command_queue.try_recv() {
+            let result = self.dispatch(request.command.clone());
+            match &result {
+                Ok(CommandOutcome::ConfigChanged(_)) => changed = true,
+                Ok(CommandOutcome::Exit) => exit = true,
+                _ => {}
             }
-        } else if let Some(port_str) = id.strip_prefix("remove_port:") {
-            if let Ok(port) = port_str.parse::<u16>() {
-                self.config.port_bindings.retain(|b| b.port != port);
-                changed = true;
+            request.respond(&result);
+        }
+        if changed {
+            self.refresh_ui_and_servers();
+        }
+        if exit {
+            if let Some(files) = &self.runtime_files {
+                files.remove();
             }
-        } else if let Some(role_change) = id.strip_prefix("role:") {
-            let parts: Vec<&str> = role_change.split(':').collect();
-            if parts.len() == 2 {
-                if let Ok(port) = parts[0].parse::<u16>() {
-                    let role_str = parts[1];
-                    if let Some(binding) = self
-                        .config
-                        .port_bindings
-                        .iter_mut()
-                        .find(|b| b.port == port)
-                    {
-                        binding.role = match role_str {
-                            "Instruction" => crate::network::port_config::PortRole::Instruction,
-                            "Verification" => crate::network::port_config::PortRole::Verification,
-                            _ => binding.role,
-                        };
-                        changed = true;
-                    }
+        }
+        exit
+    }
+
+    /// Run one command right away, refreshing the tray and servers if it changed the config
+    pub fn execute(&mut self, command: AppCommand) -> CommandResult {
+        let result = self.dispatch(command);
+        if let Ok(CommandOutcome::ConfigChanged(_)) = result {
+            self.refresh_ui_and_servers();
+        }
+        result
+    }
+
+    fn dispatch(&mut self, command: AppCommand) -> CommandResult {
+        println!("Running command: {:?}", command);
+        match command {
+            AppCommand::AddPortDialog => match self.add_port_dialog() {
+                Some(change) => command::configure(&mut self.config, &[change]),
+                None => Ok(CommandOutcome::Unchanged(self.config.clone())),
+            },
+            AppCommand::RevokeSession { session_id } => {
+                let revoked = self
+                    .context_manager
+                    .lock()
+                    .map(|mut m| m.revoke_session(&session_id))
+                    .unwrap_or(false);
+                self.refresh_tray();
+                if !revoked {
+                    return Err(CommandError::UnknownSession(session_id));
                 }
+                Ok(CommandOutcome::Unchanged(self.config.clone()))
             }
-        } else if let Some(name) = id.strip_prefix("interface:") {
-            println!("Interface change requested: {}", name);
-            // ... (rest of interface code)
-            if let Ok(interfaces) =
-                crate::network::interface_detector::InterfaceDetector::detect_all()
-            {
-                if let Some(iface) = interfaces.iter().find(|i| i.name == name) {
-                    self.config.default_interface = iface.interface_type.clone();
-                    for binding in &mut self.config.port_bindings {
-                        binding.interface = iface.clone();
-                    }
-                    changed = true;
+            AppCommand::RevokeAllSessions => {
+                if let Ok(mut manager) = self.context_manager.lock() {
+                    manager.revoke_all_sessions();
                 }
+                self.refresh_tray();
+                Ok(CommandOutcome::Unchanged(self.config.clone()))
             }
-        } else if id == "cmd:enable_all" {
-            println!("Enable all services requested");
-            for binding in &mut self.config.port_bindings {
-                binding.enabled = true;
+            AppCommand::RollbackContext { rev } => {
+                let rolled_back = self
+                    .context_manager
+                    .lock()
+                    .map_err(|_| CommandError::Failed("Context manager unavailable".to_string()))?
+                    .rollback(rev, ContextAuthor::Tray)
+                    .map_err(|e| CommandError::Failed(e.to_string()))?;
+                self.refresh_tray();
+                match rolled_back {
+                    Some(_) => Ok(CommandOutcome::Unchanged(self.config.clone())),
+                    None => Err(CommandError::Failed(format!(
+                        "The context is already as of revision {}",
+                        rev
+                    ))),
+                }
             }
-            changed = true;
-        } else if id == "cmd:disable_all" {
-            println!("Disable all services requested");
-            for binding in &mut self.config.port_bindings {
-                binding.enabled = false;
+            AppCommand::DownloadConfig => {
+                self.download_config();
+                Ok(CommandOutcome::Unchanged(self.config.clone()))
             }
-            changed = true;
-        } else if id == "cmd:add_port" {
-            println!("Add port requested");
-            self.add_default_port();
-            changed = true;
-        } else if id == "cmd:download_config" {
-            println!("Download config requested");
-            self.download_config();
-        } else if id == "cmd:open_repo" {
-            println!("Opening GitHub repository...");
-            let _ = std::process::Command::new("open")
-                .arg("https://github.com/Mitriyweb/ChaseAI")
-                .spawn();
-        } else if let Some(mode) = id.strip_prefix("mode:") {
-            println!("Verification mode change requested: {}", mode);
-            self.config.verification_mode = match mode {
-                "cli" => crate::config::network_config::VerificationMode::Cli,
-                _ => crate::config::network_config::VerificationMode::Port,
-            };
-            changed = true;
-        } else {
-            println!("Unknown menu event: {}", id);
-        }
-
-        if changed {
-            println!("Configuration changed, saving and refreshing...");
-            // 1. Save config
-            if let Err(e) = self.config.save() {
-                eprintln!("Failed to save config: {}", e);
+            AppCommand::OpenRepo => {
+                println!("Opening GitHub repository...");
+                std::process::Command::new("open")
+                    .arg("https://github.com/Mitriyweb/ChaseAI")
+                    .spawn()
+                    .map_err(|e| CommandError::Failed(e.to_string()))?;
+                Ok(CommandOutcome::Unchanged(self.config.clone()))
+            }
+            AppCommand::Quit => {
+                println!("Quit requested, exiting...");
+                Ok(CommandOutcome::Exit)
+            }
+            config_command => {
+                let changes = config_command.config_changes(&self.config)?;
+                command::configure(&mut self.config, &changes)
             }
-            self.refresh_ui_and_servers();
         }
-        should_exit
     }
 
     pub fn reload_config(&mut self) {
@@ -205,13 +248,64 @@ impl App {
         }
     }
 
-    fn refresh_ui_and_servers(&mut self) {
-        // 1. Update UI
-        if let Err(e) = self.tray.update_menu(&self.config) {
+    /// Sessions that are currently able to auto-approve actions
+    pub fn active_sessions(&self) -> Vec<instruction::session::Session> {
+        self.context_manager
+            .lock()
+            .map(|m| m.list_sessions().into_iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// The latest context changes, newest first, for the tray's Context History menu
+    pub fn recent_revisions(&self) -> Vec<ContextRevision> {
+        let mut revisions = self
+            .context_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Context manager unavailable"))
+            .and_then(|m| m.history(None))
+            .unwrap_or_else(|e| {
+                eprintln!("Failed to read context history: {}", e);
+                Vec::new()
+            });
+        revisions.reverse();
+        revisions.truncate(TRAY_REVISIONS);
+        revisions
+    }
+
+    /// Rebuild the tray menu if sessions exist or have changed, so the
+    /// remaining time shown in "Active Sessions" stays current
+    pub fn refresh_sessions_menu(&mut self) {
+        let count = self.active_sessions().len();
+        if count > 0 || count != self.tray_session_count {
+            self.refresh_tray();
+        }
+    }
+
+    /// Sessions, refused ports and prompter availability, as shown in the tray
+    pub fn tray_state(&self) -> ui::tray_menu::TrayState {
+        let pool = self.server_pool.clone();
+        let (port_errors, prompter) = self.runtime.block_on(async {
+            let pool = pool.lock().await;
+            (pool.refused_bindings().clone(), pool.prompter())
+        });
+        ui::tray_menu::TrayState {
+            sessions: self.active_sessions(),
+            revisions: self.recent_revisions(),
+            port_errors,
+            prompter_unavailable: prompter.check().err().map(|e| e.to_string()),
+        }
+    }
+
+    fn refresh_tray(&mut self) {
+        let state = self.tray_state();
+        self.tray_session_count = state.sessions.len();
+        if let Err(e) = self.tray.update_menu(&self.config, &state) {
             eprintln!("Failed to update tray: {}", e);
         }
+    }
 
-        // 2. Update Servers
+    fn refresh_ui_and_servers(&mut self) {
+        // 1. Update Servers first so the tray shows bindings they refused
         let pool = self.server_pool.clone();
         let config_clone = self.config.clone();
         self.runtime.block_on(async {
@@ -220,10 +314,30 @@ impl App {
             }
         });
 
+        self.write_runtime_files();
+
+        // 2. Update UI
+        self.refresh_tray();
+
         // 3. Update Live Manifests (if they exist in root)
         self.update_live_manifests();
     }
 
+    /// Record what the servers serve now, as `chase daemon` does
+    fn write_runtime_files(&self) {
+        let Some(files) = &self.runtime_files else {
+            return;
+        };
+        let pool = self.server_pool.clone();
+        let refused = self
+            .runtime
+            .block_on(async { pool.lock().await.refused_bindings().clone() });
+        let info = daemon::RuntimeInfo::serving(&self.config, self.started_at, refused);
+        if let Err(e) = files.write(&info) {
+            eprintln!("Failed to write runtime files: {}", e);
+        }
+    }
+
     fn update_live_manifests(&self) {
         use crate::config::generator::ConfigurationGenerator;
         use std::fs;
@@ -254,7 +368,8 @@ impl App {
         }
     }
 
-    fn add_default_port(&mut self) {
+    /// Ask for a new port, starting from the first one that is free
+    fn add_port_dialog(&self) -> Option<ConfigChange> {
         // Find the first port starting from 8888 that is:
         // 1. Not in our config
         // 2. Actually free on the system (at least on 127.0.0.1)
@@ -272,53 +387,21 @@ impl App {
         }
 
         // Show dialog to get port configuration
-        if let Some(port_config) = crate::ui::dialogs::show_add_port_dialog(default_port) {
-            // Check if port already exists
-            if self
-                .config
-                .port_bindings
-                .iter()
-                .any(|b| b.port == port_config.port)
-            {
-                eprintln!("Port {} already exists", port_config.port);
-                return;
-            }
-
-            // Get current interface or use loopback
-            let interface = if let Ok(interfaces) =
-                crate::network::interface_detector::InterfaceDetector::detect_all()
-            {
-                interfaces
-                    .into_iter()
-                    .find(|i| i.interface_type == self.config.default_interface)
-                    .unwrap_or_else(|| crate::network::interface_detector::NetworkInterface {
-                        name: "lo0".to_string(),
-                        ip_address: "127.0.0.1".parse().unwrap(),
-                        interface_type: crate::network::interface_detector::InterfaceType::Loopback,
-                    })
-            } else {
-                crate::network::interface_detector::NetworkInterface {
-                    name: "lo0".to_string(),
-                    ip_address: "127.0.0.1".parse().unwrap(),
-                    interface_type: crate::network::interface_detector::InterfaceType::Loopback,
-                }
-            };
-
-            let new_binding = crate::network::port_config::PortBinding {
-                port: port_config.port,
-                interface,
-                role: port_config.role,
-                enabled: port_config.enabled,
-            };
-
-            self.config.port_bindings.push(new_binding);
-            println!(
-                "Added new port: {} with role {:?}, enabled: {}",
-                port_config.port, port_config.role, port_config.enabled
-            );
-        } else {
+        let Some(port_config) = crate::ui::dialogs::show_add_port_dialog(default_port) else {
             println!("Add port cancelled");
-        }
+            return None;
+        };
+        println!(
+            "Adding port {} with role {:?}, enabled: {}",
+            port_config.port, port_config.role, port_config.enabled
+        );
+        Some(ConfigChange::AddPort {
+            binding: self.config.new_binding(
+                port_config.port,
+                port_config.role,
+                port_config.enabled,
+            ),
+        })
     }
 
     fn download_config(&self) {
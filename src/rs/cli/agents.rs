@@ -0,0 +1,75 @@
+use crate::cli::target::Target;
+use app::server::tls::TlsStore;
+use clap::Subcommand;
+
+#[derive(Subcommand)]
+pub enum AgentsCommand {
+    /// Issue a client certificate for mTLS ports; its name is shown as the requester.
+    /// Replaces and revokes the agent's previous certificate.
+    Issue {
+        /// Agent name, recorded as the certificate's common name
+        name: String,
+        /// Revoke the agent's certificate instead
+        #[arg(long)]
+        revoke: bool,
+    },
+    /// Give an agent a token to send as `X-ChaseAI-Agent-Token`, so it gets its own
+    /// instruction context; replaces the agent's previous token
+    Token {
+        name: String,
+        /// Revoke the agent's token instead
+        #[arg(long)]
+        revoke: bool,
+    },
+}
+
+pub fn run(command: AgentsCommand) -> anyhow::Result<()> {
+    match command {
+        AgentsCommand::Issue { name, revoke } => issue(&name, revoke),
+        AgentsCommand::Token { name, revoke } => token(name, revoke),
+    }
+}
+
+fn token(name: String, revoke: bool) -> anyhow::Result<()> {
+    let mut target = Target::connect()?;
+    if revoke {
+        target.revoke_agent_token(name.clone())?;
+        println!("Revoked the token of {} in {}", name, target.describe());
+        return Ok(());
+    }
+    // Only the token on stdout, for scripts
+    let issued = target.issue_agent_token(name)?;
+    println!("{}", issued.token);
+    eprintln!(
+        "Token for {} saved in {}; the agent sends it as X-ChaseAI-Agent-Token",
+        issued.agent,
+        target.describe()
+    );
+    Ok(())
+}
+
+fn issue(name: &str, revoke: bool) -> anyhow::Result<()> {
+    let store = TlsStore::new()?;
+    if revoke {
+        if !store.revoke_agent(name)? {
+            anyhow::bail!("{} has no client certificate", name);
+        }
+        println!("Revoked the client certificate of {}", name);
+        return Ok(());
+    }
+    let credentials = store.issue_agent(name)?;
+
+    println!("✅ Issued client certificate for {}", credentials.name);
+    println!("   Certificate: {}", credentials.cert_path.display());
+    println!("   Private key: {}", credentials.key_path.display());
+    println!("   CA:          {}", store.ca_cert_path().display());
+    println!();
+    println!("Give these files to the agent only. Example:");
+    println!(
+        "   curl --cacert {} --cert {} --key {} https://<host>:<port>/health",
+        store.ca_cert_path().display(),
+        credentials.cert_path.display(),
+        credentials.key_path.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,392 @@
+use crate::cli::{admin_binding, admin_client, check_response};
+use app::config::network_config::NetworkConfig;
+use app::instruction::session::format_remaining;
+use app::verification::pending::PendingVerification;
+use app::verification::request::{
+    ButtonDecision, ConsoleAnswer, VerificationButton, VerificationResponse,
+};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+/// How often the queue is fetched again
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Answers return once the decision is recorded, which takes a moment for sessions and the audit log
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Verifications waiting in the running instance, reached with the admin secret over a
+/// loopback port
+struct Queue {
+    client: reqwest::blocking::Client,
+    base_url: String,
+}
+
+impl Queue {
+    fn list(&self) -> anyhow::Result<Vec<PendingVerification>> {
+        let resp = self
+            .client
+            .get(format!("{}/verify/pending", self.base_url))
+            .send()?;
+        Ok(check_response(resp)?.json()?)
+    }
+
+    fn answer(
+        &self,
+        verification_id: &str,
+        answer: &ConsoleAnswer,
+    ) -> anyhow::Result<VerificationResponse> {
+        let resp = self
+            .client
+            .post(format!(
+                "{}/verify/{}/answer",
+                self.base_url, verification_id
+            ))
+            .json(answer)
+            .send()?;
+        Ok(check_response(resp)?.json()?)
+    }
+}
+
+enum Mode {
+    Browse,
+    /// Typing the comment sent with a rejection
+    Comment {
+        button: usize,
+        input: String,
+    },
+}
+
+struct Console {
+    queue: Queue,
+    waiting: Vec<PendingVerification>,
+    /// Deferred here without a defer button: still pending, listed last
+    deferred: HashSet<String>,
+    list: ListState,
+    mode: Mode,
+    status: String,
+}
+
+pub fn run() -> anyhow::Result<()> {
+    let config = NetworkConfig::load()?;
+    let binding = admin_binding(&config).ok_or_else(|| {
+        anyhow::anyhow!("chase console needs an enabled loopback port; the console is not served on LAN or public bindings")
+    })?;
+    let queue = Queue {
+        base_url: binding.base_url(),
+        client: admin_client(
+            binding,
+            reqwest::blocking::Client::builder().timeout(REQUEST_TIMEOUT),
+        )?,
+    };
+    // Fail before taking over the terminal, e.g. when the instance uses another prompter
+    let waiting = queue.list()?;
+
+    let mut terminal = ratatui::init();
+    let result = Console::new(queue, waiting).run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl Console {
+    fn new(queue: Queue, waiting: Vec<PendingVerification>) -> Self {
+        let mut console = Self {
+            queue,
+            waiting: Vec::new(),
+            deferred: HashSet::new(),
+            list: ListState::default(),
+            mode: Mode::Browse,
+            status: String::new(),
+        };
+        console.set_waiting(waiting);
+        console
+    }
+
+    fn run(mut self, terminal: &mut DefaultTerminal) -> anyhow::Result<()> {
+        let mut refreshed = Instant::now();
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            let timeout = REFRESH_INTERVAL.saturating_sub(refreshed.elapsed());
+            if event::poll(timeout)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press && self.handle_key(key.code) {
+                        return Ok(());
+                    }
+                }
+            }
+            if refreshed.elapsed() >= REFRESH_INTERVAL {
+                self.refresh();
+                refreshed = Instant::now();
+            }
+        }
+    }
+
+    fn refresh(&mut self) {
+        match self.queue.list() {
+            Ok(waiting) => self.set_waiting(waiting),
+            Err(e) => self.status = format!("⚠ {}", e),
+        }
+    }
+
+    /// Replace the queue, keeping the selection on the same verification when it is still waiting
+    fn set_waiting(&mut self, mut waiting: Vec<PendingVerification>) {
+        let selected_id = self.selected().map(|p| p.verification_id.clone());
+        self.deferred
+            .retain(|id| waiting.iter().any(|p| &p.verification_id == id));
+        waiting.sort_by_key(|p| self.deferred.contains(&p.verification_id));
+        self.waiting = waiting;
+
+        let index = selected_id
+            .and_then(|id| self.waiting.iter().position(|p| p.verification_id == id))
+            .unwrap_or(0);
+        self.list
+            .select((!self.waiting.is_empty()).then_some(index.min(self.waiting.len() - 1)));
+    }
+
+    fn selected(&self) -> Option<&PendingVerification> {
+        self.list.selected().and_then(|i| self.waiting.get(i))
+    }
+
+    /// Returns `true` when the operator quits
+    fn handle_key(&mut self, code: KeyCode) -> bool {
+        if let Mode::Comment { button, input } = &mut self.mode {
+            match code {
+                KeyCode::Enter => {
+                    let button = *button;
+                    let message = Some(input.trim().to_string()).filter(|m| !m.is_empty());
+                    self.mode = Mode::Browse;
+                    self.answer(button, message);
+                }
+                KeyCode::Esc => self.mode = Mode::Browse,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
+            }
+            return false;
+        }
+
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return true,
+            KeyCode::Down | KeyCode::Char('j') => self.list.select_next(),
+            KeyCode::Up | KeyCode::Char('k') => self.list.select_previous(),
+            KeyCode::Char('a') => self.answer_with(ButtonDecision::Approve),
+            KeyCode::Char('s') => self.answer_with(ButtonDecision::ApproveSession),
+            KeyCode::Char('r') => {
+                if let Some(button) = self.button_for(ButtonDecision::Reject) {
+                    self.mode = Mode::Comment {
+                        button,
+                        input: String::new(),
+                    };
+                }
+            }
+            KeyCode::Char('d') => self.defer(),
+            KeyCode::Char(c @ '1'..='9') => {
+                let index = c as usize - '1' as usize;
+                let offered = self
+                    .selected()
+                    .is_some_and(|p| index < p.request.buttons().len());
+                if offered {
+                    self.answer(index, None);
+                } else {
+                    self.status = format!("No button {}", c);
+                }
+            }
+            _ => {}
+        }
+        false
+    }
+
+    /// Index of the selected request's button with `decision`, noting in the status line if none
+    fn button_for(&mut self, decision: ButtonDecision) -> Option<usize> {
+        let selected = self.selected()?;
+        let index = selected
+            .request
+            .buttons()
+            .iter()
+            .position(|b| b.decision() == Some(decision));
+        if index.is_none() {
+            let kind = match decision {
+                ButtonDecision::Approve => "approve",
+                ButtonDecision::ApproveSession => "session",
+                ButtonDecision::Reject => "reject",
+                ButtonDecision::Defer => "defer",
+            };
+            self.status = format!(
+                "{} offers no {} button; pick one by number",
+                selected.verification_id, kind
+            );
+        }
+        index
+    }
+
+    fn answer_with(&mut self, decision: ButtonDecision) {
+        if let Some(button) = self.button_for(decision) {
+            self.answer(button, None);
+        }
+    }
+
+    /// Answer with the request's defer button, or keep it pending and move it to the end
+    fn defer(&mut self) {
+        let Some(selected) = self.selected() else {
+            return;
+        };
+        let id = selected.verification_id.clone();
+        let defer_button = selected
+            .request
+            .buttons()
+            .iter()
+            .position(|b| b.decision() == Some(ButtonDecision::Defer));
+        match defer_button {
+            Some(button) => self.answer(button, None),
+            None => {
+                self.deferred.insert(id.clone());
+                self.status = format!("{} left pending for later", id);
+                // Select whatever now heads the queue
+                let waiting = std::mem::take(&mut self.waiting);
+                self.list.select(None);
+                self.set_waiting(waiting);
+            }
+        }
+    }
+
+    fn answer(&mut self, button: usize, message: Option<String>) {
+        let Some(id) = self.selected().map(|p| p.verification_id.clone()) else {
+            return;
+        };
+        self.status = match self.queue.answer(&id, &ConsoleAnswer { button, message }) {
+            Ok(response) => format!("{}: {}", id, response.status),
+            Err(e) => format!("⚠ {}: {}", id, e),
+        };
+        self.refresh();
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, footer] =
+            Layout::vertical([Constraint::Min(5), Constraint::Length(3)]).areas(frame.area());
+        let [list_area, detail_area] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(main);
+
+        let now = chrono::Utc::now();
+        let items: Vec<ListItem> = self
+            .waiting
+            .iter()
+            .map(|p| {
+                let mut line = format!(
+                    "{:>4}  {}",
+                    format_remaining(now - p.created_at),
+                    p.request.action
+                );
+                if self.deferred.contains(&p.verification_id) {
+                    line.push_str("  (deferred)");
+                }
+                ListItem::new(line)
+            })
+            .collect();
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!(" Waiting ({}) ", self.waiting.len())),
+            )
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, list_area, &mut self.list);
+
+        let detail = match self.selected() {
+            Some(selected) => detail_lines(selected),
+            None => vec![Line::from("No verifications are waiting.")],
+        };
+        frame.render_widget(
+            Paragraph::new(detail)
+                .wrap(Wrap { trim: false })
+                .block(Block::default().borders(Borders::ALL).title(" Details ")),
+            detail_area,
+        );
+
+        let help = match &self.mode {
+            Mode::Browse => {
+                "↑/↓ select  a approve  s session  r reject  d defer  1-9 button  q quit"
+                    .to_string()
+            }
+            Mode::Comment { input, .. } => {
+                format!("Reject comment: {}▏ (Enter send, Esc cancel)", input)
+            }
+        };
+        frame.render_widget(
+            Paragraph::new(vec![Line::from(help), Line::from(self.status.as_str())])
+                .block(Block::default().borders(Borders::TOP).title(" ChaseAI ")),
+            footer,
+        );
+    }
+}
+
+/// Action, reason, scope, pretty-printed context and the buttons of one verification
+fn detail_lines(pending: &PendingVerification) -> Vec<Line<'static>> {
+    let request = &pending.request;
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let mut lines = vec![
+        Line::styled(pending.verification_id.clone(), bold),
+        Line::from(format!(
+            "Requester: {}",
+            request.requester.as_deref().unwrap_or("-")
+        )),
+        Line::from(format!("Task:      {}", request.task_id().unwrap_or("-"))),
+        Line::from(format!(
+            "Type:      {}",
+            request.action_type.as_deref().unwrap_or("-")
+        )),
+        Line::from(""),
+        Line::styled("Action", bold),
+        Line::from(request.action.clone()),
+        Line::from(""),
+        Line::styled("Reason", bold),
+    ];
+    lines.extend(request.reason.lines().map(|l| Line::from(l.to_string())));
+
+    let buttons = request.buttons();
+    for button in buttons
+        .iter()
+        .filter(|b| b.decision() == Some(ButtonDecision::ApproveSession))
+    {
+        lines.push(Line::from(""));
+        lines.push(Line::styled(
+            format!("Session scope ({})", button.label()),
+            bold,
+        ));
+        lines.push(Line::from(request.session_scope_for(button).describe()));
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::styled("Context", bold));
+    let context = request
+        .context
+        .as_ref()
+        .and_then(|c| serde_json::to_string_pretty(c).ok())
+        .unwrap_or_else(|| "{}".to_string());
+    lines.extend(context.lines().map(|l| Line::from(l.to_string())));
+
+    lines.push(Line::from(""));
+    lines.push(Line::styled("Buttons", bold));
+    lines.extend(
+        buttons
+            .iter()
+            .enumerate()
+            .map(|(i, b)| Line::from(format!("{}) {}{}", i + 1, b.label(), describe(b)))),
+    );
+    lines
+}
+
+fn describe(button: &VerificationButton) -> String {
+    match button.decision() {
+        Some(decision) => format!("  [{}]", decision.status()),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,405 @@
+use crate::cli::target::Target;
+use app::config::network_config::NetworkConfig;
+use app::instruction::context::ContextErrors;
+use app::instruction::history::{diff, ContextRevision};
+use app::instruction::storage::ContextKey;
+use app::instruction::template::ContextTemplate;
+use app::instruction::variables::ContextVariables;
+use app::server::admin::{BaseContext, PortContext};
+use clap::{Subcommand, ValueEnum};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+#[derive(Subcommand)]
+pub enum ContextCommand {
+    /// List ports that have an instruction context
+    List {
+        /// Print raw JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print a port's instruction context as written
+    #[command(visible_alias = "get")]
+    Show {
+        port: u16,
+        /// The context of this agent on the port instead of the port's own
+        #[arg(long)]
+        agent: Option<String>,
+        /// Print the context agents get, with its bases applied; for --agent, the port's
+        /// context if the agent has none of its own
+        #[arg(long)]
+        resolved: bool,
+        #[arg(long, value_enum, default_value = "json")]
+        format: Format,
+    },
+    /// Set a port's instruction context from a JSON, YAML or TOML file (`-` reads stdin)
+    Set {
+        port: u16,
+        file: PathBuf,
+        /// Only for this agent; the others keep the port's context
+        #[arg(long)]
+        agent: Option<String>,
+        /// Format of FILE; guessed from its extension, JSON for stdin
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+    },
+    /// Remove a port's instruction context
+    Delete {
+        port: u16,
+        /// Remove only this agent's context, which then falls back to the port's
+        #[arg(long)]
+        agent: Option<String>,
+    },
+    /// List context revisions, newest last
+    History {
+        /// Only this port's revisions
+        port: Option<u16>,
+        /// Print raw JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show what revision REV changed, or everything between REV and TO
+    Diff { rev: u64, to: Option<u64> },
+    /// Give REV's port the context it had at REV, as a new revision
+    Rollback { rev: u64 },
+    /// Manage named base contexts that port contexts can `extends`
+    Base {
+        #[command(subcommand)]
+        command: BaseCommand,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BaseCommand {
+    /// List base contexts
+    List {
+        /// Print raw JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Add or replace a base context from a JSON, YAML or TOML file (`-` reads stdin)
+    Set {
+        name: String,
+        file: PathBuf,
+        /// Format of FILE; guessed from its extension, JSON for stdin
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+    },
+    /// Remove a base context no port or base extends
+    Delete { name: String },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> anyhow::Result<Self> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Ok(Self::Json),
+            Some("yaml" | "yml") => Ok(Self::Yaml),
+            Some("toml") => Ok(Self::Toml),
+            _ => Err(anyhow::anyhow!(
+                "Cannot tell the format of {}; pass --format json, yaml or toml",
+                path.display()
+            )),
+        }
+    }
+
+    fn parse(self, text: &str) -> anyhow::Result<ContextTemplate> {
+        Ok(match self {
+            Self::Json => serde_json::from_str(text)?,
+            Self::Yaml => serde_yaml::from_str(text)?,
+            Self::Toml => toml::from_str(text)?,
+        })
+    }
+
+    fn render(self, context: &impl Serialize) -> anyhow::Result<String> {
+        Ok(match self {
+            Self::Json => serde_json::to_string_pretty(context)?,
+            Self::Yaml => serde_yaml::to_string(context)?,
+            Self::Toml => toml::to_string_pretty(context)?,
+        })
+    }
+}
+
+pub fn run(command: ContextCommand) -> anyhow::Result<()> {
+    let mut target = Target::connect()?;
+
+    match command {
+        ContextCommand::List { json } => {
+            let contexts = target.contexts()?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&contexts)?);
+            } else {
+                print_table(&contexts);
+            }
+        }
+        ContextCommand::Show {
+            port,
+            agent,
+            resolved,
+            format,
+        } => {
+            let key = ContextKey { port, agent };
+            let contexts = target.contexts()?;
+            let find = |key: &ContextKey| contexts.iter().find(|c| c.key() == *key);
+            let context = find(&key)
+                .or_else(|| resolved.then(|| find(&key.port_key())).flatten())
+                .ok_or_else(|| anyhow::anyhow!("No instruction context for {}", key))?;
+            let text = if resolved {
+                let resolved = context
+                    .resolved
+                    .as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("The context for {} does not resolve", key))?;
+                format.render(resolved)?
+            } else {
+                format.render(&context.context)?
+            };
+            println!("{}", text.trim_end());
+        }
+        ContextCommand::Set {
+            port,
+            file,
+            agent,
+            format,
+        } => {
+            let context = read_context(&file, format)?;
+            // Without a base the file must stand on its own; report every problem
+            // before anything is sent or saved
+            if context.extends.is_none() {
+                let variables = ContextVariables::from_config(&NetworkConfig::load()?);
+                if let Err(ContextErrors(errors)) = context.resolve(&HashMap::new(), &variables) {
+                    for error in &errors {
+                        eprintln!("  {}: {}", error.field, error.message);
+                    }
+                    anyhow::bail!("Invalid context in {}", file.display());
+                }
+            }
+            let key = ContextKey { port, agent };
+            target.set_context(key.clone(), context)?;
+            println!("Set context for {} in {}", key, target.describe());
+        }
+        ContextCommand::Delete { port, agent } => {
+            let key = ContextKey { port, agent };
+            target.delete_context(key.clone())?;
+            println!("Deleted context for {} in {}", key, target.describe());
+        }
+        ContextCommand::History { port, json } => {
+            let revisions = target.context_history(port)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&revisions)?);
+            } else {
+                print_history(&revisions);
+            }
+        }
+        ContextCommand::Diff { rev, to } => {
+            let revisions = target.context_history(None)?;
+            let find = |rev: u64| {
+                revisions
+                    .iter()
+                    .find(|r| r.rev == rev)
+                    .ok_or_else(|| anyhow::anyhow!("Revision {} does not exist", rev))
+            };
+            let (before, after) = match to {
+                Some(to) => (Some(find(rev)?), find(to)?),
+                None => {
+                    let after = find(rev)?;
+                    (previous(&revisions, after), after)
+                }
+            };
+            print_diff(before, after);
+        }
+        ContextCommand::Base { command } => run_base(&mut target, command)?,
+        ContextCommand::Rollback { rev } => {
+            let revision = target.rollback_context(rev)?;
+            println!(
+                "The context for {} is back to revision {} as revision {} in {}",
+                revision.target(),
+                rev,
+                revision.rev,
+                target.describe()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn run_base(target: &mut Target, command: BaseCommand) -> anyhow::Result<()> {
+    match command {
+        BaseCommand::List { json } => {
+            let bases = target.bases()?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&bases)?);
+                return Ok(());
+            }
+            if bases.is_empty() {
+                println!("No base contexts");
+                return Ok(());
+            }
+            println!("{:<16} {:<16} {:<20} ACTIONS", "NAME", "EXTENDS", "ROLE");
+            for BaseContext { name, context } in bases {
+                println!(
+                    "{:<16} {:<16} {:<20} {}",
+                    name,
+                    context.extends.as_deref().unwrap_or("-"),
+                    context.role.as_deref().unwrap_or("-"),
+                    context.allowed_actions.join(",")
+                );
+            }
+        }
+        BaseCommand::Set { name, file, format } => {
+            target.set_base(name.clone(), read_context(&file, format)?)?;
+            println!("Set base context '{}' in {}", name, target.describe());
+        }
+        BaseCommand::Delete { name } => {
+            target.delete_base(&name)?;
+            println!("Deleted base context '{}' in {}", name, target.describe());
+        }
+    }
+    Ok(())
+}
+
+/// The revision of the same port and agent, or the same base, before `revision`, if any
+fn previous<'a>(
+    revisions: &'a [ContextRevision],
+    revision: &ContextRevision,
+) -> Option<&'a ContextRevision> {
+    revisions
+        .iter()
+        .filter(|r| r.target() == revision.target() && r.rev < revision.rev)
+        .max_by_key(|r| r.rev)
+}
+
+fn print_history(revisions: &[ContextRevision]) {
+    if revisions.is_empty() {
+        println!("No context revisions");
+        return;
+    }
+
+    println!(
+        "{:<6} {:<7} {:<12} {:<20} {:<7} CHANGE",
+        "REV", "PORT", "AGENT/BASE", "WHEN", "AUTHOR"
+    );
+    for revision in revisions {
+        let before = previous(revisions, revision).and_then(|r| r.context.as_ref());
+        let change = match (before, &revision.context) {
+            (_, None) => "deleted".to_string(),
+            (None, Some(_)) => "created".to_string(),
+            (Some(before), Some(after)) => diff(Some(before), Some(after))
+                .into_iter()
+                .map(|c| c.field)
+                .collect::<Vec<_>>()
+                .join(", "),
+        };
+        let change = match revision.rollback_of {
+            Some(rev) => format!("rollback to {} ({})", rev, change),
+            None => change,
+        };
+        println!(
+            "{:<6} {:<7} {:<12} {:<20} {:<7} {}",
+            revision.rev,
+            revision
+                .port
+                .map_or_else(|| "base".to_string(), |port| port.to_string()),
+            revision
+                .agent
+                .as_deref()
+                .or(revision.base.as_deref())
+                .unwrap_or("-"),
+            revision.at.format("%Y-%m-%d %H:%M:%S"),
+            format!("{:?}", revision.author).to_lowercase(),
+            change
+        );
+    }
+}
+
+fn print_diff(before: Option<&ContextRevision>, after: &ContextRevision) {
+    match before {
+        Some(before) => println!("--- revision {} ({})", before.rev, before.target()),
+        None => println!("--- no context"),
+    }
+    println!("+++ revision {} ({})", after.rev, after.target());
+
+    let changes = diff(
+        before.and_then(|r| r.context.as_ref()),
+        after.context.as_ref(),
+    );
+    if changes.is_empty() {
+        println!("No changes");
+    }
+    for change in changes {
+        if let Some(value) = change.before {
+            println!("- {}: {}", change.field, value);
+        }
+        if let Some(value) = change.after {
+            println!("+ {}: {}", change.field, value);
+        }
+    }
+}
+
+fn read_context(file: &Path, format: Option<Format>) -> anyhow::Result<ContextTemplate> {
+    let (text, format) = if file == Path::new("-") {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text)?;
+        (text, format.unwrap_or(Format::Json))
+    } else {
+        let format = match format {
+            Some(format) => format,
+            None => Format::from_path(file)?,
+        };
+        (std::fs::read_to_string(file)?, format)
+    };
+    format
+        .parse(&text)
+        .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", file.display(), e))
+}
+
+fn print_table(contexts: &[PortContext]) {
+    if contexts.is_empty() {
+        println!("No instruction contexts");
+        return;
+    }
+
+    println!(
+        "{:<7} {:<12} {:<16} {:<16} {:<20} {:<8} ACTIONS",
+        "PORT", "AGENT", "EXTENDS", "SYSTEM", "ROLE", "VERIFY"
+    );
+    for PortContext {
+        port,
+        agent,
+        context,
+        resolved,
+    } in contexts
+    {
+        let agent = agent.as_deref().unwrap_or("-");
+        let extends = context.extends.as_deref().unwrap_or("-");
+        let Some(resolved) = resolved else {
+            println!(
+                "{:<7} {:<12} {:<16} (does not resolve)",
+                port, agent, extends
+            );
+            continue;
+        };
+        println!(
+            "{:<7} {:<12} {:<16} {:<16} {:<20} {:<8} {}",
+            port,
+            agent,
+            extends,
+            resolved.system,
+            resolved.role,
+            if resolved.verification_required {
+                "yes"
+            } else {
+                "no"
+            },
+            resolved.allowed_actions.join(",")
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use app::config::network_config::NetworkConfig;
+use app::daemon::{Daemon, RuntimeFiles};
+use app::verification::prompter::PrompterConfig;
+use clap::{Args, ValueEnum};
+
+/// How long `--detach` waits for the background daemon to report its ports
+#[cfg(unix)]
+const DETACH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// Log of a detached daemon, beside `network.toml`
+#[cfg(unix)]
+const LOG_FILE_NAME: &str = "daemon.log";
+
+#[derive(Args)]
+pub struct DaemonArgs {
+    /// Run in the background and return once the servers are up
+    #[arg(long)]
+    detach: bool,
+    /// Decide verifications on this terminal or from `chase console`, instead of [prompter] in network.toml
+    #[arg(long, value_enum, conflicts_with = "prompt_command")]
+    prompter: Option<PrompterKind>,
+    /// Decide verifications with an external program (see the `command` prompter)
+    #[arg(long, value_name = "PROGRAM")]
+    prompt_command: Option<String>,
+    /// Argument for --prompt-command, repeat for several
+    #[arg(long = "prompt-arg", value_name = "ARG", requires = "prompt_command")]
+    prompt_args: Vec<String>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum PrompterKind {
+    Terminal,
+    Console,
+}
+
+pub fn run(args: DaemonArgs) -> anyhow::Result<()> {
+    let config = NetworkConfig::load()?;
+    let prompter = args.prompter(&config.prompter)?;
+
+    if let Some(pid) = RuntimeFiles::new()?.running_pid() {
+        anyhow::bail!("ChaseAI is already running (pid {})", pid);
+    }
+    if args.detach {
+        return detach(&prompter);
+    }
+    Daemon::new(config)?.with_prompter(prompter).run()
+}
+
+impl DaemonArgs {
+    fn prompter(&self, configured: &PrompterConfig) -> anyhow::Result<PrompterConfig> {
+        let prompter = match (&self.prompt_command, self.prompter) {
+            (Some(program), _) => PrompterConfig::Command {
+                program: program.clone(),
+                args: self.prompt_args.clone(),
+            },
+            (None, Some(PrompterKind::Terminal)) => PrompterConfig::Terminal,
+            (None, Some(PrompterKind::Console)) => PrompterConfig::Console,
+            (None, None) => configured.clone(),
+        };
+        if !self.detach {
+            return Ok(prompter);
+        }
+
+        // A detached daemon has no controlling terminal to ask on
+        match prompter {
+            PrompterConfig::Terminal => Err(anyhow::anyhow!(
+                "A detached daemon has no terminal to ask on; use --prompter console or --prompt-command"
+            )),
+            PrompterConfig::Auto if !cfg!(target_os = "macos") => {
+                println!("Answer verifications with `chase console`");
+                Ok(PrompterConfig::Console)
+            }
+            other => Ok(other),
+        }
+    }
+}
+
+/// Start `chase daemon` again in a new session with the resolved prompter, its output
+/// appended to the log, and wait until it has written its runtime file
+#[cfg(unix)]
+fn detach(prompter: &PrompterConfig) -> anyhow::Result<()> {
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    let files = RuntimeFiles::new()?;
+    let log_path = NetworkConfig::config_path()?.with_file_name(LOG_FILE_NAME);
+    let log = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)?;
+
+    let mut command = Command::new(std::env::current_exe()?);
+    command.arg("daemon");
+    match prompter {
+        PrompterConfig::Console => {
+            command.args(["--prompter", "console"]);
+        }
+        PrompterConfig::Command { program, args } => {
+            command.args(["--prompt-command", program]);
+            for arg in args {
+                command.arg("--prompt-arg").arg(arg);
+            }
+        }
+        // Anything else is read from network.toml again
+        _ => {}
+    }
+    command
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+    // Leave the terminal's session so closing it (or the SSH connection) doesn't stop the daemon
+    // SAFETY: the hook runs in the forked child before exec, where only async-signal-safe
+    // calls are allowed; it calls setsid and reads errno, both of which are, and allocates nothing
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let mut child = command.spawn()?;
+
+    let started = std::time::Instant::now();
+    while started.elapsed() < DETACH_TIMEOUT {
+        if let Some(status) = child.try_wait()? {
+            anyhow::bail!(
+                "ChaseAI daemon exited with {}; see {}",
+                status,
+                log_path.display()
+            );
+        }
+        if let Some(info) = files.read().ok().flatten().filter(|i| i.pid == child.id()) {
+            println!("ChaseAI daemon started (pid {})", info.pid);
+            for port in &info.ports {
+                println!("Serving {:?} port on {}", port.role, port.base_url);
+            }
+            println!("Logs: {}", log_path.display());
+            return Ok(());
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    anyhow::bail!(
+        "ChaseAI daemon (pid {}) did not report ready within {}s; see {}",
+        child.id(),
+        DETACH_TIMEOUT.as_secs(),
+        log_path.display()
+    )
+}
+
+#[cfg(not(unix))]
+fn detach(_prompter: &PrompterConfig) -> anyhow::Result<()> {
+    anyhow::bail!("--detach is only supported on Unix; run `chase daemon` under a service manager")
+}
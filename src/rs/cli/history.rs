@@ -0,0 +1,67 @@
+use crate::cli::target::Target;
+use app::instruction::storage::ContextStorage;
+use app::verification::audit::AuditLog;
+use clap::Subcommand;
+
+#[derive(Subcommand)]
+pub enum HistoryCommand {
+    /// Walk the verification history hash chain and report the first broken link; with
+    /// ChaseAI running, also check the chain ends where the instance says it wrote it
+    Verify,
+}
+
+pub fn run(command: HistoryCommand) -> anyhow::Result<()> {
+    match command {
+        HistoryCommand::Verify => verify(),
+    }
+}
+
+fn verify() -> anyhow::Result<()> {
+    // Read the file directly so the check works while ChaseAI is not running
+    let storage = ContextStorage::new()?;
+    let log = AuditLog::in_dir(&storage.config_dir());
+    let anchor = match Target::connect() {
+        Ok(mut target) => target.audit_anchor()?,
+        Err(e) => {
+            eprintln!("⚠️  {}", e);
+            None
+        }
+    };
+    let report = match &anchor {
+        Some(anchor) => log.verify_against(anchor)?,
+        None => log.verify_chain()?,
+    };
+
+    match report.broken {
+        None => {
+            println!(
+                "✅ {}: {} record(s), chain intact (head {})",
+                log.path().display(),
+                report.verified,
+                report.head
+            );
+            if anchor.is_none() {
+                println!(
+                    "   ChaseAI is not running, so only the links were checked: records dropped"
+                );
+                println!("   from the end, or a chain rebuilt with audit.key, go unnoticed");
+            }
+            Ok(())
+        }
+        Some(link) => {
+            println!(
+                "❌ {}: chain broken at line {}{}",
+                log.path().display(),
+                link.line,
+                link.verification_id
+                    .map(|id| format!(" ({})", id))
+                    .unwrap_or_default()
+            );
+            println!("   {}", link.reason);
+            println!("   {} record(s) before it verified", report.verified);
+            Err(anyhow::anyhow!(
+                "Verification history has been tampered with"
+            ))
+        }
+    }
+}
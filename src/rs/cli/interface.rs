@@ -0,0 +1,55 @@
+use crate::cli::target::Target;
+use app::server::admin::AdminInterface;
+use clap::{Args, Subcommand};
+
+#[derive(Args)]
+pub struct InterfaceArgs {
+    /// Print JSON instead of a table
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: InterfaceCommand,
+}
+
+#[derive(Subcommand)]
+enum InterfaceCommand {
+    /// List the interfaces detected on this machine
+    List,
+    /// Serve every port on the interface with this name, e.g. `lo0` or `en0`
+    Use { name: String },
+}
+
+pub fn run(args: InterfaceArgs) -> anyhow::Result<()> {
+    let mut target = Target::connect()?;
+
+    match args.command {
+        InterfaceCommand::List => print(&target.interface()?, args.json),
+        InterfaceCommand::Use { name } => {
+            let interface = target.use_interface(name)?;
+            print(&interface, args.json)?;
+            if !args.json {
+                println!("Saved to {}", target.describe());
+            }
+            Ok(())
+        }
+    }
+}
+
+fn print(interface: &AdminInterface, json: bool) -> anyhow::Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(interface)?);
+        return Ok(());
+    }
+
+    println!("Default: {:?}", interface.default_interface);
+    println!("{:<12} {:<10} ADDRESS", "NAME", "TYPE");
+    for available in &interface.available {
+        println!(
+            "{:<12} {:<10} {}",
+            available.name,
+            format!("{:?}", available.interface_type),
+            available.ip_address
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,125 @@
+pub mod agents;
+pub mod console;
+pub mod context;
+pub mod daemon;
+pub mod history;
+pub mod interface;
+pub mod mode;
+pub mod ports;
+pub mod sessions;
+pub mod target;
+pub mod token;
+
+use app::config::network_config::NetworkConfig;
+use app::network::port_config::{PortBinding, PortRole};
+use app::server::auth::{admin_token, cli_token, CLI_TOKEN_HEADER};
+use app::server::tls::{TlsStore, CLI_AGENT_NAME};
+
+/// First enabled verification binding, which is how the CLI reaches the running instance
+pub fn verification_binding(config: &NetworkConfig) -> anyhow::Result<&PortBinding> {
+    config
+        .port_bindings
+        .iter()
+        .find(|b| b.role == PortRole::Verification && b.enabled)
+        .ok_or_else(|| {
+            anyhow::anyhow!("No enabled verification port found. Is ChaseAI running and configured with a verification port?")
+        })
+}
+
+/// Base URL of the running instance's verification port
+pub fn instance_url(config: &NetworkConfig) -> anyhow::Result<String> {
+    Ok(verification_binding(config)?.base_url())
+}
+
+/// HTTP client that sends the verification binding's bearer token with every request
+/// and trusts the local CA when the binding is served over HTTPS. On mTLS ports it
+/// presents the `chase-cli` client certificate.
+pub fn instance_client(
+    config: &NetworkConfig,
+    builder: reqwest::blocking::ClientBuilder,
+) -> anyhow::Result<reqwest::blocking::Client> {
+    binding_client(verification_binding(config)?, builder)
+}
+
+/// Like [`instance_client`], for any binding
+pub fn binding_client(
+    binding: &PortBinding,
+    builder: reqwest::blocking::ClientBuilder,
+) -> anyhow::Result<reqwest::blocking::Client> {
+    client_with_token(
+        binding,
+        binding.token.as_deref(),
+        Default::default(),
+        builder,
+    )
+}
+
+/// Enabled loopback binding the admin API is served on, the verification port first
+pub fn admin_binding(config: &NetworkConfig) -> Option<&PortBinding> {
+    config
+        .port_bindings
+        .iter()
+        .filter(|b| b.enabled && b.is_loopback())
+        .min_by_key(|b| b.role != PortRole::Verification)
+}
+
+/// Client for the admin routes on `binding`, sending the admin secret from the config
+/// directory instead of the binding's token, and the CLI's own secret so context changes
+/// are recorded as the CLI's
+pub fn admin_client(
+    binding: &PortBinding,
+    builder: reqwest::blocking::ClientBuilder,
+) -> anyhow::Result<reqwest::blocking::Client> {
+    let config_path = NetworkConfig::config_path()?;
+    let dir = config_path
+        .parent()
+        .ok_or_else(|| anyhow::anyhow!("Config path has no parent directory"))?;
+    let mut headers = reqwest::header::HeaderMap::new();
+    let mut cli = reqwest::header::HeaderValue::from_str(&cli_token(dir)?)?;
+    cli.set_sensitive(true);
+    headers.insert(CLI_TOKEN_HEADER, cli);
+    client_with_token(binding, Some(&admin_token(dir)?), headers, builder)
+}
+
+fn client_with_token(
+    binding: &PortBinding,
+    token: Option<&str>,
+    mut headers: reqwest::header::HeaderMap,
+    mut builder: reqwest::blocking::ClientBuilder,
+) -> anyhow::Result<reqwest::blocking::Client> {
+    if binding.uses_tls() {
+        let store = TlsStore::new()?;
+        let ca = store.ca_cert_pem()?;
+        builder = builder.add_root_certificate(reqwest::Certificate::from_pem(ca.as_bytes())?);
+        if binding.mtls {
+            let agent = store.agent(CLI_AGENT_NAME)?;
+            builder = builder.identity(reqwest::Identity::from_pkcs8_pem(
+                &std::fs::read(&agent.cert_path)?,
+                &std::fs::read(&agent.key_path)?,
+            )?);
+        }
+    }
+
+    if let Some(token) = token {
+        let mut value = reqwest::header::HeaderValue::from_str(&format!("Bearer {}", token))?;
+        value.set_sensitive(true);
+        headers.insert(reqwest::header::AUTHORIZATION, value);
+    }
+    Ok(builder.default_headers(headers).build()?)
+}
+
+/// Turn a non-success HTTP response into an error carrying the server's message
+pub fn check_response(
+    resp: reqwest::blocking::Response,
+) -> anyhow::Result<reqwest::blocking::Response> {
+    if resp.status().is_success() {
+        return Ok(resp);
+    }
+    let status = resp.status();
+    let text = resp.text().unwrap_or_else(|_| "Unknown error".to_string());
+    Err(anyhow::anyhow!(
+        "Request failed with status {}: {}",
+        status,
+        text
+    ))
+}
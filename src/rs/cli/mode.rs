@@ -0,0 +1,54 @@
+use crate::cli::target::Target;
+use app::config::network_config::VerificationMode;
+use app::server::admin::AdminMode;
+use clap::{Args, ValueEnum};
+
+#[derive(Args)]
+pub struct ModeArgs {
+    /// Switch to this mode; prints the current one when omitted
+    #[arg(value_enum)]
+    mode: Option<Mode>,
+    /// Print JSON instead of text
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Mode {
+    /// Agents call the verification port
+    Port,
+    /// Agents run `chase --verification`
+    Cli,
+}
+
+impl From<Mode> for VerificationMode {
+    fn from(mode: Mode) -> Self {
+        match mode {
+            Mode::Port => VerificationMode::Port,
+            Mode::Cli => VerificationMode::Cli,
+        }
+    }
+}
+
+pub fn run(args: ModeArgs) -> anyhow::Result<()> {
+    let mut target = Target::connect()?;
+
+    let mode = match args.mode {
+        Some(mode) => target.set_mode(mode.into())?,
+        None => target.mode()?,
+    };
+    print(&mode, args.json)?;
+    if args.mode.is_some() && !args.json {
+        println!("Saved to {}", target.describe());
+    }
+    Ok(())
+}
+
+fn print(mode: &AdminMode, json: bool) -> anyhow::Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(mode)?);
+    } else {
+        println!("Verification mode: {:?}", mode.mode);
+    }
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use crate::cli::target::Target;
+use app::network::port_config::PortRole;
+use app::server::admin::{AdminPort, NewPort, PortUpdate};
+use clap::{Args, Subcommand, ValueEnum};
+
+#[derive(Args)]
+pub struct PortsArgs {
+    /// Print JSON instead of a table
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: PortsCommand,
+}
+
+#[derive(Subcommand)]
+enum PortsCommand {
+    /// List configured ports
+    List,
+    /// Add a port on the default interface
+    Add {
+        port: u16,
+        #[arg(long, value_enum, default_value = "instruction")]
+        role: Role,
+        /// Add the port without serving it yet
+        #[arg(long)]
+        disabled: bool,
+    },
+    /// Remove a port
+    Remove { port: u16 },
+    /// Serve a port, or every port with --all
+    Enable {
+        #[arg(required_unless_present = "all")]
+        port: Option<u16>,
+        #[arg(long, conflicts_with = "port")]
+        all: bool,
+    },
+    /// Stop serving a port, or every port with --all
+    Disable {
+        #[arg(required_unless_present = "all")]
+        port: Option<u16>,
+        #[arg(long, conflicts_with = "port")]
+        all: bool,
+    },
+    /// Change what a port serves
+    Role {
+        port: u16,
+        #[arg(value_enum)]
+        role: Role,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Role {
+    Instruction,
+    Verification,
+}
+
+impl From<Role> for PortRole {
+    fn from(role: Role) -> Self {
+        match role {
+            Role::Instruction => PortRole::Instruction,
+            Role::Verification => PortRole::Verification,
+        }
+    }
+}
+
+pub fn run(args: PortsArgs) -> anyhow::Result<()> {
+    let mut target = Target::connect()?;
+
+    let ports = match args.command {
+        PortsCommand::List => return print(&target.ports()?, args.json),
+        PortsCommand::Add {
+            port,
+            role,
+            disabled,
+        } => vec![target.add_port(NewPort {
+            port,
+            role: role.into(),
+            enabled: !disabled,
+        })?],
+        PortsCommand::Remove { port } => target.remove_port(port)?,
+        PortsCommand::Enable { port, .. } => set_enabled(&mut target, port, true)?,
+        PortsCommand::Disable { port, .. } => set_enabled(&mut target, port, false)?,
+        PortsCommand::Role { port, role } => vec![target.update_port(
+            port,
+            PortUpdate {
+                enabled: None,
+                role: Some(role.into()),
+            },
+        )?],
+    };
+
+    print(&ports, args.json)?;
+    if !args.json {
+        println!("Saved to {}", target.describe());
+    }
+    Ok(())
+}
+
+/// One port, or every port when `port` is `None` (`--all`)
+fn set_enabled(
+    target: &mut Target,
+    port: Option<u16>,
+    enabled: bool,
+) -> anyhow::Result<Vec<AdminPort>> {
+    match port {
+        Some(port) => Ok(vec![target.update_port(
+            port,
+            PortUpdate {
+                enabled: Some(enabled),
+                role: None,
+            },
+        )?]),
+        None => target.set_all_enabled(enabled),
+    }
+}
+
+fn print(ports: &[AdminPort], json: bool) -> anyhow::Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(ports)?);
+        return Ok(());
+    }
+    if ports.is_empty() {
+        println!("No ports configured");
+        return Ok(());
+    }
+
+    println!(
+        "{:<7} {:<13} {:<9} {:<12} URL",
+        "PORT", "ROLE", "ENABLED", "INTERFACE"
+    );
+    for port in ports {
+        println!(
+            "{:<7} {:<13} {:<9} {:<12} {}",
+            port.port,
+            format!("{:?}", port.role),
+            if port.enabled { "yes" } else { "no" },
+            port.interface.name,
+            port.base_url
+        );
+    }
+    Ok(())
+}
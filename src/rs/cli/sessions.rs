@@ -0,0 +1,103 @@
+use crate::cli::{admin_binding, admin_client, check_response};
+use app::config::network_config::NetworkConfig;
+use app::instruction::session::{format_remaining, Session};
+use clap::Subcommand;
+
+#[derive(Subcommand)]
+pub enum SessionsCommand {
+    /// List active sessions of the running instance
+    List {
+        /// Print raw JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Revoke one session, or all of them with --all
+    Revoke {
+        /// Session id, as `chase sessions list` shows it
+        id: Option<String>,
+        /// Revoke every active session
+        #[arg(long, conflicts_with = "id")]
+        all: bool,
+    },
+}
+
+pub fn run(command: SessionsCommand) -> anyhow::Result<()> {
+    let config = NetworkConfig::load()?;
+    let binding = admin_binding(&config).ok_or_else(|| {
+        anyhow::anyhow!("chase sessions needs an enabled loopback port; sessions are not listed on LAN or public bindings")
+    })?;
+    let base_url = binding.base_url();
+    let client = admin_client(binding, reqwest::blocking::Client::builder())?;
+
+    match command {
+        SessionsCommand::List { json } => {
+            let resp = check_response(client.get(format!("{}/sessions", base_url)).send()?)?;
+            let sessions: Vec<Session> = resp.json()?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&sessions)?);
+            } else {
+                print_table(&sessions);
+            }
+        }
+        SessionsCommand::Revoke { id: Some(id), .. } => {
+            check_response(
+                client
+                    .delete(format!("{}/sessions/{}", base_url, id))
+                    .send()?,
+            )?;
+            println!("Revoked session {}", id);
+        }
+        SessionsCommand::Revoke {
+            id: None,
+            all: true,
+        } => {
+            let resp = check_response(client.delete(format!("{}/sessions", base_url)).send()?)?;
+            let body: serde_json::Value = resp.json()?;
+            println!(
+                "Revoked {} session(s)",
+                body["revoked"].as_u64().unwrap_or(0)
+            );
+        }
+        SessionsCommand::Revoke {
+            id: None,
+            all: false,
+        } => {
+            return Err(anyhow::anyhow!(
+                "Specify a session id or --all to revoke every session"
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn print_table(sessions: &[Session]) {
+    if sessions.is_empty() {
+        println!("No active sessions");
+        return;
+    }
+
+    println!(
+        "{:<34} {:<20} {:<10} {:<8} {:<16} ACTIONS",
+        "ID", "OWNER", "REMAINING", "USES", "TASK"
+    );
+    for session in sessions {
+        let uses = match session.scope.max_uses {
+            Some(max) => format!("{}/{}", session.uses, max),
+            None => session.uses.to_string(),
+        };
+        let owner = match &session.owner.agent {
+            Some(agent) => format!("{}/{}", session.owner.port, agent),
+            None => session.owner.port.to_string(),
+        };
+        println!(
+            "{:<34} {:<20} {:<10} {:<8} {:<16} {}",
+            session.id,
+            owner,
+            format_remaining(session.remaining()),
+            uses,
+            session.scope.task_id.as_deref().unwrap_or("-"),
+            session.scope.actions.join(", ")
+        );
+    }
+}
@@ -0,0 +1,412 @@
+use crate::cli::{admin_binding, admin_client};
+use app::command::configure;
+use app::config::change::{find_interface, ConfigChange, ConfigChangeError};
+use app::config::network_config::{NetworkConfig, VerificationMode};
+use app::daemon::RuntimeFiles;
+use app::instruction::history::{ContextAuthor, ContextRevision};
+use app::instruction::manager::ContextManager;
+use app::instruction::storage::ContextKey;
+use app::instruction::template::ContextTemplate;
+use app::instruction::variables::ContextVariables;
+use app::network::port_config::generate_token;
+use app::server::admin::{
+    AdminInterface, AdminMode, AdminPort, AgentToken, AllPortsUpdate, BaseContext, InterfaceUpdate,
+    NewPort, PortContext, PortUpdate, Rollback,
+};
+use app::verification::audit::ChainAnchor;
+use reqwest::blocking::Client;
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::time::Duration;
+
+/// The admin API answers once the change is saved, before servers restart
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Where `chase ports`, `interface`, `mode` and `context` take effect
+pub enum Target {
+    /// The running tray app or daemon, through its admin API
+    Instance { client: Client, base_url: String },
+    /// `network.toml`, while ChaseAI is not running
+    File(NetworkConfig),
+}
+
+impl Target {
+    /// The running instance if its admin API answers on a loopback port, otherwise the config file
+    pub fn connect() -> anyhow::Result<Self> {
+        let config = NetworkConfig::load()?;
+        if let Some(binding) = admin_binding(&config) {
+            let client = admin_client(binding, Client::builder().timeout(REQUEST_TIMEOUT))?;
+            let base_url = binding.base_url();
+            match client.get(format!("{}/admin/mode", base_url)).send() {
+                Ok(resp) => {
+                    check(resp)?;
+                    return Ok(Self::Instance { client, base_url });
+                }
+                Err(e) if !e.is_connect() => return Err(e.into()),
+                Err(_) => {}
+            }
+        }
+        // Editing the file under a running tray app or daemon would be overwritten by its next change
+        if let Some(pid) = RuntimeFiles::new()?.running_pid() {
+            anyhow::bail!(
+                "ChaseAI (pid {}) is running but serves no loopback port for the admin API",
+                pid
+            );
+        }
+        Ok(Self::File(config))
+    }
+
+    /// Where changes went, for the confirmation line
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Instance { .. } => "the running instance".to_string(),
+            // network.toml and contexts.json live side by side
+            Self::File(_) => NetworkConfig::config_path()
+                .ok()
+                .and_then(|p| p.parent().map(|dir| dir.display().to_string()))
+                .map(|dir| format!("{} (ChaseAI is not running)", dir))
+                .unwrap_or_else(|| "the config directory".to_string()),
+        }
+    }
+
+    pub fn ports(&mut self) -> anyhow::Result<Vec<AdminPort>> {
+        match self {
+            Self::Instance { .. } => self.call(Method::GET, "/admin/ports", None::<()>),
+            Self::File(config) => Ok(AdminPort::list(config)),
+        }
+    }
+
+    pub fn add_port(&mut self, new: NewPort) -> anyhow::Result<AdminPort> {
+        match self {
+            Self::Instance { .. } => self.call(Method::POST, "/admin/ports", Some(new)),
+            Self::File(config) => {
+                let binding = config.new_binding(new.port, new.role, new.enabled);
+                configure(config, &[ConfigChange::AddPort { binding }])?;
+                port(config, new.port)
+            }
+        }
+    }
+
+    pub fn update_port(&mut self, port: u16, update: PortUpdate) -> anyhow::Result<AdminPort> {
+        match self {
+            Self::Instance { .. } => self.call(
+                Method::PATCH,
+                &format!("/admin/ports/{}", port),
+                Some(update),
+            ),
+            Self::File(config) => {
+                let mut changes = Vec::new();
+                if let Some(enabled) = update.enabled {
+                    changes.push(ConfigChange::SetPortEnabled { port, enabled });
+                }
+                if let Some(role) = update.role {
+                    changes.push(ConfigChange::SetPortRole { port, role });
+                }
+                configure(config, &changes)?;
+                self::port(config, port)
+            }
+        }
+    }
+
+    pub fn set_all_enabled(&mut self, enabled: bool) -> anyhow::Result<Vec<AdminPort>> {
+        match self {
+            Self::Instance { .. } => self.call(
+                Method::PATCH,
+                "/admin/ports",
+                Some(AllPortsUpdate { enabled }),
+            ),
+            Self::File(config) => {
+                configure(config, &[ConfigChange::SetAllEnabled { enabled }])?;
+                Ok(AdminPort::list(config))
+            }
+        }
+    }
+
+    pub fn remove_port(&mut self, port: u16) -> anyhow::Result<Vec<AdminPort>> {
+        match self {
+            Self::Instance { .. } => self.call(
+                Method::DELETE,
+                &format!("/admin/ports/{}", port),
+                None::<()>,
+            ),
+            Self::File(config) => {
+                configure(config, &[ConfigChange::RemovePort { port }])?;
+                Ok(AdminPort::list(config))
+            }
+        }
+    }
+
+    pub fn interface(&mut self) -> anyhow::Result<AdminInterface> {
+        match self {
+            Self::Instance { .. } => self.call(Method::GET, "/admin/interface", None::<()>),
+            Self::File(config) => Ok(AdminInterface::from(&*config)),
+        }
+    }
+
+    pub fn use_interface(&mut self, name: String) -> anyhow::Result<AdminInterface> {
+        match self {
+            Self::Instance { .. } => self.call(
+                Method::PUT,
+                "/admin/interface",
+                Some(InterfaceUpdate { name }),
+            ),
+            Self::File(config) => {
+                let interface = find_interface(&name)?;
+                configure(config, &[ConfigChange::SetInterface { interface }])?;
+                Ok(AdminInterface::from(&*config))
+            }
+        }
+    }
+
+    pub fn mode(&mut self) -> anyhow::Result<AdminMode> {
+        match self {
+            Self::Instance { .. } => self.call(Method::GET, "/admin/mode", None::<()>),
+            Self::File(config) => Ok(AdminMode::from(&*config)),
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: VerificationMode) -> anyhow::Result<AdminMode> {
+        match self {
+            Self::Instance { .. } => {
+                self.call(Method::PUT, "/admin/mode", Some(AdminMode { mode }))
+            }
+            Self::File(config) => {
+                configure(config, &[ConfigChange::SetVerificationMode { mode }])?;
+                Ok(AdminMode::from(&*config))
+            }
+        }
+    }
+
+    pub fn contexts(&mut self) -> anyhow::Result<Vec<PortContext>> {
+        match self {
+            Self::Instance { .. } => self.call(Method::GET, "/contexts", None::<()>),
+            Self::File(config) => Ok(PortContext::list(&context_manager(config)?)),
+        }
+    }
+
+    pub fn set_context(
+        &mut self,
+        key: ContextKey,
+        context: ContextTemplate,
+    ) -> anyhow::Result<PortContext> {
+        match self {
+            Self::Instance { .. } => self.call(Method::PUT, &context_path(&key)?, Some(context)),
+            Self::File(config) => {
+                let mut manager = ContextManager::new()?;
+                manager.set_context(key.clone(), context, config, ContextAuthor::Cli)?;
+                PortContext::new(&manager, key.clone())
+                    .ok_or_else(|| anyhow::anyhow!("No instruction context for {}", key))
+            }
+        }
+    }
+
+    pub fn delete_context(&mut self, key: ContextKey) -> anyhow::Result<()> {
+        match self {
+            Self::Instance { .. } => {
+                self.send(Method::DELETE, &context_path(&key)?, None::<()>)?;
+            }
+            Self::File(_) => {
+                let mut manager = ContextManager::new()?;
+                if manager.template(key.clone()).is_none() {
+                    anyhow::bail!("No instruction context for {}", key);
+                }
+                manager.delete_context(key, ContextAuthor::Cli)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// A new token for `agent`, replacing any it had
+    pub fn issue_agent_token(&mut self, agent: String) -> anyhow::Result<AgentToken> {
+        match self {
+            Self::Instance { .. } => self.call(
+                Method::PUT,
+                &format!("/admin/agents/{}/token", encode(&agent)?),
+                None::<()>,
+            ),
+            Self::File(config) => {
+                let token = generate_token()?;
+                let change = ConfigChange::SetAgentToken {
+                    agent: agent.clone(),
+                    token: Some(token.clone()),
+                };
+                configure(config, &[change])?;
+                Ok(AgentToken { agent, token })
+            }
+        }
+    }
+
+    pub fn revoke_agent_token(&mut self, agent: String) -> anyhow::Result<()> {
+        match self {
+            Self::Instance { .. } => {
+                self.send(
+                    Method::DELETE,
+                    &format!("/admin/agents/{}/token", encode(&agent)?),
+                    None::<()>,
+                )?;
+            }
+            Self::File(config) => {
+                configure(
+                    config,
+                    &[ConfigChange::SetAgentToken { agent, token: None }],
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn bases(&mut self) -> anyhow::Result<Vec<BaseContext>> {
+        match self {
+            Self::Instance { .. } => self.call(Method::GET, "/contexts/bases", None::<()>),
+            Self::File(config) => Ok(BaseContext::list(&context_manager(config)?)),
+        }
+    }
+
+    pub fn set_base(&mut self, name: String, context: ContextTemplate) -> anyhow::Result<()> {
+        match self {
+            Self::Instance { .. } => {
+                self.send(
+                    Method::PUT,
+                    &format!("/contexts/bases/{}", name),
+                    Some(context),
+                )?;
+            }
+            Self::File(config) => {
+                context_manager(config)?.set_base(&name, context, ContextAuthor::Cli)?
+            }
+        }
+        Ok(())
+    }
+
+    pub fn delete_base(&mut self, name: &str) -> anyhow::Result<()> {
+        match self {
+            Self::Instance { .. } => {
+                self.send(
+                    Method::DELETE,
+                    &format!("/contexts/bases/{}", name),
+                    None::<()>,
+                )?;
+            }
+            Self::File(_) => {
+                if !ContextManager::new()?.delete_base(name, ContextAuthor::Cli)? {
+                    anyhow::bail!("No base context named '{}'", name);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Context revisions of `port`, or of every port, oldest first
+    pub fn context_history(&mut self, port: Option<u16>) -> anyhow::Result<Vec<ContextRevision>> {
+        match self {
+            Self::Instance { .. } => {
+                let query = port.map(|p| format!("?port={}", p)).unwrap_or_default();
+                self.call(
+                    Method::GET,
+                    &format!("/context/history{}", query),
+                    None::<()>,
+                )
+            }
+            Self::File(_) => ContextManager::new()?.history(port),
+        }
+    }
+
+    pub fn rollback_context(&mut self, rev: u64) -> anyhow::Result<ContextRevision> {
+        match self {
+            Self::Instance { .. } => {
+                self.call(Method::POST, "/context/rollback", Some(Rollback { rev }))
+            }
+            Self::File(config) => context_manager(config)?
+                .rollback(rev, ContextAuthor::Cli)?
+                .ok_or_else(|| anyhow::anyhow!("The context is already as of revision {}", rev)),
+        }
+    }
+
+    /// End of the audit chain as the running instance wrote it; the file alone has none
+    pub fn audit_anchor(&mut self) -> anyhow::Result<Option<ChainAnchor>> {
+        match self {
+            Self::Instance { .. } => self
+                .call(Method::GET, "/audit/anchor", None::<()>)
+                .map(Some),
+            Self::File(_) => Ok(None),
+        }
+    }
+
+    fn call<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<impl Serialize>,
+    ) -> anyhow::Result<T> {
+        Ok(self.send(method, path, body)?.json()?)
+    }
+
+    fn send(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<impl Serialize>,
+    ) -> anyhow::Result<reqwest::blocking::Response> {
+        let Self::Instance { client, base_url } = self else {
+            anyhow::bail!("Not connected to a running instance");
+        };
+        let mut request = client.request(method, format!("{}{}", base_url, path));
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        check(request.send()?)
+    }
+}
+
+/// `/context` for `key`, the agent name escaped
+fn context_path(key: &ContextKey) -> anyhow::Result<String> {
+    let mut url = reqwest::Url::parse("http://localhost/context")?;
+    url.query_pairs_mut()
+        .append_pair("port", &key.port.to_string());
+    if let Some(agent) = &key.agent {
+        url.query_pairs_mut().append_pair("agent", agent);
+    }
+    Ok(format!(
+        "{}?{}",
+        url.path(),
+        url.query().unwrap_or_default()
+    ))
+}
+
+/// `value` escaped as one URL path segment
+fn encode(value: &str) -> anyhow::Result<String> {
+    let mut url = reqwest::Url::parse("http://localhost/")?;
+    url.path_segments_mut()
+        .map_err(|_| anyhow::anyhow!("Can't escape '{}' as a URL path segment", value))?
+        .pop_if_empty()
+        .push(value);
+    Ok(url.path()[1..].to_string())
+}
+
+/// Contexts in the config directory, rendering placeholders as the running app would
+fn context_manager(config: &NetworkConfig) -> anyhow::Result<ContextManager> {
+    let mut manager = ContextManager::new()?;
+    manager.set_variables(ContextVariables::from_config(config));
+    Ok(manager)
+}
+
+fn port(config: &NetworkConfig, port: u16) -> anyhow::Result<AdminPort> {
+    config
+        .binding(port)
+        .map(AdminPort::from)
+        .ok_or_else(|| ConfigChangeError::UnknownPort(port).into())
+}
+
+/// The admin API's `message` as the error, e.g. "Port 8888 is not configured"
+fn check(resp: reqwest::blocking::Response) -> anyhow::Result<reqwest::blocking::Response> {
+    if resp.status().is_success() {
+        return Ok(resp);
+    }
+    let status = resp.status();
+    let body: serde_json::Value = resp.json().unwrap_or_default();
+    match body["message"].as_str() {
+        Some(message) => Err(anyhow::anyhow!("{}", message)),
+        None => Err(anyhow::anyhow!("Request failed with status {}", status)),
+    }
+}
@@ -0,0 +1,21 @@
+use crate::cli::verification_binding;
+use app::config::network_config::NetworkConfig;
+
+/// Print a binding's token on stdout so agents can read it at runtime instead of
+/// copying the secret into their configuration
+pub fn run(port: Option<u16>) -> anyhow::Result<()> {
+    let config = NetworkConfig::load()?;
+    let binding = match port {
+        Some(port) => config
+            .binding(port)
+            .ok_or_else(|| anyhow::anyhow!("No binding configured for port {}", port))?,
+        None => verification_binding(&config)?,
+    };
+
+    let token = binding
+        .token
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("Port {} does not require a token", binding.port))?;
+    println!("{}", token);
+    Ok(())
+}
@@ -0,0 +1,236 @@
+use crate::config::change::{find_interface, ConfigChange, ConfigChangeError};
+use crate::config::network_config::NetworkConfig;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+/// How long [`CommandChannel::send`] waits for the app loop to run a command
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Everything the tray, the admin API or another control surface can ask the app to do
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum AppCommand {
+    /// Change `network.toml`, all or nothing; no changes just reads it
+    Configure {
+        changes: Vec<ConfigChange>,
+    },
+    TogglePort {
+        port: u16,
+    },
+    /// Serve every binding on the detected interface with this name
+    SelectInterface {
+        name: String,
+    },
+    /// Ask for a port in a dialog, then add it
+    AddPortDialog,
+    RevokeSession {
+        session_id: String,
+    },
+    RevokeAllSessions,
+    /// Give a context or base the one it had in revision `rev`, as a new revision
+    RollbackContext {
+        rev: u64,
+    },
+    DownloadConfig,
+    OpenRepo,
+    Quit,
+}
+
+impl AppCommand {
+    /// A single change to `network.toml`
+    pub fn change(change: ConfigChange) -> Self {
+        Self::Configure {
+            changes: vec![change],
+        }
+    }
+
+    /// Id of the tray menu item that submits this command
+    pub fn menu_id(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// The command behind a tray menu item; `None` for items that only show information
+    pub fn from_menu_id(id: &str) -> Option<Self> {
+        serde_json::from_str(id).ok()
+    }
+
+    /// The changes to `network.toml` this command stands for, resolved against `config`.
+    /// Empty for commands that don't touch the config.
+    pub fn config_changes(
+        &self,
+        config: &NetworkConfig,
+    ) -> Result<Vec<ConfigChange>, CommandError> {
+        let changes = match self {
+            Self::Configure { changes } => changes.clone(),
+            Self::TogglePort { port } => {
+                let binding = config
+                    .binding(*port)
+                    .ok_or(ConfigChangeError::UnknownPort(*port))?;
+                vec![ConfigChange::SetPortEnabled {
+                    port: *port,
+                    enabled: !binding.enabled,
+                }]
+            }
+            Self::SelectInterface { name } => vec![ConfigChange::SetInterface {
+                interface: find_interface(name)?,
+            }],
+            _ => Vec::new(),
+        };
+        Ok(changes)
+    }
+}
+
+/// What running a command did
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandOutcome {
+    /// `network.toml` was changed and saved; servers and tray still need a refresh
+    ConfigChanged(NetworkConfig),
+    /// Done without changing `network.toml`, which is returned for callers that read it
+    Unchanged(NetworkConfig),
+    /// The app should quit
+    Exit,
+}
+
+impl CommandOutcome {
+    /// The config after the command, unless the app is quitting
+    pub fn config(&self) -> Option<&NetworkConfig> {
+        match self {
+            Self::ConfigChanged(config) | Self::Unchanged(config) => Some(config),
+            Self::Exit => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandError {
+    Rejected(ConfigChangeError),
+    SaveFailed(String),
+    UnknownSession(String),
+    /// The command needs the tray, e.g. to show a dialog, and the daemon has none
+    Unsupported(String),
+    Failed(String),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rejected(e) => write!(f, "{}", e),
+            Self::SaveFailed(e) => write!(f, "Failed to save network.toml: {}", e),
+            Self::UnknownSession(id) => write!(f, "No active session {}", id),
+            Self::Unsupported(what) => write!(f, "{} needs the tray app", what),
+            Self::Failed(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl From<ConfigChangeError> for CommandError {
+    fn from(e: ConfigChangeError) -> Self {
+        Self::Rejected(e)
+    }
+}
+
+pub type CommandResult = Result<CommandOutcome, CommandError>;
+
+/// Apply every change to `config` or none, and save. No changes just reads the config.
+pub fn configure(config: &mut NetworkConfig, changes: &[ConfigChange]) -> CommandResult {
+    if changes.is_empty() {
+        return Ok(CommandOutcome::Unchanged(config.clone()));
+    }
+    let mut updated = config.clone();
+    for change in changes {
+        updated.apply(change)?;
+    }
+    updated
+        .save()
+        .map_err(|e| CommandError::SaveFailed(e.to_string()))?;
+    *config = updated;
+    Ok(CommandOutcome::ConfigChanged(config.clone()))
+}
+
+/// A command in the app loop's queue, with whoever waits for its result
+pub struct CommandRequest {
+    pub command: AppCommand,
+    reply: Option<oneshot::Sender<CommandResult>>,
+}
+
+impl CommandRequest {
+    /// Hand the result to the waiting sender. Answer before refreshing the servers:
+    /// the server waiting may be one of those restarted.
+    pub fn respond(self, result: &CommandResult) {
+        if let Err(e) = result {
+            eprintln!("{:?} failed: {}", self.command, e);
+        }
+        if let Some(reply) = self.reply {
+            let _ = reply.send(result.clone());
+        }
+    }
+}
+
+type Waker = Arc<Mutex<Option<Box<dyn Fn() + Send>>>>;
+
+/// Queue of commands into the app loop, shared by every control surface
+#[derive(Clone)]
+pub struct CommandChannel {
+    sender: mpsc::UnboundedSender<CommandRequest>,
+    waker: Waker,
+}
+
+impl CommandChannel {
+    pub fn new() -> (Self, mpsc::UnboundedReceiver<CommandRequest>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let channel = Self {
+            sender,
+            waker: Arc::new(Mutex::new(None)),
+        };
+        (channel, receiver)
+    }
+
+    /// Called after each command is queued, for loops that don't await the receiver,
+    /// like the tray's event loop
+    pub fn set_waker(&self, waker: impl Fn() + Send + 'static) {
+        *self.waker.lock().unwrap_or_else(|e| e.into_inner()) = Some(Box::new(waker));
+    }
+
+    /// Queue `command` without waiting for it
+    pub fn submit(&self, command: AppCommand) {
+        self.queue(CommandRequest {
+            command,
+            reply: None,
+        });
+    }
+
+    /// Queue `command` and wait for its result; `None` if the loop didn't answer in time
+    pub async fn send(&self, command: AppCommand) -> Option<CommandResult> {
+        let (reply, answer) = oneshot::channel();
+        if !self.queue(CommandRequest {
+            command,
+            reply: Some(reply),
+        }) {
+            return None;
+        }
+        tokio::time::timeout(COMMAND_TIMEOUT, answer)
+            .await
+            .ok()?
+            .ok()
+    }
+
+    fn queue(&self, request: CommandRequest) -> bool {
+        if self.sender.send(request).is_err() {
+            return false;
+        }
+        if let Some(wake) = self
+            .waker
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+        {
+            wake();
+        }
+        true
+    }
+}
@@ -0,0 +1,154 @@
+use crate::config::network_config::{NetworkConfig, VerificationMode};
+use crate::network::interface_detector::{InterfaceDetector, InterfaceType, NetworkInterface};
+use crate::network::port_config::{generate_token, validate_port, PortBinding, PortRole};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A change to `network.toml`, made from the tray menu or the admin API
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum ConfigChange {
+    SetPortEnabled {
+        port: u16,
+        enabled: bool,
+    },
+    SetPortRole {
+        port: u16,
+        role: PortRole,
+    },
+    AddPort {
+        binding: PortBinding,
+    },
+    RemovePort {
+        port: u16,
+    },
+    /// Serve every binding on this interface
+    SetInterface {
+        interface: NetworkInterface,
+    },
+    SetAllEnabled {
+        enabled: bool,
+    },
+    SetVerificationMode {
+        mode: VerificationMode,
+    },
+    /// Give `agent` a new token, or revoke its token with `None`
+    SetAgentToken {
+        agent: String,
+        token: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigChangeError {
+    InvalidPort(u16, String),
+    UnknownPort(u16),
+    PortExists(u16),
+    UnknownInterface(String),
+    UnknownAgent(String),
+}
+
+impl fmt::Display for ConfigChangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidPort(port, reason) => write!(f, "Port {}: {}", port, reason),
+            Self::UnknownPort(port) => write!(f, "Port {} is not configured", port),
+            Self::PortExists(port) => write!(f, "Port {} already exists", port),
+            Self::UnknownInterface(name) => write!(f, "No network interface named {:?}", name),
+            Self::UnknownAgent(agent) => write!(f, "Agent {:?} has no token", agent),
+        }
+    }
+}
+
+impl std::error::Error for ConfigChangeError {}
+
+impl NetworkConfig {
+    /// Apply `change` in memory; the caller saves and restarts the servers
+    pub fn apply(&mut self, change: &ConfigChange) -> Result<(), ConfigChangeError> {
+        match change {
+            ConfigChange::SetPortEnabled { port, enabled } => {
+                self.binding_mut(*port)?.enabled = *enabled;
+            }
+            ConfigChange::SetPortRole { port, role } => {
+                self.binding_mut(*port)?.role = *role;
+            }
+            ConfigChange::AddPort { binding } => {
+                validate_port(binding.port)
+                    .map_err(|e| ConfigChangeError::InvalidPort(binding.port, e.to_string()))?;
+                if self.binding(binding.port).is_some() {
+                    return Err(ConfigChangeError::PortExists(binding.port));
+                }
+                self.port_bindings.push(binding.clone());
+            }
+            ConfigChange::RemovePort { port } => {
+                self.binding_mut(*port)?;
+                self.port_bindings.retain(|b| b.port != *port);
+            }
+            ConfigChange::SetInterface { interface } => {
+                self.default_interface = interface.interface_type.clone();
+                for binding in &mut self.port_bindings {
+                    binding.interface = interface.clone();
+                }
+            }
+            ConfigChange::SetAllEnabled { enabled } => {
+                for binding in &mut self.port_bindings {
+                    binding.enabled = *enabled;
+                }
+            }
+            ConfigChange::SetVerificationMode { mode } => {
+                self.verification_mode = *mode;
+            }
+            ConfigChange::SetAgentToken { agent, token } => match token {
+                Some(token) => {
+                    self.agent_tokens.insert(agent.clone(), token.clone());
+                }
+                None => {
+                    self.agent_tokens
+                        .remove(agent)
+                        .ok_or_else(|| ConfigChangeError::UnknownAgent(agent.clone()))?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn binding_mut(&mut self, port: u16) -> Result<&mut PortBinding, ConfigChangeError> {
+        self.port_bindings
+            .iter_mut()
+            .find(|b| b.port == port)
+            .ok_or(ConfigChangeError::UnknownPort(port))
+    }
+
+    /// A new binding on the default interface (loopback if it isn't up), with a fresh token
+    pub fn new_binding(&self, port: u16, role: PortRole, enabled: bool) -> PortBinding {
+        let interface = InterfaceDetector::detect_all()
+            .ok()
+            .and_then(|interfaces| {
+                interfaces
+                    .into_iter()
+                    .find(|i| i.interface_type == self.default_interface)
+            })
+            .unwrap_or_else(|| NetworkInterface {
+                name: "lo0".to_string(),
+                ip_address: "127.0.0.1".parse().unwrap(),
+                interface_type: InterfaceType::Loopback,
+            });
+        PortBinding {
+            port,
+            interface,
+            role,
+            enabled,
+            // Falls back to `None`; the next config load fills it in
+            token: generate_token().ok(),
+            mtls: false,
+        }
+    }
+}
+
+/// The detected interface called `name`, for [`ConfigChange::SetInterface`]
+pub fn find_interface(name: &str) -> Result<NetworkInterface, ConfigChangeError> {
+    InterfaceDetector::detect_all()
+        .ok()
+        .and_then(|interfaces| interfaces.into_iter().find(|i| i.name == name))
+        .ok_or_else(|| ConfigChangeError::UnknownInterface(name.to_string()))
+}
@@ -1,2 +1,3 @@
+pub mod change;
 pub mod generator;
 pub mod network_config;
@@ -1,8 +1,11 @@
 use crate::network::interface_detector::InterfaceType;
-use crate::network::port_config::PortBinding;
+use crate::network::port_config::{generate_token, PortBinding};
+use crate::verification::prompter::PrompterConfig;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::fs;
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
 use std::path::PathBuf;
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
@@ -17,6 +20,18 @@ pub struct NetworkConfig {
     pub port_bindings: Vec<PortBinding>,
     #[serde(default = "default_verification_mode")]
     pub verification_mode: VerificationMode,
+    /// Serve non-loopback bindings even when they have no token
+    #[serde(default)]
+    pub allow_insecure_public: bool,
+    /// How verifications are put in front of the human
+    #[serde(default)]
+    pub prompter: PrompterConfig,
+    /// Custom `{{name}}` values for instruction contexts, beside the built-in ones
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub context_variables: BTreeMap<String, String>,
+    /// Agent name to the token it sends in `X-ChaseAI-Agent-Token` to get its own context
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub agent_tokens: BTreeMap<String, String>,
 }
 
 fn default_verification_mode() -> VerificationMode {
@@ -39,6 +54,8 @@ impl NetworkConfig {
             interface: loopback_interface.clone(),
             role: crate::network::port_config::PortRole::Verification,
             enabled: true,
+            token: None,
+            mtls: false,
         };
 
         #[cfg(feature = "beta")]
@@ -56,6 +73,8 @@ impl NetworkConfig {
                 interface: loopback_interface,
                 role: crate::network::port_config::PortRole::Instruction,
                 enabled: false,
+                token: None,
+                mtls: false,
             },
         );
 
@@ -63,24 +82,52 @@ impl NetworkConfig {
             default_interface: InterfaceType::Loopback,
             port_bindings: default_bindings,
             verification_mode: VerificationMode::Port,
+            allow_insecure_public: false,
+            prompter: PrompterConfig::default(),
+            context_variables: BTreeMap::new(),
+            agent_tokens: BTreeMap::new(),
         }
     }
 
     pub fn load() -> Result<Self> {
         let path = Self::config_path()?;
         if !path.exists() {
-            return Ok(Self::new());
+            // Persist the defaults so the app and the CLI agree on the generated tokens
+            let mut config = Self::new();
+            config.ensure_tokens()?;
+            config.save()?;
+            return Ok(config);
         }
 
         let content = fs::read_to_string(&path)
             .with_context(|| format!("Failed to read config file at {:?}", path))?;
 
-        let config: NetworkConfig = toml::from_str(&content)
+        let mut config: NetworkConfig = toml::from_str(&content)
             .with_context(|| "Failed to parse network configuration TOML")?;
 
+        // Bindings from older configs (or added by hand) get a token on first load
+        if config.ensure_tokens()? {
+            config.save()?;
+        }
+
         Ok(config)
     }
 
+    /// Generate a token for every binding that has none. Returns whether any was added.
+    pub fn ensure_tokens(&mut self) -> Result<bool> {
+        let mut added = false;
+        for binding in self.port_bindings.iter_mut().filter(|b| b.token.is_none()) {
+            binding.token = Some(generate_token()?);
+            added = true;
+        }
+        Ok(added)
+    }
+
+    /// Binding serving `port`, if any
+    pub fn binding(&self, port: u16) -> Option<&PortBinding> {
+        self.port_bindings.iter().find(|b| b.port == port)
+    }
+
     pub fn save(&self) -> Result<()> {
         let path = Self::config_path()?;
 
@@ -92,15 +139,30 @@ impl NetworkConfig {
         let content = toml::to_string_pretty(self)
             .with_context(|| "Failed to serialize network configuration to TOML")?;
 
-        fs::write(&path, content)
-            .with_context(|| format!("Failed to write config file to {:?}", path))?;
-
-        // On Unix, set permissions to 600
+        // Tokens live here: never readable by others, not even for a moment, and never
+        // left half-written for a reader or a crash
+        let temp_path = path.with_extension("toml.tmp");
+        match fs::remove_file(&temp_path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => {
+                return Err(e).with_context(|| format!("Failed to remove stale {:?}", temp_path));
+            }
+            _ => {}
+        }
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
         #[cfg(unix)]
         {
-            use std::os::unix::fs::PermissionsExt;
-            fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
         }
+        let mut file = options
+            .open(&temp_path)
+            .with_context(|| format!("Failed to create {:?}", temp_path))?;
+        file.write_all(content.as_bytes())
+            .and_then(|()| file.sync_all())
+            .with_context(|| format!("Failed to write config file to {:?}", temp_path))?;
+        fs::rename(&temp_path, &path)
+            .with_context(|| format!("Failed to write config file to {:?}", path))?;
 
         Ok(())
     }
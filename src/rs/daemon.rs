@@ -0,0 +1,371 @@
+use crate::command::{
+    self, AppCommand, CommandChannel, CommandError, CommandOutcome, CommandRequest, CommandResult,
+};
+use crate::config::network_config::NetworkConfig;
+use crate::instruction::manager::ContextManager;
+use crate::network::port_config::PortRole;
+use crate::server::pool::ServerPool;
+use crate::verification::prompter::PrompterConfig;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::runtime::Runtime;
+use tokio::sync::Mutex as TokioMutex;
+
+/// PID of the running tray app or daemon, beside `network.toml`
+pub const PID_FILE_NAME: &str = "chase.pid";
+
+/// What the running tray app or daemon serves, beside `network.toml`
+pub const RUNTIME_FILE_NAME: &str = "runtime.json";
+
+/// Contents of [`RUNTIME_FILE_NAME`]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RuntimeInfo {
+    pub pid: u32,
+    pub started_at: DateTime<Utc>,
+    pub version: String,
+    pub prompter: PrompterConfig,
+    pub ports: Vec<RuntimePort>,
+    /// Enabled bindings that were refused, with the reason
+    #[serde(default)]
+    pub refused: BTreeMap<u16, String>,
+}
+
+impl RuntimeInfo {
+    /// This process serving the enabled bindings of `config` that were not `refused`
+    pub fn serving(
+        config: &NetworkConfig,
+        started_at: DateTime<Utc>,
+        refused: BTreeMap<u16, String>,
+    ) -> Self {
+        let ports = config
+            .port_bindings
+            .iter()
+            .filter(|b| b.enabled && !refused.contains_key(&b.port))
+            .map(|b| RuntimePort {
+                port: b.port,
+                role: b.role,
+                base_url: b.base_url(),
+            })
+            .collect();
+        Self {
+            pid: std::process::id(),
+            started_at,
+            version: crate::version().to_string(),
+            prompter: config.prompter.clone(),
+            ports,
+            refused,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RuntimePort {
+    pub port: u16,
+    pub role: PortRole,
+    pub base_url: String,
+}
+
+/// PID and runtime files of the tray app or daemon, so `chase` knows one is running
+pub struct RuntimeFiles {
+    dir: PathBuf,
+}
+
+impl RuntimeFiles {
+    /// Files in the directory of `network.toml`
+    pub fn new() -> Result<Self> {
+        let config_path = NetworkConfig::config_path()?;
+        let dir = config_path
+            .parent()
+            .context("Config path has no parent directory")?;
+        Ok(Self::in_dir(dir))
+    }
+
+    pub fn in_dir(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn pid_path(&self) -> PathBuf {
+        self.dir.join(PID_FILE_NAME)
+    }
+
+    pub fn runtime_path(&self) -> PathBuf {
+        self.dir.join(RUNTIME_FILE_NAME)
+    }
+
+    /// PID of an instance that is still alive; stale PID files are ignored
+    pub fn running_pid(&self) -> Option<u32> {
+        let pid = fs::read_to_string(self.pid_path())
+            .ok()?
+            .trim()
+            .parse()
+            .ok()?;
+        process_alive(pid).then_some(pid)
+    }
+
+    pub fn read(&self) -> Result<Option<RuntimeInfo>> {
+        let path = self.runtime_path();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content =
+            fs::read_to_string(&path).with_context(|| format!("Failed to read {:?}", path))?;
+        Ok(Some(serde_json::from_str(&content)?))
+    }
+
+    pub fn write(&self, info: &RuntimeInfo) -> Result<()> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {:?}", self.dir))?;
+        write_file(&self.pid_path(), &format!("{}\n", info.pid))?;
+        write_file(&self.runtime_path(), &serde_json::to_string_pretty(info)?)
+    }
+
+    /// Remove both files, unless another process has taken them over
+    pub fn remove(&self) {
+        let ours = fs::read_to_string(self.pid_path())
+            .map(|pid| pid.trim() == std::process::id().to_string())
+            .unwrap_or(false);
+        if ours {
+            let _ = fs::remove_file(self.runtime_path());
+            let _ = fs::remove_file(self.pid_path());
+        }
+    }
+}
+
+fn write_file(path: &Path, content: &str) -> Result<()> {
+    fs::write(path, content).with_context(|| format!("Failed to write {:?}", path))
+}
+
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // Signal 0 only checks that the process exists; EPERM means it does but isn't ours
+    // SAFETY: kill takes plain integers and signal 0 is never delivered, so no process
+    // and none of our memory is affected
+    let result = unsafe { libc::kill(pid, 0) };
+    result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn process_alive(_pid: u32) -> bool {
+    true
+}
+
+/// The server pool and context manager without tray or event loop, for servers and CI runners
+pub struct Daemon {
+    config: NetworkConfig,
+    /// Replaces `config.prompter` without being saved with admin API changes
+    prompter: Option<PrompterConfig>,
+    started_at: DateTime<Utc>,
+    runtime: Runtime,
+    context_manager: Arc<Mutex<ContextManager>>,
+    server_pool: Arc<TokioMutex<ServerPool>>,
+    command_queue: tokio::sync::mpsc::UnboundedReceiver<CommandRequest>,
+    files: RuntimeFiles,
+}
+
+impl Daemon {
+    pub fn new(config: NetworkConfig) -> Result<Self> {
+        let context_manager = Arc::new(Mutex::new(ContextManager::new()?));
+        let (commands, command_queue) = CommandChannel::new();
+        let mut server_pool = ServerPool::new(context_manager.clone());
+        server_pool.set_admin(commands);
+        Ok(Self {
+            config,
+            prompter: None,
+            started_at: Utc::now(),
+            runtime: Runtime::new().context("Failed to create Tokio runtime")?,
+            context_manager,
+            server_pool: Arc::new(TokioMutex::new(server_pool)),
+            command_queue,
+            files: RuntimeFiles::new()?,
+        })
+    }
+
+    /// Ask with this prompter instead of the one in `network.toml`
+    pub fn with_prompter(mut self, prompter: PrompterConfig) -> Self {
+        self.prompter = Some(prompter);
+        self
+    }
+
+    pub fn with_runtime_files(mut self, files: RuntimeFiles) -> Self {
+        self.files = files;
+        self
+    }
+
+    /// Start the enabled bindings and write the PID and runtime files.
+    /// Refuses to start while another daemon is alive.
+    pub fn start(&self) -> Result<RuntimeInfo> {
+        if let Some(pid) = self.files.running_pid() {
+            if pid != std::process::id() {
+                anyhow::bail!("ChaseAI is already running (pid {})", pid);
+            }
+        }
+
+        self.serve()
+    }
+
+    /// Bring the servers in line with the config and record what they serve
+    fn serve(&self) -> Result<RuntimeInfo> {
+        let mut config = self.config.clone();
+        if let Some(prompter) = &self.prompter {
+            config.prompter = prompter.clone();
+        }
+        let pool = self.server_pool.clone();
+        let refused = self.runtime.block_on(async {
+            let mut pool = pool.lock().await;
+            pool.update(&config).await?;
+            anyhow::Ok(pool.refused_bindings().clone())
+        })?;
+
+        let info = RuntimeInfo::serving(&config, self.started_at, refused);
+        self.files.write(&info)?;
+        Ok(info)
+    }
+
+    /// Stop every server gracefully and remove the PID and runtime files
+    pub fn stop(&self) {
+        let pool = self.server_pool.clone();
+        self.runtime
+            .block_on(async { pool.lock().await.shutdown().await });
+        self.files.remove();
+    }
+
+    /// Start, serve and run admin API commands until SIGTERM, SIGINT (Ctrl+C elsewhere)
+    /// or a quit command, then stop
+    pub fn run(&mut self) -> Result<()> {
+        // Listen before the runtime file announces us, so an early SIGTERM still shuts down cleanly
+        let mut signals = {
+            let _runtime = self.runtime.enter();
+            ShutdownSignals::install()?
+        };
+        let info = self.start()?;
+        for port in &info.ports {
+            println!("Serving {:?} port on {}", port.role, port.base_url);
+        }
+        for (port, reason) in &info.refused {
+            eprintln!("⚠ Port {} refused: {}", port, reason);
+        }
+        let prompter = self
+            .runtime
+            .block_on(async { self.server_pool.lock().await.prompter() });
+        if let Err(unavailable) = prompter.check() {
+            println!("⚠ {}", unavailable);
+        }
+        println!(
+            "ChaseAI daemon running (pid {}). Send SIGTERM or press Ctrl+C to stop.",
+            info.pid
+        );
+
+        let signal = loop {
+            let request = self.runtime.block_on(async {
+                tokio::select! {
+                    signal = signals.recv() => Err(signal),
+                    Some(request) = self.command_queue.recv() => Ok(request),
+                }
+            });
+            let request = match request {
+                Ok(request) => request,
+                Err(signal) => break signal,
+            };
+            let result = self.dispatch(request.command.clone());
+            request.respond(&result);
+            match result {
+                Ok(CommandOutcome::ConfigChanged(_)) => {
+                    if let Err(e) = self.serve() {
+                        eprintln!("Failed to apply the new configuration: {}", e);
+                    }
+                }
+                Ok(CommandOutcome::Exit) => break Ok("quit command"),
+                _ => {}
+            }
+        };
+        match &signal {
+            Ok(name) => println!("Received {}, shutting down...", name),
+            Err(e) => eprintln!("Failed to wait for a shutdown signal: {}", e),
+        }
+        self.stop();
+        signal.map(|_| ())
+    }
+
+    /// Commands about the config and sessions; tray-only ones are refused
+    fn dispatch(&mut self, command: AppCommand) -> CommandResult {
+        match command {
+            AppCommand::RevokeSession { session_id } => {
+                let revoked = self
+                    .context_manager
+                    .lock()
+                    .map(|mut m| m.revoke_session(&session_id))
+                    .unwrap_or(false);
+                if !revoked {
+                    return Err(CommandError::UnknownSession(session_id));
+                }
+            }
+            AppCommand::RevokeAllSessions => {
+                if let Ok(mut manager) = self.context_manager.lock() {
+                    manager.revoke_all_sessions();
+                }
+            }
+            AppCommand::Quit => return Ok(CommandOutcome::Exit),
+            // The admin API rolls contexts back itself; this one is the tray's
+            AppCommand::AddPortDialog
+            | AppCommand::DownloadConfig
+            | AppCommand::OpenRepo
+            | AppCommand::RollbackContext { .. } => {
+                return Err(CommandError::Unsupported(format!("{:?}", command)))
+            }
+            AppCommand::Configure { .. }
+            | AppCommand::TogglePort { .. }
+            | AppCommand::SelectInterface { .. } => {
+                let changes = command.config_changes(&self.config)?;
+                return command::configure(&mut self.config, &changes);
+            }
+        }
+        Ok(CommandOutcome::Unchanged(self.config.clone()))
+    }
+}
+
+struct ShutdownSignals {
+    #[cfg(unix)]
+    terminate: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    interrupt: tokio::signal::unix::Signal,
+}
+
+impl ShutdownSignals {
+    /// Must be called within the runtime
+    fn install() -> Result<Self> {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            Ok(Self {
+                terminate: signal(SignalKind::terminate())?,
+                interrupt: signal(SignalKind::interrupt())?,
+            })
+        }
+        #[cfg(not(unix))]
+        Ok(Self {})
+    }
+
+    /// Wait for the first signal and return its name
+    async fn recv(&mut self) -> Result<&'static str> {
+        #[cfg(unix)]
+        {
+            tokio::select! {
+                _ = self.terminate.recv() => Ok("SIGTERM"),
+                _ = self.interrupt.recv() => Ok("SIGINT"),
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            tokio::signal::ctrl_c().await?;
+            Ok("Ctrl+C")
+        }
+    }
+}
@@ -0,0 +1,188 @@
+/// One entry of `allowed_actions`:
+///
+/// ```text
+/// pattern  = [ "!" ] ( name [ ".*" ] | "*" )
+/// name     = segment { "." segment }
+/// segment  = lowercase letter { lowercase letter | digit | "-" }
+/// ```
+///
+/// `fs.write` grants exactly that action, `fs.*` every action under `fs` and `*` every
+/// action. A leading `!` denies instead, and a deny always wins over a grant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionPattern {
+    deny: bool,
+    /// Name segments before the wildcard, if any
+    segments: Vec<String>,
+    wildcard: bool,
+}
+
+impl ActionPattern {
+    /// Parse `text`, or say exactly what is wrong with it and where
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (deny, body, offset) = match text.strip_prefix('!') {
+            Some(body) => (true, body, 1),
+            None => (false, text, 0),
+        };
+        if text.is_empty() {
+            return Err("Action names cannot be empty".to_string());
+        }
+        if body.is_empty() {
+            return Err(format!(
+                "'{}' must be followed by an action name or '*'",
+                text
+            ));
+        }
+
+        let mut segments = Vec::new();
+        let mut wildcard = false;
+        let mut position = offset;
+        let parts: Vec<&str> = body.split('.').collect();
+        for (index, segment) in parts.iter().enumerate() {
+            let last = index + 1 == parts.len();
+            if *segment == "*" {
+                if !last {
+                    return Err(format!(
+                        "'*' at position {} in '{}' must be the last segment, as in 'fs.*'",
+                        position + 1,
+                        text
+                    ));
+                }
+                wildcard = true;
+            } else {
+                check_segment(segment, position, text)?;
+                segments.push(segment.to_string());
+            }
+            position += segment.len() + 1;
+        }
+        Ok(Self {
+            deny,
+            segments,
+            wildcard,
+        })
+    }
+
+    pub fn is_deny(&self) -> bool {
+        self.deny
+    }
+
+    /// Whether this pattern, grant or deny, covers the action `name`
+    pub fn matches(&self, name: &[&str]) -> bool {
+        if self.wildcard {
+            name.len() > self.segments.len() && name.starts_with(&self.segment_refs())
+        } else {
+            name == self.segment_refs().as_slice()
+        }
+    }
+
+    fn segment_refs(&self) -> Vec<&str> {
+        self.segments.iter().map(String::as_str).collect()
+    }
+}
+
+/// Segments of an action name as agents send it in `action_type`: no wildcard, no `!`
+pub fn parse_action(name: &str) -> Result<Vec<&str>, String> {
+    if name.is_empty() {
+        return Err("Action names cannot be empty".to_string());
+    }
+    let mut position = 0;
+    let mut segments = Vec::new();
+    for segment in name.split('.') {
+        check_segment(segment, position, name)?;
+        segments.push(segment);
+        position += segment.len() + 1;
+    }
+    Ok(segments)
+}
+
+/// What an `allowed_actions` list says about one action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionMatch {
+    Granted,
+    /// A `!` pattern covers it, whatever grants it
+    Denied,
+    NotGranted,
+}
+
+/// Parsed `allowed_actions`, to ask whether they permit an action
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ActionRules {
+    patterns: Vec<ActionPattern>,
+}
+
+impl ActionRules {
+    /// Rules from `allowed_actions`; entries that do not parse grant and deny nothing.
+    /// Contexts are validated before they are served, so a served list has none.
+    pub fn new(allowed_actions: &[String]) -> Self {
+        Self {
+            patterns: allowed_actions
+                .iter()
+                .filter_map(|pattern| ActionPattern::parse(pattern).ok())
+                .collect(),
+        }
+    }
+
+    pub fn check(&self, action: &str) -> ActionMatch {
+        let Ok(name) = parse_action(action) else {
+            return ActionMatch::NotGranted;
+        };
+        let mut granted = false;
+        for pattern in self.patterns.iter().filter(|p| p.matches(&name)) {
+            if pattern.is_deny() {
+                return ActionMatch::Denied;
+            }
+            granted = true;
+        }
+        if granted {
+            ActionMatch::Granted
+        } else {
+            ActionMatch::NotGranted
+        }
+    }
+
+    pub fn permits(&self, action: &str) -> bool {
+        self.check(action) == ActionMatch::Granted
+    }
+
+    /// Whether any pattern grants, rather than denies
+    pub fn grants_any(&self) -> bool {
+        self.patterns.iter().any(|p| !p.is_deny())
+    }
+}
+
+/// `segment` starts at `position` (0-based) in `text`
+fn check_segment(segment: &str, position: usize, text: &str) -> Result<(), String> {
+    let Some(first) = segment.chars().next() else {
+        return Err(format!(
+            "Empty segment at position {} in '{}': use one '.' between segments",
+            position + 1,
+            text
+        ));
+    };
+    if !first.is_ascii_lowercase() {
+        return Err(format!(
+            "Segment '{}' at position {} in '{}' must start with a lowercase letter",
+            segment,
+            position + 1,
+            text
+        ));
+    }
+    if let Some((index, c)) = segment
+        .char_indices()
+        .find(|(_, c)| !(c.is_ascii_lowercase() || c.is_ascii_digit() || *c == '-'))
+    {
+        if c == '*' {
+            return Err(format!(
+                "'*' at position {} in '{}' must be a whole segment, as in 'fs.*'",
+                position + index + 1,
+                text
+            ));
+        }
+        return Err(format!(
+            "Invalid character '{}' at position {} in '{}': segments use lowercase letters, numbers and hyphens",
+            c,
+            position + index + 1,
+            text
+        ));
+    }
+    Ok(())
+}
@@ -1,5 +1,39 @@
-use regex::Regex;
+use crate::instruction::actions::{ActionMatch, ActionPattern, ActionRules};
+use crate::instruction::variables::{placeholders, ContextVariables};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Why a request's `action_type` falls outside a context's `allowed_actions`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionTypeViolation {
+    ActionTypeRequired,
+    ActionTypeNotAllowed,
+    /// A `!` entry denies it, whatever grants it
+    ActionTypeDenied,
+    /// The stored context does not resolve, so there is no contract to check against
+    ContextUnresolved,
+}
+
+/// One problem with an [`InstructionContext`], by field
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ContextError {
+    pub field: String,
+    pub message: String,
+}
+
+/// Everything wrong with a context; the error behind a failed [`InstructionContext::validate`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextErrors(pub Vec<ContextError>);
+
+impl fmt::Display for ContextErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let messages: Vec<&str> = self.0.iter().map(|e| e.message.as_str()).collect();
+        write!(f, "{}", messages.join("; "))
+    }
+}
+
+impl std::error::Error for ContextErrors {}
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct InstructionContext {
@@ -9,10 +43,11 @@ pub struct InstructionContext {
     /// Agent role (e.g., "execution-agent")
     pub role: String,
 
-    /// Base instruction text defining agent behavior
+    /// Base instruction text defining agent behavior; may use `{{variable}}` placeholders
     pub base_instruction: String,
 
-    /// List of allowed action types
+    /// Action types agents may ask for: names like `fs.write`, wildcards like `fs.*`
+    /// and denies like `!fs.delete`; see [`ActionPattern`]
     pub allowed_actions: Vec<String>,
 
     /// Whether verification is required for actions
@@ -34,34 +69,98 @@ impl InstructionContext {
             allowed_actions,
             verification_required,
         };
-        context.validate()?;
+        context.validate(&ContextVariables::default())?;
         Ok(context)
     }
 
-    pub fn validate(&self) -> anyhow::Result<()> {
-        if self.system.trim().is_empty() {
-            return Err(anyhow::anyhow!("System identifier cannot be empty"));
+    /// Fails with [`ContextErrors`] listing every problem, not just the first.
+    /// Placeholders must name a built-in variable or one of `variables`.
+    pub fn validate(&self, variables: &ContextVariables) -> anyhow::Result<()> {
+        let errors = self.errors(variables);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ContextErrors(errors).into())
         }
-        if self.role.trim().is_empty() {
-            return Err(anyhow::anyhow!("Agent role cannot be empty"));
-        }
-        if self.base_instruction.trim().is_empty() {
-            return Err(anyhow::anyhow!("Base instruction cannot be empty"));
-        }
-        if self.allowed_actions.is_empty() {
-            return Err(anyhow::anyhow!("Allowed actions list cannot be empty"));
+    }
+
+    /// Every reason this context is invalid, in field order
+    pub fn errors(&self, variables: &ContextVariables) -> Vec<ContextError> {
+        let mut errors = Vec::new();
+        let mut check = |field: &str, ok: bool, message: String| {
+            if !ok {
+                errors.push(ContextError {
+                    field: field.to_string(),
+                    message,
+                });
+            }
+        };
+
+        check(
+            "system",
+            !self.system.trim().is_empty(),
+            "System identifier cannot be empty".to_string(),
+        );
+        check(
+            "role",
+            !self.role.trim().is_empty(),
+            "Agent role cannot be empty".to_string(),
+        );
+        check(
+            "base_instruction",
+            !self.base_instruction.trim().is_empty(),
+            "Base instruction cannot be empty".to_string(),
+        );
+        match placeholders(&self.base_instruction) {
+            Ok(names) => {
+                for name in names {
+                    check(
+                        "base_instruction",
+                        variables.is_known(name),
+                        format!("Unknown variable '{{{{{}}}}}' in base instruction", name),
+                    );
+                }
+            }
+            Err(message) => check("base_instruction", false, message),
         }
+        check(
+            "allowed_actions",
+            !self.allowed_actions.is_empty(),
+            "Allowed actions list cannot be empty".to_string(),
+        );
 
-        let action_regex = Regex::new(r"^[a-z][a-z0-9-]*$").unwrap();
+        let mut valid = true;
         for action in &self.allowed_actions {
-            if !action_regex.is_match(action) {
-                return Err(anyhow::anyhow!(
-                    "Invalid action name '{}': must start with a lowercase letter and contain only lowercase letters, numbers, and hyphens",
-                    action
-                ));
+            if let Err(message) = ActionPattern::parse(action) {
+                valid = false;
+                check("allowed_actions", false, message);
             }
         }
+        check(
+            "allowed_actions",
+            !valid || self.allowed_actions.is_empty() || self.rules().grants_any(),
+            "Allowed actions only deny; grant at least one action".to_string(),
+        );
+
+        errors
+    }
+
+    /// Whether this context lets agents ask for `action`
+    pub fn permits(&self, action: &str) -> bool {
+        self.rules().permits(action)
+    }
 
-        Ok(())
+    pub fn rules(&self) -> ActionRules {
+        ActionRules::new(&self.allowed_actions)
+    }
+
+    /// Check a request's `action_type` against this context's contract
+    pub fn check_action_type(&self, action_type: Option<&str>) -> Result<(), ActionTypeViolation> {
+        let action_type = action_type.ok_or(ActionTypeViolation::ActionTypeRequired)?;
+        match self.rules().check(action_type) {
+            ActionMatch::Granted => Ok(()),
+            ActionMatch::Denied => Err(ActionTypeViolation::ActionTypeDenied),
+            ActionMatch::NotGranted => Err(ActionTypeViolation::ActionTypeNotAllowed),
+        }
     }
 }
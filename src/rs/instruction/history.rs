@@ -0,0 +1,202 @@
+use crate::instruction::storage::ContextKey;
+use crate::instruction::template::ContextTemplate;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// File name of the context history inside the ChaseAI config directory
+pub const HISTORY_FILE_NAME: &str = "context_history.jsonl";
+
+/// Who changed a context, as the server saw the change arrive
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextAuthor {
+    /// A rollback chosen in the tray's Context History menu
+    Tray,
+    /// `chase`, editing the files or calling the admin API
+    Cli,
+    /// Anyone else calling the admin API
+    Api,
+}
+
+/// What a revision changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RevisionTarget {
+    /// A port's or an agent's context
+    Context(ContextKey),
+    /// A named base context, and with it everything extending it
+    Base(String),
+}
+
+impl fmt::Display for RevisionTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Context(key) => key.fmt(f),
+            Self::Base(name) => write!(f, "base '{}'", name),
+        }
+    }
+}
+
+/// One change to a port's or an agent's context, or to a base context
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ContextRevision {
+    /// Increases by one with every change, across all ports and bases
+    pub rev: u64,
+    /// Absent when the change was to a base
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Set when the change was to one agent's context on the port
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent: Option<String>,
+    /// Set when the change was to this base context
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
+    pub at: DateTime<Utc>,
+    pub author: ContextAuthor,
+    /// The context as written from this revision on; `None` when it was deleted
+    pub context: Option<ContextTemplate>,
+    /// Revision whose context this one restored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollback_of: Option<u64>,
+}
+
+/// A field that differs between two versions of a context
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+/// Fields that differ from `before` to `after`; a missing side is a created or deleted context
+pub fn diff<T: Serialize>(before: Option<&T>, after: Option<&T>) -> Vec<FieldChange> {
+    let fields = |context: Option<&T>| match context.map(serde_json::to_value) {
+        Some(Ok(serde_json::Value::Object(fields))) => fields,
+        _ => serde_json::Map::new(),
+    };
+    let (before, after) = (fields(before), fields(after));
+
+    let mut names: Vec<&String> = before.keys().chain(after.keys()).collect();
+    names.sort();
+    names.dedup();
+    names
+        .into_iter()
+        .filter(|name| before.get(*name) != after.get(*name))
+        .map(|name| FieldChange {
+            field: name.clone(),
+            before: before.get(name).cloned(),
+            after: after.get(name).cloned(),
+        })
+        .collect()
+}
+
+/// Append-only JSONL log of every context change
+pub struct ContextHistory {
+    path: PathBuf,
+    // Highest revision number written so far; also serializes concurrent appends.
+    // `None` until the existing file has been read.
+    last_rev: Mutex<Option<u64>>,
+}
+
+impl ContextRevision {
+    /// Whose context this revision changed
+    pub fn target(&self) -> RevisionTarget {
+        match &self.base {
+            Some(name) => RevisionTarget::Base(name.clone()),
+            None => RevisionTarget::Context(ContextKey {
+                port: self.port.unwrap_or_default(),
+                agent: self.agent.clone(),
+            }),
+        }
+    }
+}
+
+impl ContextHistory {
+    pub fn with_path(path: PathBuf) -> Self {
+        Self {
+            path,
+            last_rev: Mutex::new(None),
+        }
+    }
+
+    /// History stored in `dir`, next to `contexts.json`
+    pub fn in_dir(dir: &Path) -> Self {
+        Self::with_path(dir.join(HISTORY_FILE_NAME))
+    }
+
+    /// Every revision, oldest first. Lines that don't parse are reported and skipped.
+    pub fn load(&self) -> Result<Vec<ContextRevision>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let file = fs::File::open(&self.path)
+            .with_context(|| format!("Failed to open context history at {:?}", self.path))?;
+        let mut revisions = Vec::new();
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(revision) => revisions.push(revision),
+                Err(e) => eprintln!("Skipping malformed context revision {}: {}", index + 1, e),
+            }
+        }
+        Ok(revisions)
+    }
+
+    /// Record `context` as the next revision of `target` and return it
+    pub fn append(
+        &self,
+        target: &RevisionTarget,
+        context: Option<&ContextTemplate>,
+        author: ContextAuthor,
+        rollback_of: Option<u64>,
+    ) -> Result<ContextRevision> {
+        let mut last_rev = self.last_rev.lock().unwrap_or_else(|e| e.into_inner());
+        let last = match *last_rev {
+            Some(rev) => rev,
+            None => self.load()?.iter().map(|r| r.rev).max().unwrap_or(0),
+        };
+        let rev = last + 1;
+        let (port, agent, base) = match target {
+            RevisionTarget::Context(key) => (Some(key.port), key.agent.clone(), None),
+            RevisionTarget::Base(name) => (None, None, Some(name.clone())),
+        };
+        let revision = ContextRevision {
+            rev,
+            port,
+            agent,
+            base,
+            at: Utc::now(),
+            author,
+            context: context.cloned(),
+            rollback_of,
+        };
+
+        if let Some(parent) = self.path.parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        let mut options = OpenOptions::new();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options
+            .open(&self.path)
+            .with_context(|| format!("Failed to open context history at {:?}", self.path))?;
+        writeln!(file, "{}", serde_json::to_string(&revision)?)?;
+        file.sync_data()?;
+        *last_rev = Some(rev);
+        Ok(revision)
+    }
+}
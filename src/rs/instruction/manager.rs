@@ -1,65 +1,394 @@
 use crate::config::network_config::NetworkConfig;
-use crate::instruction::context::InstructionContext;
-use crate::instruction::storage::ContextStorage;
+use crate::instruction::context::{ContextError, ContextErrors, InstructionContext};
+use crate::instruction::history::{ContextAuthor, ContextHistory, ContextRevision, RevisionTarget};
+use crate::instruction::session::{new_session_id, Session, SessionScope};
+use crate::instruction::storage::{ContextKey, ContextStorage, StoredContexts};
+use crate::instruction::template::{is_valid_base_name, ContextTemplate};
+use crate::instruction::variables::{render, ContextVariables};
+use crate::server::auth::constant_time_eq;
 use anyhow::{anyhow, Result};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
 
-pub type SessionMap = HashMap<String, (chrono::DateTime<chrono::Utc>, Vec<String>)>;
+pub type SessionMap = HashMap<String, Session>;
 
 pub struct ContextManager {
-    contexts: HashMap<u16, InstructionContext>,
+    stored: StoredContexts,
     storage: ContextStorage,
-    /// Maps verification_id to (expires_at, allowed_actions)
+    /// Every change to a port's context, next to `contexts.json`
+    history: ContextHistory,
+    /// What `{{name}}` placeholders in base instructions render to
+    variables: ContextVariables,
+    /// `[agent_tokens]` from the config: agent name to the token that identifies it
+    agent_tokens: BTreeMap<String, String>,
+    /// Approved sessions by id
     pub sessions: SessionMap,
 }
 
 impl ContextManager {
     pub fn new() -> Result<Self> {
-        let storage = ContextStorage::new()?;
-        let contexts = storage.load_all()?;
-        Ok(Self {
-            contexts,
-            storage,
-            sessions: HashMap::new(),
-        })
+        Self::new_with_storage(ContextStorage::new()?)
     }
 
     pub fn new_with_storage(storage: ContextStorage) -> Result<Self> {
-        let contexts = storage.load_all()?;
+        let stored = storage.load_all()?;
+        let history = ContextHistory::in_dir(&storage.config_dir());
         Ok(Self {
-            contexts,
+            stored,
             storage,
+            history,
+            variables: ContextVariables::default(),
+            agent_tokens: BTreeMap::new(),
             sessions: HashMap::new(),
         })
     }
 
+    /// Take placeholder values from the current config; contexts using a custom
+    /// variable it no longer defines stop being served
+    pub fn set_variables(&mut self, variables: ContextVariables) {
+        self.variables = variables;
+    }
+
+    /// Directory where contexts (and the files stored alongside them) are persisted
+    pub fn data_dir(&self) -> PathBuf {
+        self.storage.config_dir()
+    }
+
+    /// Save `context` for a port, or one agent on it, and record it in the history; it must
+    /// be valid once its bases are applied. Returns the new revision, or `None` if it was
+    /// already the context.
     pub fn set_context(
         &mut self,
-        port: u16,
-        context: InstructionContext,
+        key: impl Into<ContextKey>,
+        context: impl Into<ContextTemplate>,
         config: &NetworkConfig,
+        author: ContextAuthor,
+    ) -> Result<Option<ContextRevision>> {
+        let key = key.into();
+        self.validate_port(key.port, config)?;
+        if key.agent.as_deref().is_some_and(str::is_empty) {
+            return Err(anyhow!("Agent names cannot be empty"));
+        }
+        self.variables = ContextVariables::from_config(config);
+        let context = context.into();
+        context.resolve(&self.stored.bases, &self.variables)?;
+        self.store(key, Some(context), author, None)
+    }
+
+    /// The context an agent gets: its own on the port if it has one, else the port's. Bases
+    /// are applied and placeholders rendered for the port. `None` if one fails to resolve.
+    pub fn get_context(&self, key: impl Into<ContextKey>) -> Option<InstructionContext> {
+        self.resolve_context(key).unwrap_or_else(|e| {
+            eprintln!("⚠️ {}", e);
+            None
+        })
+    }
+
+    /// Like [`get_context`](Self::get_context), but a stored context that fails to resolve is an
+    /// error rather than `None`, which means no context applies at all
+    pub fn resolve_context(
+        &self,
+        key: impl Into<ContextKey>,
+    ) -> Result<Option<InstructionContext>> {
+        let key = key.into();
+        let Some((key, template)) = self
+            .stored
+            .contexts
+            .get_key_value(&key)
+            .or_else(|| self.stored.contexts.get_key_value(&key.port_key()))
+        else {
+            return Ok(None);
+        };
+        let mut context = template
+            .resolve(&self.stored.bases, &self.variables)
+            .map_err(|e| anyhow!("Context for {} is not served: {}", key, e))?;
+        context.base_instruction =
+            render(&context.base_instruction, &self.variables.values(key.port));
+        Ok(Some(context))
+    }
+
+    /// The context stored under exactly `key`, as written, before its bases are applied
+    pub fn template(&self, key: impl Into<ContextKey>) -> Option<&ContextTemplate> {
+        self.stored.contexts.get(&key.into())
+    }
+
+    /// Remove the context stored under exactly `key`; the port's stays when an agent's goes
+    pub fn delete_context(
+        &mut self,
+        key: impl Into<ContextKey>,
+        author: ContextAuthor,
+    ) -> Result<Option<ContextRevision>> {
+        self.store(key.into(), None, author, None)
+    }
+
+    /// Take agent tokens from the current config, replacing the previous ones
+    pub fn set_agent_tokens(&mut self, tokens: BTreeMap<String, String>) {
+        self.agent_tokens = tokens;
+    }
+
+    /// The agent that was given `token`
+    pub fn agent_for_token(&self, token: &str) -> Option<&str> {
+        self.agent_tokens
+            .iter()
+            .find(|(_, expected)| constant_time_eq(token.as_bytes(), expected.as_bytes()))
+            .map(|(agent, _)| agent.as_str())
+    }
+
+    /// Revisions of every context and base, or only of `port`'s contexts, oldest first
+    pub fn history(&self, port: Option<u16>) -> Result<Vec<ContextRevision>> {
+        let mut revisions = self.history.load()?;
+        revisions.retain(|r| port.is_none_or(|port| r.port == Some(port)));
+        Ok(revisions)
+    }
+
+    pub fn revision(&self, rev: u64) -> Result<Option<ContextRevision>> {
+        Ok(self.history.load()?.into_iter().find(|r| r.rev == rev))
+    }
+
+    /// Give the port, agent or base of revision `rev` the context it had then, as a new
+    /// revision. `None` if it already has it.
+    pub fn rollback(&mut self, rev: u64, author: ContextAuthor) -> Result<Option<ContextRevision>> {
+        let revision = self
+            .revision(rev)?
+            .ok_or_else(|| anyhow!("Revision {} does not exist", rev))?;
+        match revision.target() {
+            RevisionTarget::Context(key) => {
+                if let Some(context) = &revision.context {
+                    context.resolve(&self.stored.bases, &self.variables)?;
+                }
+                self.store(key, revision.context, author, Some(rev))
+            }
+            RevisionTarget::Base(name) => {
+                match &revision.context {
+                    Some(base) => self.check_base(&name, base)?,
+                    None => self.check_base_unused(&name)?,
+                }
+                self.store_base(&name, revision.context, author, Some(rev))
+            }
+        }
+    }
+
+    fn store(
+        &mut self,
+        key: ContextKey,
+        context: Option<ContextTemplate>,
+        author: ContextAuthor,
+        rollback_of: Option<u64>,
+    ) -> Result<Option<ContextRevision>> {
+        if self.stored.contexts.get(&key) == context.as_ref() {
+            return Ok(None);
+        }
+        match &context {
+            Some(context) => self.stored.contexts.insert(key.clone(), context.clone()),
+            None => self.stored.contexts.remove(&key),
+        };
+        self.storage.save_all(&self.stored)?;
+        let revision = self.history.append(
+            &RevisionTarget::Context(key),
+            context.as_ref(),
+            author,
+            rollback_of,
+        )?;
+        Ok(Some(revision))
+    }
+
+    fn store_base(
+        &mut self,
+        name: &str,
+        base: Option<ContextTemplate>,
+        author: ContextAuthor,
+        rollback_of: Option<u64>,
+    ) -> Result<Option<ContextRevision>> {
+        if self.stored.bases.get(name) == base.as_ref() {
+            return Ok(None);
+        }
+        match &base {
+            Some(base) => self.stored.bases.insert(name.to_string(), base.clone()),
+            None => self.stored.bases.remove(name),
+        };
+        self.storage.save_all(&self.stored)?;
+        let revision = self.history.append(
+            &RevisionTarget::Base(name.to_string()),
+            base.as_ref(),
+            author,
+            rollback_of,
+        )?;
+        Ok(Some(revision))
+    }
+
+    /// Port and agent contexts as written, by port with each port's own context first
+    pub fn list_contexts(&self) -> Vec<(&ContextKey, &ContextTemplate)> {
+        let mut contexts: Vec<(&ContextKey, &ContextTemplate)> =
+            self.stored.contexts.iter().collect();
+        contexts.sort_by_key(|(key, _)| *key);
+        contexts
+    }
+
+    pub fn list_bases(&self) -> Vec<(&str, &ContextTemplate)> {
+        let mut bases: Vec<(&str, &ContextTemplate)> = self
+            .stored
+            .bases
+            .iter()
+            .map(|(name, base)| (name.as_str(), base))
+            .collect();
+        bases.sort_by_key(|(name, _)| *name);
+        bases
+    }
+
+    /// Add or replace a named base. Refused if any port context extending it would
+    /// become invalid; the errors name the port.
+    pub fn set_base(
+        &mut self,
+        name: &str,
+        base: ContextTemplate,
+        author: ContextAuthor,
     ) -> Result<()> {
-        self.validate_port(port, config)?;
-        context.validate()?;
+        self.check_base(name, &base)?;
+        self.store_base(name, Some(base), author, None)?;
+        Ok(())
+    }
 
-        self.contexts.insert(port, context);
-        self.storage.save_all(&self.contexts)?;
+    /// Whether `base` can be stored as `name` without breaking a context extending it
+    fn check_base(&self, name: &str, base: &ContextTemplate) -> Result<()> {
+        if !is_valid_base_name(name) {
+            return Err(anyhow!(
+                "Invalid base name '{}': use lowercase letters, numbers, hyphens and underscores",
+                name
+            ));
+        }
+        let mut bases = self.stored.bases.clone();
+        bases.insert(name.to_string(), base.clone());
+
+        // Only the chain matters for the base itself; ports check the merged fields
+        let mut errors: Vec<ContextError> = match base.resolve(&bases, &self.variables) {
+            Err(ContextErrors(errors)) => errors
+                .into_iter()
+                .filter(|e| e.field == "extends")
+                .collect(),
+            Ok(_) => Vec::new(),
+        };
+        for (key, context) in self.list_contexts() {
+            if let Err(ContextErrors(key_errors)) = context.resolve(&bases, &self.variables) {
+                errors.extend(key_errors.into_iter().map(|e| ContextError {
+                    field: e.field,
+                    message: format!("Context of {}: {}", key, e.message),
+                }));
+            }
+        }
+        if !errors.is_empty() {
+            return Err(ContextErrors(errors).into());
+        }
         Ok(())
     }
 
-    pub fn get_context(&self, port: u16) -> Option<&InstructionContext> {
-        self.contexts.get(&port)
+    /// Remove a named base nothing extends; returns false if it did not exist
+    pub fn delete_base(&mut self, name: &str, author: ContextAuthor) -> Result<bool> {
+        if !self.stored.bases.contains_key(name) {
+            return Ok(false);
+        }
+        self.check_base_unused(name)?;
+        self.store_base(name, None, author, None)?;
+        Ok(true)
     }
 
-    pub fn delete_context(&mut self, port: u16) -> Result<()> {
-        if self.contexts.remove(&port).is_some() {
-            self.storage.save_all(&self.contexts)?;
+    fn check_base_unused(&self, name: &str) -> Result<()> {
+        let users = self.base_users(name);
+        if !users.is_empty() {
+            return Err(anyhow!(
+                "Base context '{}' is extended by {}",
+                name,
+                users.join(", ")
+            ));
         }
         Ok(())
     }
 
-    pub fn list_contexts(&self) -> Vec<(u16, &InstructionContext)> {
-        self.contexts.iter().map(|(k, v)| (*k, v)).collect()
+    /// Contexts and bases that extend the base `name`, e.g. `port 8888`,
+    /// `port 8888 (agent 'ci')` or `base 'strict'`
+    pub fn base_users(&self, name: &str) -> Vec<String> {
+        let extends = |template: &ContextTemplate| template.extends.as_deref() == Some(name);
+        let mut users: Vec<String> = self
+            .list_contexts()
+            .into_iter()
+            .filter(|(_, context)| extends(context))
+            .map(|(key, _)| key.to_string())
+            .collect();
+        users.extend(
+            self.list_bases()
+                .into_iter()
+                .filter(|(_, base)| extends(base))
+                .map(|(base, _)| format!("base '{}'", base)),
+        );
+        users.sort();
+        users
+    }
+
+    /// Record a session the human approved in `verification_id` for `owner`, under a new
+    /// random id
+    pub fn create_session(
+        &mut self,
+        verification_id: &str,
+        owner: ContextKey,
+        scope: SessionScope,
+    ) -> Result<&Session> {
+        self.prune_sessions();
+        let id = new_session_id()?;
+        Ok(self
+            .sessions
+            .entry(id.clone())
+            .or_insert_with(|| Session::new(&id, verification_id, owner, scope)))
+    }
+
+    /// Use one approval from a session if it belongs to `owner` and the action is inside its
+    /// scope.
+    ///
+    /// Expired sessions are removed; the error explains why the session did not apply.
+    pub fn use_session(
+        &mut self,
+        id: &str,
+        owner: &ContextKey,
+        action: &str,
+        task_id: Option<&str>,
+    ) -> Result<&Session> {
+        self.prune_sessions();
+        let session = self
+            .sessions
+            .get_mut(id)
+            .ok_or_else(|| anyhow!("Session {} does not exist or has expired", id))?;
+
+        session.check(owner, action, task_id)?;
+        session.uses += 1;
+        Ok(session)
+    }
+
+    /// Active sessions, soonest to expire first
+    pub fn list_sessions(&self) -> Vec<&Session> {
+        let mut sessions: Vec<&Session> =
+            self.sessions.values().filter(|s| !s.is_expired()).collect();
+        sessions.sort_by_key(|s| s.expires_at);
+        sessions
+    }
+
+    /// Revoke a single session; returns false if it did not exist
+    pub fn revoke_session(&mut self, id: &str) -> bool {
+        let revoked = self.sessions.remove(id).is_some();
+        if revoked {
+            println!("🚫 Session revoked: {}", id);
+        }
+        revoked
+    }
+
+    /// Revoke every session and return how many were active
+    pub fn revoke_all_sessions(&mut self) -> usize {
+        self.prune_sessions();
+        let count = self.sessions.len();
+        self.sessions.clear();
+        println!("🚫 All sessions revoked ({})", count);
+        count
+    }
+
+    fn prune_sessions(&mut self) {
+        self.sessions.retain(|_, s| !s.is_expired());
     }
 
     fn validate_port(&self, port: u16, config: &NetworkConfig) -> Result<()> {
@@ -1,3 +1,8 @@
+pub mod actions;
 pub mod context;
+pub mod history;
 pub mod manager;
+pub mod session;
 pub mod storage;
+pub mod template;
+pub mod variables;
@@ -0,0 +1,186 @@
+use crate::instruction::storage::ContextKey;
+use chrono::{DateTime, Duration, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Default lifetime of an approved session
+pub const DEFAULT_SESSION_TTL_MINUTES: u32 = 60;
+
+/// Longest lifetime an agent may request for a session
+pub const MAX_SESSION_TTL_MINUTES: u32 = 8 * 60;
+
+/// What a human approved when choosing "Approve Session".
+///
+/// Agents propose a scope in `VerificationRequest.session_scope`; it is shown in
+/// the dialog and, once approved, bounds every auto-approval made through the session.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SessionScope {
+    /// Action patterns covered by the session; `*` matches any sequence of characters.
+    /// Defaults to the exact action of the approving request.
+    #[serde(default)]
+    pub actions: Vec<String>,
+
+    /// Task the session is bound to. Defaults to `context.task_id` of the approving request.
+    #[serde(default)]
+    pub task_id: Option<String>,
+
+    /// Number of auto-approvals allowed before the session is exhausted
+    #[serde(default)]
+    pub max_uses: Option<u32>,
+
+    /// Requested lifetime in minutes (capped at [`MAX_SESSION_TTL_MINUTES`])
+    #[serde(default)]
+    pub ttl_minutes: Option<u32>,
+}
+
+impl SessionScope {
+    /// Fill in defaults from the request that is being approved
+    pub fn resolve(mut self, action: &str, task_id: Option<&str>) -> Self {
+        if self.actions.is_empty() {
+            self.actions.push(action.to_string());
+        }
+        if self.task_id.is_none() {
+            self.task_id = task_id.map(str::to_string);
+        }
+        self
+    }
+
+    pub fn ttl(&self) -> Duration {
+        let minutes = self
+            .ttl_minutes
+            .unwrap_or(DEFAULT_SESSION_TTL_MINUTES)
+            .clamp(1, MAX_SESSION_TTL_MINUTES);
+        Duration::minutes(i64::from(minutes))
+    }
+
+    pub fn matches_action(&self, action: &str) -> bool {
+        self.actions.iter().any(|p| pattern_matches(p, action))
+    }
+
+    /// Human-readable summary used in dialogs and menus
+    pub fn describe(&self) -> String {
+        let mut parts = vec![format!("actions: {}", self.actions.join(", "))];
+        if let Some(task_id) = &self.task_id {
+            parts.push(format!("task: {}", task_id));
+        }
+        if let Some(max_uses) = self.max_uses {
+            parts.push(format!("max uses: {}", max_uses));
+        }
+        parts.join("; ")
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Session {
+    /// From [`new_session_id`]; agents present it as `session_id` to use the session
+    pub id: String,
+    /// The verification whose approval started the session
+    pub verification_id: String,
+    /// The port and agent that asked for it; nobody else may use it
+    pub owner: ContextKey,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub scope: SessionScope,
+    pub uses: u32,
+}
+
+impl Session {
+    pub fn new(id: &str, verification_id: &str, owner: ContextKey, scope: SessionScope) -> Self {
+        let created_at = Utc::now();
+        Self {
+            id: id.to_string(),
+            verification_id: verification_id.to_string(),
+            owner,
+            created_at,
+            expires_at: created_at + scope.ttl(),
+            scope,
+            uses: 0,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now()
+    }
+
+    /// Time left before the session expires (zero once expired)
+    pub fn remaining(&self) -> Duration {
+        (self.expires_at - Utc::now()).max(Duration::zero())
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.scope.max_uses.is_some_and(|max| self.uses >= max)
+    }
+
+    /// Check whether `owner` may use this session for an action inside its approved scope
+    pub fn check(
+        &self,
+        owner: &ContextKey,
+        action: &str,
+        task_id: Option<&str>,
+    ) -> anyhow::Result<()> {
+        if &self.owner != owner {
+            return Err(anyhow::anyhow!(
+                "Session {} belongs to {}, not {}",
+                self.id,
+                self.owner,
+                owner
+            ));
+        }
+        if self.is_expired() {
+            return Err(anyhow::anyhow!("Session {} has expired", self.id));
+        }
+        if self.is_exhausted() {
+            return Err(anyhow::anyhow!(
+                "Session {} has used all {} approvals",
+                self.id,
+                self.uses
+            ));
+        }
+        if let Some(scoped_task) = &self.scope.task_id {
+            if task_id != Some(scoped_task.as_str()) {
+                return Err(anyhow::anyhow!(
+                    "Session {} is limited to task '{}'",
+                    self.id,
+                    scoped_task
+                ));
+            }
+        }
+        if !self.scope.matches_action(action) {
+            return Err(anyhow::anyhow!(
+                "Action '{}' is outside the scope of session {}",
+                action,
+                self.id
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A new random session id. It lets an agent skip the human, so it carries no timestamp
+/// or counter that could be guessed.
+pub fn new_session_id() -> anyhow::Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| anyhow::anyhow!("Failed to generate session id: {}", e))?;
+    Ok(format!("s-{}", hex::encode(bytes)))
+}
+
+/// Match an action against a pattern where `*` stands for any sequence of characters
+pub fn pattern_matches(pattern: &str, action: &str) -> bool {
+    let escaped = regex::escape(pattern).replace(r"\*", ".*");
+    Regex::new(&format!("^{}$", escaped))
+        .map(|re| re.is_match(action))
+        .unwrap_or(false)
+}
+
+/// Compact remaining-time label such as `1h 05m`, `42m` or `<1m`
+pub fn format_remaining(remaining: Duration) -> String {
+    let minutes = remaining.num_minutes();
+    if minutes < 1 {
+        "<1m".to_string()
+    } else if minutes < 60 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    }
+}
@@ -1,22 +1,81 @@
-use crate::instruction::context::InstructionContext;
+use crate::instruction::template::ContextTemplate;
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub struct ContextStorage {
     config_path: PathBuf,
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
+/// Whose context: every agent on a port, or one agent on it
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ContextKey {
+    pub port: u16,
+    /// Agent name from its client certificate or agent token;
+    /// `None` for the port's context, which agents without their own fall back to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent: Option<String>,
+}
+
+impl ContextKey {
+    pub fn agent(port: u16, agent: impl Into<String>) -> Self {
+        Self {
+            port,
+            agent: Some(agent.into()),
+        }
+    }
+
+    /// The port's context, which this key falls back to
+    pub fn port_key(&self) -> Self {
+        Self::from(self.port)
+    }
+}
+
+impl From<u16> for ContextKey {
+    fn from(port: u16) -> Self {
+        Self { port, agent: None }
+    }
+}
+
+impl fmt::Display for ContextKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.agent {
+            Some(agent) => write!(f, "port {} (agent '{}')", self.port, agent),
+            None => write!(f, "port {}", self.port),
+        }
+    }
+}
+
+/// Everything in `contexts.json`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StoredContexts {
+    pub contexts: HashMap<ContextKey, ContextTemplate>,
+    /// Named base contexts that port contexts can `extends`
+    pub bases: HashMap<String, ContextTemplate>,
+}
+
+#[derive(Serialize, Deserialize)]
 struct ContextsContainer {
     contexts: Vec<ContextEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    bases: Vec<BaseEntry>,
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
+/// Files written before per-agent contexts have no `agent`: they load as port contexts
+#[derive(Serialize, Deserialize)]
 struct ContextEntry {
-    port: u16,
-    context: InstructionContext,
+    #[serde(flatten)]
+    key: ContextKey,
+    context: ContextTemplate,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BaseEntry {
+    name: String,
+    context: ContextTemplate,
 }
 
 impl ContextStorage {
@@ -38,32 +97,57 @@ impl ContextStorage {
         Self { config_path: path }
     }
 
-    pub fn load_all(&self) -> Result<HashMap<u16, InstructionContext>> {
+    /// Directory holding `contexts.json`; other ChaseAI data files live next to it
+    pub fn config_dir(&self) -> PathBuf {
+        self.config_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default()
+    }
+
+    pub fn load_all(&self) -> Result<StoredContexts> {
         if !self.config_path.exists() {
-            return Ok(HashMap::new());
+            return Ok(StoredContexts::default());
         }
 
         let content = fs::read_to_string(&self.config_path)?;
         let container: ContextsContainer = serde_json::from_str(&content)?;
 
-        let mut map = HashMap::new();
-        for entry in container.contexts {
-            map.insert(entry.port, entry.context);
-        }
-
-        Ok(map)
+        Ok(StoredContexts {
+            contexts: container
+                .contexts
+                .into_iter()
+                .map(|entry| (entry.key, entry.context))
+                .collect(),
+            bases: container
+                .bases
+                .into_iter()
+                .map(|entry| (entry.name, entry.context))
+                .collect(),
+        })
     }
 
-    pub fn save_all(&self, contexts: &HashMap<u16, InstructionContext>) -> Result<()> {
-        let entries: Vec<ContextEntry> = contexts
+    pub fn save_all(&self, stored: &StoredContexts) -> Result<()> {
+        let mut contexts: Vec<ContextEntry> = stored
+            .contexts
+            .iter()
+            .map(|(key, context)| ContextEntry {
+                key: key.clone(),
+                context: context.clone(),
+            })
+            .collect();
+        contexts.sort_by(|a, b| a.key.cmp(&b.key));
+        let mut bases: Vec<BaseEntry> = stored
+            .bases
             .iter()
-            .map(|(port, context)| ContextEntry {
-                port: *port,
+            .map(|(name, context)| BaseEntry {
+                name: name.clone(),
                 context: context.clone(),
             })
             .collect();
+        bases.sort_by(|a, b| a.name.cmp(&b.name));
 
-        let container = ContextsContainer { contexts: entries };
+        let container = ContextsContainer { contexts, bases };
         let content = serde_json::to_string_pretty(&container)?;
 
         if let Some(parent) = self.config_path.parent() {
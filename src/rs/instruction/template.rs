@@ -0,0 +1,120 @@
+use crate::instruction::context::{ContextError, ContextErrors, InstructionContext};
+use crate::instruction::variables::ContextVariables;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A context as written for a port or a named base: any field may be left out and
+/// taken from the base context it `extends`.
+///
+/// Strings and `verification_required` override the base; `allowed_actions` add to it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ContextTemplate {
+    /// Name of the base context to start from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_instruction: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_actions: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification_required: Option<bool>,
+}
+
+impl From<InstructionContext> for ContextTemplate {
+    fn from(context: InstructionContext) -> Self {
+        Self {
+            extends: None,
+            system: Some(context.system),
+            role: Some(context.role),
+            base_instruction: Some(context.base_instruction),
+            allowed_actions: context.allowed_actions,
+            verification_required: Some(context.verification_required),
+        }
+    }
+}
+
+impl ContextTemplate {
+    /// The context this template stands for once its bases are applied, validated against
+    /// `variables`. Left unset all the way down, `verification_required` is `true`.
+    pub fn resolve(
+        &self,
+        bases: &HashMap<String, ContextTemplate>,
+        variables: &ContextVariables,
+    ) -> Result<InstructionContext, ContextErrors> {
+        let merged = self.flatten(bases, &mut Vec::new())?;
+        let context = InstructionContext {
+            system: merged.system.unwrap_or_default(),
+            role: merged.role.unwrap_or_default(),
+            base_instruction: merged.base_instruction.unwrap_or_default(),
+            allowed_actions: merged.allowed_actions,
+            verification_required: merged.verification_required.unwrap_or(true),
+        };
+        let errors = context.errors(variables);
+        if errors.is_empty() {
+            Ok(context)
+        } else {
+            Err(ContextErrors(errors))
+        }
+    }
+
+    /// This template over its chain of bases; `chain` holds the bases already visited
+    fn flatten(
+        &self,
+        bases: &HashMap<String, ContextTemplate>,
+        chain: &mut Vec<String>,
+    ) -> Result<ContextTemplate, ContextErrors> {
+        let Some(name) = &self.extends else {
+            return Ok(self.clone());
+        };
+        if chain.contains(name) {
+            chain.push(name.clone());
+            return Err(extends_error(format!(
+                "Base contexts extend each other in a cycle: {}",
+                chain.join(" -> ")
+            )));
+        }
+        let base = bases
+            .get(name)
+            .ok_or_else(|| extends_error(format!("No base context named '{}'", name)))?;
+        chain.push(name.clone());
+        let base = base.flatten(bases, chain)?;
+        Ok(self.over(base))
+    }
+
+    /// This template's fields on top of `base`'s
+    fn over(&self, base: ContextTemplate) -> ContextTemplate {
+        let mut allowed_actions = base.allowed_actions;
+        for action in &self.allowed_actions {
+            if !allowed_actions.contains(action) {
+                allowed_actions.push(action.clone());
+            }
+        }
+        ContextTemplate {
+            extends: None,
+            system: self.system.clone().or(base.system),
+            role: self.role.clone().or(base.role),
+            base_instruction: self.base_instruction.clone().or(base.base_instruction),
+            allowed_actions,
+            verification_required: self.verification_required.or(base.verification_required),
+        }
+    }
+}
+
+/// Base names are lowercase letters, numbers, hyphens and underscores
+pub fn is_valid_base_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+fn extends_error(message: String) -> ContextErrors {
+    ContextErrors(vec![ContextError {
+        field: "extends".to_string(),
+        message,
+    }])
+}
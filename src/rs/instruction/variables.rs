@@ -0,0 +1,131 @@
+use crate::config::network_config::NetworkConfig;
+use crate::network::port_config::{PortBinding, PortRole};
+use std::collections::{BTreeMap, HashMap};
+
+/// Variables every `base_instruction` may use, filled in for the port serving it.
+/// There is no `project_dir`: ChaseAI serves agents working anywhere, and its own
+/// directory is none of theirs. Set one in `[context_variables]` instead.
+pub const BUILTIN_VARIABLES: &[&str] = &["port", "verify_url", "user", "date"];
+
+/// What `{{name}}` placeholders render to, taken from `network.toml`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContextVariables {
+    /// Verification endpoint an agent on each configured port should call
+    verify_urls: HashMap<u16, String>,
+    /// `[context_variables]`; these win over the built-in values of the same name
+    custom: BTreeMap<String, String>,
+}
+
+impl ContextVariables {
+    pub fn from_config(config: &NetworkConfig) -> Self {
+        let verify_urls = config
+            .port_bindings
+            .iter()
+            .filter_map(|binding| {
+                verify_binding(config, binding.port)
+                    .map(|verify| (binding.port, format!("{}/verify", verify.base_url())))
+            })
+            .collect();
+        Self {
+            verify_urls,
+            custom: config.context_variables.clone(),
+        }
+    }
+
+    pub fn is_known(&self, name: &str) -> bool {
+        BUILTIN_VARIABLES.contains(&name) || self.custom.contains_key(name)
+    }
+
+    /// Value of every known variable for an agent on `port`. `verify_url` is empty when
+    /// no verification port is configured.
+    pub fn values(&self, port: u16) -> HashMap<String, String> {
+        let mut values = HashMap::from([
+            ("port".to_string(), port.to_string()),
+            (
+                "verify_url".to_string(),
+                self.verify_urls.get(&port).cloned().unwrap_or_default(),
+            ),
+            (
+                "user".to_string(),
+                std::env::var("USER")
+                    .or_else(|_| std::env::var("USERNAME"))
+                    .unwrap_or_default(),
+            ),
+            (
+                "date".to_string(),
+                chrono::Local::now().format("%Y-%m-%d").to_string(),
+            ),
+        ]);
+        values.extend(self.custom.clone());
+        values
+    }
+}
+
+/// The port's own binding when it verifies, else an enabled verification binding on the
+/// same address, else any enabled one
+fn verify_binding(config: &NetworkConfig, port: u16) -> Option<&PortBinding> {
+    let binding = config.binding(port)?;
+    if binding.role == PortRole::Verification {
+        return Some(binding);
+    }
+    let verifiers: Vec<_> = config
+        .port_bindings
+        .iter()
+        .filter(|b| b.enabled && b.role == PortRole::Verification)
+        .collect();
+    verifiers
+        .iter()
+        .find(|b| b.interface.ip_address == binding.interface.ip_address)
+        .or(verifiers.first())
+        .copied()
+}
+
+/// Names of the `{{name}}` placeholders in `text`, in order. Spaces inside the braces
+/// are allowed; a `{{` without its `}}` or with an empty or malformed name is an error.
+pub fn placeholders(text: &str) -> Result<Vec<&str>, String> {
+    let mut names = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| "Unclosed '{{' placeholder".to_string())?;
+        let name = after[..end].trim();
+        if !is_variable_name(name) {
+            return Err(format!(
+                "Invalid placeholder '{{{{{}}}}}': names use lowercase letters, numbers and underscores",
+                &after[..end]
+            ));
+        }
+        names.push(name);
+        rest = &after[end + 2..];
+    }
+    Ok(names)
+}
+
+/// `text` with every placeholder replaced by its value; unknown names are left as they are
+pub fn render(text: &str, values: &HashMap<String, String>) -> String {
+    let mut rendered = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            break;
+        };
+        rendered.push_str(&rest[..start]);
+        match values.get(after[..end].trim()) {
+            Some(value) => rendered.push_str(value),
+            None => rendered.push_str(&rest[start..start + end + 4]),
+        }
+        rest = &after[end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+fn is_variable_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
@@ -1,7 +1,9 @@
+mod cli;
 mod platform;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 // Re-export app module so it's accessible from main
+#[cfg(feature = "tray")]
 pub use app::App;
 
 #[derive(Parser)]
@@ -11,6 +13,49 @@ struct Cli {
     /// Verification request in JSON format
     #[arg(long)]
     verification: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Inspect and revoke approved sessions
+    Sessions {
+        #[command(subcommand)]
+        command: cli::sessions::SessionsCommand,
+    },
+    /// Print the bearer token agents must send to a port
+    Token {
+        /// Port to print the token for (defaults to the verification port)
+        #[arg(long)]
+        port: Option<u16>,
+    },
+    /// Check the verification history for tampering
+    History {
+        #[command(subcommand)]
+        command: cli::history::HistoryCommand,
+    },
+    /// Manage client certificates and tokens that identify agents
+    Agents {
+        #[command(subcommand)]
+        command: cli::agents::AgentsCommand,
+    },
+    /// Answer waiting verifications in a terminal UI (needs the console prompter)
+    Console,
+    /// Serve the configured ports without a tray, e.g. on servers and CI runners
+    Daemon(cli::daemon::DaemonArgs),
+    /// Add, remove, enable and disable ports, live or in network.toml
+    Ports(cli::ports::PortsArgs),
+    /// Show the detected network interfaces or serve every port on another one
+    Interface(cli::interface::InterfaceArgs),
+    /// Show or switch how agents ask for verification
+    Mode(cli::mode::ModeArgs),
+    /// Read and edit the instruction context served on each port
+    Context {
+        #[command(subcommand)]
+        command: cli::context::ContextCommand,
+    },
 }
 
 fn main() -> anyhow::Result<()> {
@@ -20,7 +65,19 @@ fn main() -> anyhow::Result<()> {
         return handle_verification(verification_data);
     }
 
-    platform::run()
+    match cli.command {
+        Some(Command::Sessions { command }) => cli::sessions::run(command),
+        Some(Command::History { command }) => cli::history::run(command),
+        Some(Command::Token { port }) => cli::token::run(port),
+        Some(Command::Agents { command }) => cli::agents::run(command),
+        Some(Command::Console) => cli::console::run(),
+        Some(Command::Daemon(args)) => cli::daemon::run(args),
+        Some(Command::Ports(args)) => cli::ports::run(args),
+        Some(Command::Interface(args)) => cli::interface::run(args),
+        Some(Command::Mode(args)) => cli::mode::run(args),
+        Some(Command::Context { command }) => cli::context::run(command),
+        None => platform::run(),
+    }
 }
 
 fn handle_verification(data: String) -> anyhow::Result<()> {
@@ -28,25 +85,14 @@ fn handle_verification(data: String) -> anyhow::Result<()> {
     let config = app::config::network_config::NetworkConfig::load()?;
 
     // 2. Find the first enabled verification port
-    let binding = config
-        .port_bindings
-        .iter()
-        .find(|b| b.role == app::network::port_config::PortRole::Verification && b.enabled)
-        .ok_or_else(|| {
-            anyhow::anyhow!("No enabled verification port found. Is ChaseAI running and configured with a verification port?")
-        })?;
-
-    let url = format!(
-        "http://{}:{}/verify",
-        binding.interface.ip_address, binding.port
-    );
+    let url = format!("{}/verify", cli::instance_url(&config)?);
 
     // 3. Parse data to ensure it's valid JSON
     let json_data: serde_json::Value = serde_json::from_str(&data)
         .map_err(|e| anyhow::anyhow!("Invalid JSON provided for verification: {}", e))?;
 
-    // 4. Send request
-    let client = reqwest::blocking::Client::new();
+    // 4. Send request, authenticated with the binding's token
+    let client = cli::instance_client(&config, reqwest::blocking::Client::builder())?;
     let resp = client.post(url).json(&json_data).send()?;
 
     if resp.status().is_success() {
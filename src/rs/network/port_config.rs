@@ -1,7 +1,8 @@
-use crate::network::interface_detector::NetworkInterface;
+use crate::network::interface_detector::{InterfaceType, NetworkInterface};
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::net::SocketAddr;
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum PortRole {
@@ -15,6 +16,56 @@ pub struct PortBinding {
     pub interface: NetworkInterface,
     pub role: PortRole,
     pub enabled: bool,
+    /// Bearer token required on every route except `/health`; `None` leaves the port open
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Require a client certificate issued by `chase agents issue`; implies TLS
+    #[serde(default)]
+    pub mtls: bool,
+}
+
+impl PortBinding {
+    /// Whether the binding is only reachable from this machine
+    pub fn is_loopback(&self) -> bool {
+        self.interface.ip_address.is_loopback()
+    }
+
+    /// LAN, public and mTLS bindings are served over HTTPS with the local CA
+    pub fn uses_tls(&self) -> bool {
+        self.mtls
+            || matches!(
+                self.interface.interface_type,
+                InterfaceType::Lan | InterfaceType::Public
+            )
+    }
+
+    /// `http(s)://ip:port` for this binding
+    pub fn base_url(&self) -> String {
+        let scheme = if self.uses_tls() { "https" } else { "http" };
+        format!(
+            "{}://{}",
+            scheme,
+            SocketAddr::new(self.interface.ip_address, self.port)
+        )
+    }
+}
+
+/// Prefix that makes ChaseAI tokens recognizable in logs and secret scanners
+pub const TOKEN_PREFIX: &str = "chai_";
+
+/// Generate a new random API token
+pub fn generate_token() -> Result<String> {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).map_err(|e| anyhow!("Failed to generate token: {}", e))?;
+    Ok(format!("{}{}", TOKEN_PREFIX, hex::encode(bytes)))
+}
+
+/// Whether ChaseAI may bind `port`; the tray, admin API and CLI all check this
+pub fn validate_port(port: u16) -> Result<()> {
+    if port < 1024 {
+        return Err(anyhow!("Ports below 1024 are reserved for system services"));
+    }
+    Ok(())
 }
 
 pub struct PortConfig {
@@ -53,10 +104,7 @@ impl PortConfig {
     }
 
     pub fn validate_port(&self, port: u16) -> Result<()> {
-        if port < 1024 {
-            return Err(anyhow!("Ports below 1024 are reserved for system services"));
-        }
-        Ok(())
+        validate_port(port)
     }
 }
 
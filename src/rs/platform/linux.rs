@@ -1,5 +1,9 @@
+use app::config::network_config::NetworkConfig;
+use app::daemon::Daemon;
+
+/// Linux has no tray yet: serve the configured ports headless, like `chase daemon`.
+/// Verifications are answered with the configured prompter, e.g. `chase console`.
 pub fn run() -> anyhow::Result<()> {
-    println!("Starting ChaseAI on Linux...");
-    eprintln!("Linux support is not yet implemented");
-    std::process::exit(1);
+    println!("Starting ChaseAI on Linux (headless)...");
+    Daemon::new(NetworkConfig::load()?)?.run()
 }
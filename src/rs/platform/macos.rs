@@ -1,8 +1,14 @@
 use crate::App;
+use app::command::AppCommand;
 use cocoa::appkit::{NSApp, NSApplication, NSApplicationActivationPolicy};
-use tao::event_loop::{ControlFlow, EventLoop};
+use std::time::{Duration, Instant};
+use tao::event::{Event, StartCause};
+use tao::event_loop::{ControlFlow, EventLoopBuilder};
 use tray_icon::menu::MenuEvent;
 
+/// How often the tray wakes up to refresh session countdowns
+const SESSION_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
 pub fn run() -> anyhow::Result<()> {
     println!("Starting ChaseAI on macOS...");
 
@@ -14,8 +20,10 @@ pub fn run() -> anyhow::Result<()> {
         );
     }
 
-    // Create tao event loop first (this initializes NSApp)
-    let event_loop = EventLoop::new();
+    // Create tao event loop first (this initializes NSApp).
+    // User events wake it when a command is queued.
+    let event_loop = EventLoopBuilder::<()>::with_user_event().build();
+    let proxy = event_loop.create_proxy();
 
     // Enforce activation policy AFTER EventLoop (override if it reset it)
     unsafe {
@@ -26,37 +34,42 @@ pub fn run() -> anyhow::Result<()> {
     }
 
     // Create and initialize app
-    let mut app_instance = App::new();
+    let mut app = App::new();
+    app.commands().set_waker(move || {
+        let _ = proxy.send_event(());
+    });
 
-    // Run app initialization (this sets up tray)
-    app_instance.run()?;
+    // Menu clicks become commands in the same queue as the admin API's
+    let commands = app.commands().clone();
+    MenuEvent::set_event_handler(Some(
+        move |event: MenuEvent| match AppCommand::from_menu_id(event.id.as_ref()) {
+            Some(command) => commands.submit(command),
+            None => println!("Unknown menu event: {:?}", event.id),
+        },
+    ));
 
-    // Store app instance as static (not ideal but works for menu bar app)
-    static mut APP_INSTANCE: Option<App> = None;
-    unsafe {
-        APP_INSTANCE = Some(app_instance);
-    }
+    // Run app initialization (this sets up tray)
+    app.run()?;
 
     println!("Setting up event loop...");
     println!("Entering main loop...");
     println!("Application is running. Tray icon should be visible and clickable in menu bar.");
 
-    // Run the event loop
-    event_loop.run(move |_event, _, control_flow| {
-        *control_flow = ControlFlow::Wait;
+    // Run the event loop; it owns the app from here on
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::WaitUntil(Instant::now() + SESSION_REFRESH_INTERVAL);
 
-        // Check for menu events
-        while let Ok(menu_event) = MenuEvent::receiver().try_recv() {
-            println!("Menu event received: {:?}", menu_event.id);
-            unsafe {
-                if let Some(ref mut app) = APP_INSTANCE {
-                    app.handle_menu_event(menu_event);
+        match event {
+            // Periodic wake-up: keep "Active Sessions" remaining times current
+            Event::NewEvents(StartCause::ResumeTimeReached { .. }) => {
+                app.refresh_sessions_menu();
+            }
+            Event::UserEvent(()) => {
+                if app.process_commands() {
+                    *control_flow = ControlFlow::Exit;
                 }
             }
+            _ => {}
         }
     });
-
-    // Note: run() theoretically diverts control, but for the Result signature:
-    #[allow(unreachable_code)]
-    Ok(())
 }
@@ -1,4 +1,4 @@
-#[cfg(target_os = "macos")]
+#[cfg(all(target_os = "macos", feature = "tray"))]
 pub mod macos;
 
 #[cfg(target_os = "windows")]
@@ -7,9 +7,16 @@ pub mod windows;
 #[cfg(target_os = "linux")]
 pub mod linux;
 
-#[cfg(target_os = "macos")]
+#[cfg(all(target_os = "macos", feature = "tray"))]
 pub use macos::run;
 
+/// Built without the tray: serve the configured ports headless, like `chase daemon`
+#[cfg(all(target_os = "macos", not(feature = "tray")))]
+pub fn run() -> anyhow::Result<()> {
+    use app::config::network_config::NetworkConfig;
+    app::daemon::Daemon::new(NetworkConfig::load()?)?.run()
+}
+
 #[cfg(target_os = "windows")]
 pub use windows::run;
 
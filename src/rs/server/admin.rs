@@ -0,0 +1,627 @@
+use crate::command::{AppCommand, CommandChannel, CommandError, CommandOutcome, CommandResult};
+use crate::config::change::{ConfigChange, ConfigChangeError};
+use crate::config::network_config::{NetworkConfig, VerificationMode};
+use crate::instruction::context::{ContextError, ContextErrors, InstructionContext};
+use crate::instruction::history::{ContextAuthor, ContextRevision};
+use crate::instruction::manager::ContextManager;
+use crate::instruction::storage::ContextKey;
+use crate::instruction::template::{is_valid_base_name, ContextTemplate};
+use crate::network::interface_detector::{InterfaceDetector, InterfaceType, NetworkInterface};
+use crate::network::port_config::{generate_token, PortBinding, PortRole};
+use axum::{
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, patch, post, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+/// A binding as the admin API shows it, without its token
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AdminPort {
+    pub port: u16,
+    pub role: PortRole,
+    pub enabled: bool,
+    pub interface: NetworkInterface,
+    pub mtls: bool,
+    pub base_url: String,
+}
+
+impl From<&PortBinding> for AdminPort {
+    fn from(binding: &PortBinding) -> Self {
+        Self {
+            port: binding.port,
+            role: binding.role,
+            enabled: binding.enabled,
+            interface: binding.interface.clone(),
+            mtls: binding.mtls,
+            base_url: binding.base_url(),
+        }
+    }
+}
+
+impl AdminPort {
+    /// Every binding in `config`
+    pub fn list(config: &NetworkConfig) -> Vec<Self> {
+        config.port_bindings.iter().map(Self::from).collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AdminInterface {
+    pub default_interface: InterfaceType,
+    /// Interfaces detected on this machine, by name for `PUT /admin/interface`
+    pub available: Vec<NetworkInterface>,
+}
+
+impl From<&NetworkConfig> for AdminInterface {
+    fn from(config: &NetworkConfig) -> Self {
+        Self {
+            default_interface: config.default_interface.clone(),
+            available: InterfaceDetector::detect_all().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AdminMode {
+    pub mode: VerificationMode,
+}
+
+impl From<&NetworkConfig> for AdminMode {
+    fn from(config: &NetworkConfig) -> Self {
+        Self {
+            mode: config.verification_mode,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewPort {
+    pub port: u16,
+    pub role: PortRole,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PortUpdate {
+    pub enabled: Option<bool>,
+    pub role: Option<PortRole>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AllPortsUpdate {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InterfaceUpdate {
+    pub name: String,
+}
+
+/// A port's or an agent's instruction context, as `GET /contexts` lists it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PortContext {
+    pub port: u16,
+    /// Set for a context of one agent on the port
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent: Option<String>,
+    /// As written, possibly extending a base
+    pub context: ContextTemplate,
+    /// What `GET /context` serves the port's agents, or this agent; absent if it does not resolve
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved: Option<InstructionContext>,
+}
+
+impl PortContext {
+    /// The context stored under exactly `key`
+    pub fn new(manager: &ContextManager, key: impl Into<ContextKey>) -> Option<Self> {
+        let key = key.into();
+        Some(Self {
+            context: manager.template(key.clone())?.clone(),
+            resolved: manager.get_context(key.clone()),
+            port: key.port,
+            agent: key.agent,
+        })
+    }
+
+    /// Every context in `manager`, by port, each port's own before its agents'
+    pub fn list(manager: &ContextManager) -> Vec<Self> {
+        manager
+            .list_contexts()
+            .into_iter()
+            .filter_map(|(key, _)| Self::new(manager, key.clone()))
+            .collect()
+    }
+
+    pub fn key(&self) -> ContextKey {
+        ContextKey {
+            port: self.port,
+            agent: self.agent.clone(),
+        }
+    }
+}
+
+/// A named base context, as `GET /contexts/bases` lists it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BaseContext {
+    pub name: String,
+    pub context: ContextTemplate,
+}
+
+impl BaseContext {
+    pub fn list(manager: &ContextManager) -> Vec<Self> {
+        manager
+            .list_bases()
+            .into_iter()
+            .map(|(name, context)| Self {
+                name: name.to_string(),
+                context: context.clone(),
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ContextQuery {
+    /// Defaults to the port serving the request
+    pub port: Option<u16>,
+    /// One agent's context on the port instead of the port's own
+    pub agent: Option<String>,
+}
+
+impl ContextQuery {
+    fn key(self, serving: u16) -> ContextKey {
+        ContextKey {
+            port: self.port.unwrap_or(serving),
+            agent: self.agent,
+        }
+    }
+}
+
+/// A token just given to an agent; only shown once, in this answer
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AgentToken {
+    pub agent: String,
+    pub token: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HistoryQuery {
+    /// Only this port's revisions; every port's when absent
+    pub port: Option<u16>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Rollback {
+    pub rev: u64,
+}
+
+/// Tray actions as REST routes; served on loopback bindings only, behind the admin secret
+pub fn router<S: Clone + Send + Sync + 'static>() -> Router<S> {
+    Router::new()
+        .route(
+            "/admin/ports",
+            get(list_ports).post(add_port).patch(update_all_ports),
+        )
+        .route("/admin/ports/:port", patch(update_port).delete(remove_port))
+        .route("/admin/interface", get(get_interface).put(set_interface))
+        .route("/admin/mode", get(get_mode).put(set_mode))
+        .route(
+            "/admin/agents/:name/token",
+            put(issue_agent_token).delete(revoke_agent_token),
+        )
+}
+
+/// Writing instruction contexts, next to the read-only `GET /context` agents use; loopback
+/// bindings only, behind the admin secret like [`router`]
+pub fn context_router() -> Router<Arc<Mutex<ContextManager>>> {
+    Router::new()
+        .route("/context", put(set_context).delete(delete_context))
+        .route("/context/history", get(context_history))
+        .route("/context/rollback", post(rollback_context))
+        .route("/contexts", get(list_contexts))
+        .route("/contexts/bases", get(list_bases))
+        .route("/contexts/bases/:name", put(set_base).delete(delete_base))
+}
+
+async fn list_ports(Extension(commands): Extension<CommandChannel>) -> Response {
+    respond(configure(&commands, Vec::new()).await, ports)
+}
+
+async fn add_port(
+    Extension(commands): Extension<CommandChannel>,
+    Json(new): Json<NewPort>,
+) -> Response {
+    // Same interface and token as a port added from the tray
+    let binding = match configure(&commands, Vec::new()).await {
+        Some(Ok(CommandOutcome::Unchanged(config))) => {
+            config.new_binding(new.port, new.role, new.enabled)
+        }
+        other => return respond(other, ports),
+    };
+    let result = configure(&commands, vec![ConfigChange::AddPort { binding }]).await;
+    match respond(result, |config| port(config, new.port)) {
+        created if created.status() == StatusCode::OK => {
+            (StatusCode::CREATED, created).into_response()
+        }
+        other => other,
+    }
+}
+
+async fn update_all_ports(
+    Extension(commands): Extension<CommandChannel>,
+    Json(update): Json<AllPortsUpdate>,
+) -> Response {
+    let change = ConfigChange::SetAllEnabled {
+        enabled: update.enabled,
+    };
+    respond(configure(&commands, vec![change]).await, ports)
+}
+
+async fn update_port(
+    Extension(commands): Extension<CommandChannel>,
+    Path(port): Path<u16>,
+    Json(update): Json<PortUpdate>,
+) -> Response {
+    let mut changes = Vec::new();
+    if let Some(enabled) = update.enabled {
+        changes.push(ConfigChange::SetPortEnabled { port, enabled });
+    }
+    if let Some(role) = update.role {
+        changes.push(ConfigChange::SetPortRole { port, role });
+    }
+    if changes.is_empty() {
+        return error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "empty_update",
+            "Set \"enabled\", \"role\" or both",
+        );
+    }
+    respond(configure(&commands, changes).await, |config| {
+        self::port(config, port)
+    })
+}
+
+async fn remove_port(
+    Extension(commands): Extension<CommandChannel>,
+    Path(port): Path<u16>,
+) -> Response {
+    let change = ConfigChange::RemovePort { port };
+    respond(configure(&commands, vec![change]).await, ports)
+}
+
+async fn get_interface(Extension(commands): Extension<CommandChannel>) -> Response {
+    respond(configure(&commands, Vec::new()).await, interface)
+}
+
+async fn set_interface(
+    Extension(commands): Extension<CommandChannel>,
+    Json(update): Json<InterfaceUpdate>,
+) -> Response {
+    let command = AppCommand::SelectInterface { name: update.name };
+    respond(commands.send(command).await, self::interface)
+}
+
+async fn get_mode(Extension(commands): Extension<CommandChannel>) -> Response {
+    respond(configure(&commands, Vec::new()).await, mode)
+}
+
+async fn set_mode(
+    Extension(commands): Extension<CommandChannel>,
+    Json(update): Json<AdminMode>,
+) -> Response {
+    let change = ConfigChange::SetVerificationMode { mode: update.mode };
+    respond(configure(&commands, vec![change]).await, mode)
+}
+
+/// A new token for `name`, replacing any it had
+async fn issue_agent_token(
+    Extension(commands): Extension<CommandChannel>,
+    Path(agent): Path<String>,
+) -> Response {
+    let token = match generate_token() {
+        Ok(token) => token,
+        Err(e) => {
+            return error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "token_failed",
+                &e.to_string(),
+            )
+        }
+    };
+    let change = ConfigChange::SetAgentToken {
+        agent: agent.clone(),
+        token: Some(token.clone()),
+    };
+    respond(configure(&commands, vec![change]).await, |_| {
+        Json(AgentToken { agent, token }).into_response()
+    })
+}
+
+async fn revoke_agent_token(
+    Extension(commands): Extension<CommandChannel>,
+    Path(agent): Path<String>,
+) -> Response {
+    let change = ConfigChange::SetAgentToken { agent, token: None };
+    respond(configure(&commands, vec![change]).await, |_| {
+        StatusCode::NO_CONTENT.into_response()
+    })
+}
+
+async fn list_contexts(State(manager): State<Arc<Mutex<ContextManager>>>) -> Response {
+    match manager.lock() {
+        Ok(manager) => Json(PortContext::list(&manager)).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+async fn set_context(
+    State(manager): State<Arc<Mutex<ContextManager>>>,
+    Extension(commands): Extension<CommandChannel>,
+    Extension(serving): Extension<u16>,
+    Query(query): Query<ContextQuery>,
+    author: ContextAuthor,
+    Json(context): Json<ContextTemplate>,
+) -> Response {
+    let key = query.key(serving);
+    let port = key.port;
+    // The live config, as the tray and the admin routes see it
+    let config = match configure(&commands, Vec::new()).await {
+        Some(Ok(CommandOutcome::Unchanged(config))) => config,
+        other => return respond(other, ports),
+    };
+    match config.binding(port) {
+        None => return rejected(ConfigChangeError::UnknownPort(port)),
+        Some(binding) if !binding.enabled => {
+            return error(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "port_disabled",
+                &format!("Port {} is disabled", port),
+            )
+        }
+        Some(_) => {}
+    }
+
+    let Ok(mut manager) = manager.lock() else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    match manager.set_context(key.clone(), context, &config, author) {
+        Ok(_) => Json(PortContext::new(&manager, key)).into_response(),
+        Err(e) => context_failed(e),
+    }
+}
+
+async fn delete_context(
+    State(manager): State<Arc<Mutex<ContextManager>>>,
+    Extension(serving): Extension<u16>,
+    Query(query): Query<ContextQuery>,
+    author: ContextAuthor,
+) -> Response {
+    let key = query.key(serving);
+    let Ok(mut manager) = manager.lock() else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    if manager.template(key.clone()).is_none() {
+        return error(
+            StatusCode::NOT_FOUND,
+            "no_context",
+            &format!("No instruction context for {}", key),
+        );
+    }
+    match manager.delete_context(key, author) {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "save_failed",
+            &e.to_string(),
+        ),
+    }
+}
+
+async fn list_bases(State(manager): State<Arc<Mutex<ContextManager>>>) -> Response {
+    match manager.lock() {
+        Ok(manager) => Json(BaseContext::list(&manager)).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+async fn set_base(
+    State(manager): State<Arc<Mutex<ContextManager>>>,
+    Path(name): Path<String>,
+    author: ContextAuthor,
+    Json(context): Json<ContextTemplate>,
+) -> Response {
+    if !is_valid_base_name(&name) {
+        return error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "invalid_name",
+            "Base names are lowercase letters, numbers, hyphens and underscores",
+        );
+    }
+    let Ok(mut manager) = manager.lock() else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    match manager.set_base(&name, context.clone(), author) {
+        Ok(()) => Json(BaseContext { name, context }).into_response(),
+        Err(e) => context_failed(e),
+    }
+}
+
+async fn delete_base(
+    State(manager): State<Arc<Mutex<ContextManager>>>,
+    Path(name): Path<String>,
+    author: ContextAuthor,
+) -> Response {
+    let Ok(mut manager) = manager.lock() else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let users = manager.base_users(&name);
+    if !users.is_empty() {
+        return error(
+            StatusCode::CONFLICT,
+            "base_in_use",
+            &format!(
+                "Base context '{}' is extended by {}",
+                name,
+                users.join(", ")
+            ),
+        );
+    }
+    match manager.delete_base(&name, author) {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => error(
+            StatusCode::NOT_FOUND,
+            "unknown_base",
+            &format!("No base context named '{}'", name),
+        ),
+        Err(e) => context_failed(e),
+    }
+}
+
+async fn context_history(
+    State(manager): State<Arc<Mutex<ContextManager>>>,
+    Query(query): Query<HistoryQuery>,
+) -> Response {
+    let Ok(manager) = manager.lock() else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    match manager.history(query.port) {
+        Ok(revisions) => Json(revisions).into_response(),
+        Err(e) => error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "history_unreadable",
+            &e.to_string(),
+        ),
+    }
+}
+
+async fn rollback_context(
+    State(manager): State<Arc<Mutex<ContextManager>>>,
+    author: ContextAuthor,
+    Json(rollback): Json<Rollback>,
+) -> Response {
+    let Ok(mut manager) = manager.lock() else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    match manager.revision(rollback.rev) {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return error(
+                StatusCode::NOT_FOUND,
+                "unknown_revision",
+                &format!("Revision {} does not exist", rollback.rev),
+            )
+        }
+        Err(e) => return context_failed(e),
+    }
+    match manager.rollback(rollback.rev, author) {
+        Ok(Some(revision)) => Json::<ContextRevision>(revision).into_response(),
+        Ok(None) => error(
+            StatusCode::CONFLICT,
+            "unchanged",
+            &format!("The context is already as of revision {}", rollback.rev),
+        ),
+        Err(e) => context_failed(e),
+    }
+}
+
+/// Validation errors as a 422, anything else as a failed save
+fn context_failed(e: anyhow::Error) -> Response {
+    match e.downcast::<ContextErrors>() {
+        Ok(ContextErrors(errors)) => invalid_context(errors),
+        Err(e) => error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "save_failed",
+            &e.to_string(),
+        ),
+    }
+}
+
+fn ports(config: &NetworkConfig) -> Response {
+    Json(AdminPort::list(config)).into_response()
+}
+
+fn port(config: &NetworkConfig, port: u16) -> Response {
+    match config.binding(port) {
+        Some(binding) => Json(AdminPort::from(binding)).into_response(),
+        None => rejected(ConfigChangeError::UnknownPort(port)),
+    }
+}
+
+fn interface(config: &NetworkConfig) -> Response {
+    Json(AdminInterface::from(config)).into_response()
+}
+
+fn mode(config: &NetworkConfig) -> Response {
+    Json(AdminMode::from(config)).into_response()
+}
+
+async fn configure(commands: &CommandChannel, changes: Vec<ConfigChange>) -> Option<CommandResult> {
+    commands.send(AppCommand::Configure { changes }).await
+}
+
+fn respond(
+    result: Option<CommandResult>,
+    view: impl FnOnce(&NetworkConfig) -> Response,
+) -> Response {
+    match result
+        .as_ref()
+        .map(|r| r.as_ref().map(CommandOutcome::config))
+    {
+        Some(Ok(Some(config))) => view(config),
+        Some(Err(CommandError::Rejected(e))) => rejected(e.clone()),
+        Some(Err(e @ CommandError::SaveFailed(_))) => error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "save_failed",
+            &e.to_string(),
+        ),
+        Some(Err(e)) => error(StatusCode::UNPROCESSABLE_ENTITY, "failed", &e.to_string()),
+        Some(Ok(None)) | None => error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "admin_unavailable",
+            "ChaseAI did not apply the request; is the tray app or daemon still running?",
+        ),
+    }
+}
+
+fn rejected(e: ConfigChangeError) -> Response {
+    let (status, code) = match e {
+        ConfigChangeError::InvalidPort(..) => (StatusCode::UNPROCESSABLE_ENTITY, "invalid_port"),
+        ConfigChangeError::UnknownPort(_) => (StatusCode::NOT_FOUND, "unknown_port"),
+        ConfigChangeError::PortExists(_) => (StatusCode::CONFLICT, "port_exists"),
+        ConfigChangeError::UnknownInterface(_) => {
+            (StatusCode::UNPROCESSABLE_ENTITY, "unknown_interface")
+        }
+        ConfigChangeError::UnknownAgent(_) => (StatusCode::NOT_FOUND, "unknown_agent"),
+    };
+    error(status, code, &e.to_string())
+}
+
+/// 422 with every validation error, so a client can fix them all in one go
+fn invalid_context(errors: Vec<ContextError>) -> Response {
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        Json(serde_json::json!({
+            "error": "invalid_context",
+            "message": format!("Invalid context: {}", ContextErrors(errors.clone())),
+            "errors": errors,
+        })),
+    )
+        .into_response()
+}
+
+fn error(status: StatusCode, code: &str, message: &str) -> Response {
+    (
+        status,
+        Json(serde_json::json!({ "error": code, "message": message })),
+    )
+        .into_response()
+}
@@ -0,0 +1,232 @@
+use crate::instruction::history::ContextAuthor;
+use crate::instruction::manager::ContextManager;
+use crate::network::port_config::generate_token;
+use crate::server::tls::ClientIdentity;
+use anyhow::Context;
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::convert::Infallible;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Secret for the admin API, beside `network.toml`. Only this machine's user can read it;
+/// agents get binding tokens from `chase token`, never this one.
+pub const ADMIN_TOKEN_FILE_NAME: &str = "admin.token";
+
+/// Second secret beside the admin secret that only `chase` sends, so the context history
+/// can tell its changes from those of other admin API callers
+pub const CLI_TOKEN_FILE_NAME: &str = "cli.token";
+
+/// Header carrying the secret from [`CLI_TOKEN_FILE_NAME`]
+pub const CLI_TOKEN_HEADER: &str = "x-chaseai-cli-token";
+
+/// Token from `chase agents token <name>`, naming the agent it was given to
+pub const AGENT_TOKEN_HEADER: &str = "x-chaseai-agent-token";
+
+/// Token a server expects in `Authorization: Bearer <token>`; `None` disables the check
+pub type ExpectedToken = Option<Arc<str>>;
+
+/// Reject requests that don't present the binding's bearer token
+pub async fn require_bearer(
+    State(expected): State<ExpectedToken>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(expected) = expected else {
+        return next.run(request).await;
+    };
+    if presents(&request, &expected) {
+        return next.run(request).await;
+    }
+    unauthorized("Missing or invalid bearer token. Get it with `chase token --port <port>`.")
+}
+
+/// Reject requests that don't present the admin secret as their bearer token. Without a
+/// secret every request is rejected; a binding token is never enough.
+pub async fn require_admin(
+    State(expected): State<ExpectedToken>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match expected {
+        Some(expected) if presents(&request, &expected) => next.run(request).await,
+        _ => unauthorized(&format!(
+            "Missing or invalid admin token. The chase CLI reads it from {} beside network.toml.",
+            ADMIN_TOKEN_FILE_NAME
+        )),
+    }
+}
+
+fn presents(request: &Request, expected: &str) -> bool {
+    request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|token| constant_time_eq(token.trim().as_bytes(), expected.as_bytes()))
+}
+
+fn unauthorized(message: &str) -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        Json(serde_json::json!({
+            "error": "unauthorized",
+            "message": message
+        })),
+    )
+        .into_response()
+}
+
+/// The admin secret in `dir`, generated on first use and readable by this user only
+pub fn admin_token(dir: &Path) -> anyhow::Result<String> {
+    secret(dir, ADMIN_TOKEN_FILE_NAME)
+}
+
+/// The CLI's secret in `dir`, generated like the admin secret
+pub fn cli_token(dir: &Path) -> anyhow::Result<String> {
+    secret(dir, CLI_TOKEN_FILE_NAME)
+}
+
+fn secret(dir: &Path, file_name: &str) -> anyhow::Result<String> {
+    let path = dir.join(file_name);
+    match fs::read_to_string(&path) {
+        Ok(token) => return non_empty(token, &path),
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", path)),
+    }
+
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    let token = generate_token()?;
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    match options.open(&path) {
+        Ok(mut file) => {
+            writeln!(file, "{}", token)?;
+            file.sync_data()?;
+            Ok(token)
+        }
+        // The app and the CLI may both create it on first run; the first one wins
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+            non_empty(fs::read_to_string(&path)?, &path)
+        }
+        Err(e) => Err(e).with_context(|| format!("Failed to create {:?}", path)),
+    }
+}
+
+fn non_empty(token: String, path: &Path) -> anyhow::Result<String> {
+    let token = token.trim();
+    anyhow::ensure!(
+        !token.is_empty(),
+        "{:?} is empty; delete it to generate a new admin token",
+        path
+    );
+    Ok(token.to_string())
+}
+
+/// The agent making a request, for its own instruction context: the client certificate's
+/// common name on mTLS ports, else the agent its token was given to. Anyone else gets the port's
+/// context; a name the client merely claims would let it pick another agent's.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Agent {
+    pub name: Option<String>,
+    /// `name` is the common name of a verified client certificate
+    pub certified: bool,
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<Mutex<ContextManager>>> for Agent {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        manager: &Arc<Mutex<ContextManager>>,
+    ) -> Result<Self, Self::Rejection> {
+        if let Some(Some(ClientIdentity(name))) = parts.extensions.get::<Option<ClientIdentity>>() {
+            return Ok(Self {
+                name: Some(name.clone()),
+                certified: true,
+            });
+        }
+        let token = parts
+            .headers
+            .get(AGENT_TOKEN_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| !value.is_empty());
+        if let Some(token) = token {
+            let manager = manager
+                .lock()
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+            // A wrong token is refused rather than served the port's context
+            return match manager.agent_for_token(token) {
+                Some(agent) => Ok(Self {
+                    name: Some(agent.to_string()),
+                    certified: false,
+                }),
+                None => Err((
+                    StatusCode::UNAUTHORIZED,
+                    Json(serde_json::json!({
+                        "error": "unknown_agent_token",
+                        "message": "Unknown agent token. Get one with `chase agents token <name>`."
+                    })),
+                )
+                    .into_response()),
+            };
+        }
+        Ok(Self::default())
+    }
+}
+
+// Compare without short-circuiting so response timing doesn't reveal how much of a guess was right
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// The author of a context change over the admin API: `chase` when it presents the CLI's
+/// secret from the config directory, anyone else the API. A name the client merely claims
+/// is never used.
+#[async_trait]
+impl FromRequestParts<Arc<Mutex<ContextManager>>> for ContextAuthor {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        manager: &Arc<Mutex<ContextManager>>,
+    ) -> Result<Self, Self::Rejection> {
+        let Some(presented) = parts
+            .headers
+            .get(CLI_TOKEN_HEADER)
+            .and_then(|value| value.to_str().ok())
+        else {
+            return Ok(Self::Api);
+        };
+        let dir = manager.lock().unwrap_or_else(|e| e.into_inner()).data_dir();
+        match cli_token(&dir) {
+            Ok(expected) if constant_time_eq(presented.trim().as_bytes(), expected.as_bytes()) => {
+                Ok(Self::Cli)
+            }
+            Ok(_) => Ok(Self::Api),
+            Err(e) => {
+                eprintln!("Can't check the CLI token: {}", e);
+                Ok(Self::Api)
+            }
+        }
+    }
+}
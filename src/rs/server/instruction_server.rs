@@ -1,17 +1,36 @@
+use crate::command::CommandChannel;
 use crate::config::generator::ConfigurationGenerator;
 use crate::config::network_config::NetworkConfig;
 use crate::instruction::context::InstructionContext;
 use crate::instruction::manager::ContextManager;
+use crate::instruction::session::{Session, SessionScope};
+use crate::instruction::storage::ContextKey;
 use crate::network::interface_detector::NetworkInterface;
+use crate::server::admin;
+use crate::server::auth::{admin_token, require_admin, require_bearer, Agent, ExpectedToken};
+use crate::server::tls::{IdentityAcceptor, ServerTls};
+use crate::verification::audit::{AuditFilter, AuditLog, AuditRecord, ChainAnchor};
+use crate::verification::pending::{
+    parse_wait, PendingVerification, PendingVerifications, BLOCKING_WAIT, MAX_WAIT,
+};
+use crate::verification::policy::{PolicyDecision, PolicyEffect, PolicyInput, PolicyStore};
+use crate::verification::prompter::{Prompt, PromptAnswer, Prompter, PrompterConfig, Unavailable};
+use crate::verification::request::{
+    new_verification_id, ActionTypeRejection, ButtonDecision, ConsoleAnswer, VerificationButton,
+    STATUS_FORBIDDEN, STATUS_NO_DECISION, STATUS_UNAVAILABLE,
+};
+pub use crate::verification::request::{VerificationRequest, VerificationResponse};
 use axum::{
-    extract::{Extension, Query, State},
+    extract::{ConnectInfo, Extension, Path, Query, State},
     http::StatusCode,
+    response::{IntoResponse, Response},
     routing::get,
     Json, Router,
 };
 use serde::Deserialize;
 use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::sync::broadcast;
 
 #[derive(Debug, Deserialize)]
@@ -20,29 +39,50 @@ pub struct ConfigFormat {
     format: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct WaitParams {
+    wait: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PolicyQuery {
+    action: String,
+    task_id: Option<String>,
+    /// Defaults to the port serving the request
+    port: Option<u16>,
+    /// Local time `HH:MM`, defaults to now
+    time: Option<String>,
+}
+
 pub struct InstructionServer {
     port: u16,
     interface: NetworkInterface,
     context_manager: Arc<Mutex<ContextManager>>,
     network_config: Arc<Mutex<NetworkConfig>>,
+    pending: Arc<PendingVerifications>,
+    audit: Arc<AuditLog>,
+    policy: Arc<PolicyStore>,
+    token: ExpectedToken,
+    admin_token: ExpectedToken,
+    tls: Option<ServerTls>,
+    prompter: Arc<dyn Prompter>,
+    admin: Option<CommandChannel>,
     shutdown_tx: broadcast::Sender<()>,
+    task: Mutex<Option<tokio::task::JoinHandle<()>>>,
 }
 
+/// How long `stop` lets in-flight requests finish before the server is torn down.
+/// A blocking `/verify` can wait on a human indefinitely.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+
 impl InstructionServer {
     pub fn new(
         port: u16,
         interface: NetworkInterface,
         context_manager: Arc<Mutex<ContextManager>>,
     ) -> Self {
-        let (shutdown_tx, _) = broadcast::channel(1);
         let network_config = Arc::new(Mutex::new(NetworkConfig::new()));
-        Self {
-            port,
-            interface,
-            context_manager,
-            network_config,
-            shutdown_tx,
-        }
+        Self::with_config(port, interface, context_manager, network_config)
     }
 
     pub fn with_config(
@@ -52,15 +92,75 @@ impl InstructionServer {
         network_config: Arc<Mutex<NetworkConfig>>,
     ) -> Self {
         let (shutdown_tx, _) = broadcast::channel(1);
+        let audit = Arc::new(default_audit_log(&context_manager));
+        let policy = Arc::new(default_policy_store(&context_manager));
         Self {
             port,
             interface,
             context_manager,
             network_config,
+            pending: Arc::new(PendingVerifications::new()),
+            audit,
+            policy,
+            token: None,
+            admin_token: None,
+            tls: None,
+            prompter: PrompterConfig::default().build(),
+            admin: None,
             shutdown_tx,
+            task: Mutex::new(None),
         }
     }
 
+    /// Share a verification registry with other servers (used by `ServerPool`)
+    pub fn with_pending(mut self, pending: Arc<PendingVerifications>) -> Self {
+        self.pending = pending;
+        self
+    }
+
+    /// Share an audit log with other servers (used by `ServerPool`)
+    pub fn with_audit(mut self, audit: Arc<AuditLog>) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    /// Require `Authorization: Bearer <token>` on every route except `/health`
+    pub fn with_token(mut self, token: Option<&str>) -> Self {
+        self.token = token.map(Arc::from);
+        self
+    }
+
+    /// Require this admin secret, and not the binding's token, on the admin, console, session
+    /// and audit routes. Without one they reject every request.
+    pub fn with_admin_token(mut self, token: Option<&str>) -> Self {
+        self.admin_token = token.map(Arc::from);
+        self
+    }
+
+    /// Serve HTTPS with this certificate instead of plain HTTP
+    pub fn with_tls(mut self, tls: Option<ServerTls>) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Ask the human through this prompter instead of the platform default
+    pub fn with_prompter(mut self, prompter: Arc<dyn Prompter>) -> Self {
+        self.prompter = prompter;
+        self
+    }
+
+    /// Share a policy with other servers (used by `ServerPool`)
+    pub fn with_policy(mut self, policy: Arc<PolicyStore>) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Serve `/admin/*`, submitting its commands here; only on loopback interfaces
+    pub fn with_admin(mut self, admin: Option<CommandChannel>) -> Self {
+        self.admin = admin;
+        self
+    }
+
     pub async fn start(&self) -> anyhow::Result<()> {
         let app = self.router();
         let addr = SocketAddr::new(self.interface.ip_address, self.port);
@@ -68,78 +168,567 @@ impl InstructionServer {
         // Bind first to ensure port is available and fail fast if not
         let listener = tokio::net::TcpListener::bind(addr).await?;
         let mut shutdown_rx = self.shutdown_tx.subscribe();
+        let service = app.into_make_service_with_connect_info::<SocketAddr>();
 
-        println!("Starting InstructionServer on {}", addr);
-
-        tokio::spawn(async move {
-            if let Err(e) = axum::serve(listener, app)
-                .with_graceful_shutdown(async move {
+        let task = match self.tls.clone() {
+            Some(tls) => {
+                println!("Starting InstructionServer on https://{}", addr);
+                let listener = listener.into_std()?;
+                let handle = axum_server::Handle::new();
+                let shutdown = handle.clone();
+                tokio::spawn(async move {
                     let _ = shutdown_rx.recv().await;
+                    shutdown.graceful_shutdown(Some(SHUTDOWN_GRACE));
+                });
+                tokio::spawn(async move {
+                    if let Err(e) = axum_server::from_tcp(listener)
+                        .acceptor(IdentityAcceptor::new(tls))
+                        .handle(handle)
+                        .serve(service)
+                        .await
+                    {
+                        eprintln!("Server error: {}", e);
+                    }
                 })
-                .await
-            {
-                eprintln!("Server error: {}", e);
             }
-        });
+            None => {
+                println!("Starting InstructionServer on {}", addr);
+                tokio::spawn(async move {
+                    if let Err(e) = axum::serve(listener, service)
+                        .with_graceful_shutdown(async move {
+                            let _ = shutdown_rx.recv().await;
+                        })
+                        .await
+                    {
+                        eprintln!("Server error: {}", e);
+                    }
+                })
+            }
+        };
+        *self.task.lock().unwrap_or_else(|e| e.into_inner()) = Some(task);
 
         Ok(())
     }
 
+    /// Stop serving and wait until the port is released, so it can be bound again right away
     pub async fn stop(&self) -> anyhow::Result<()> {
         let _ = self.shutdown_tx.send(());
+        let task = self.task.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(mut task) = task {
+            if tokio::time::timeout(SHUTDOWN_GRACE, &mut task)
+                .await
+                .is_err()
+            {
+                task.abort();
+                let _ = task.await;
+            }
+        }
         Ok(())
     }
 
     fn router(&self) -> Router {
-        Router::new()
+        let mut router = Router::new()
             .route("/context", get(get_context))
             .route("/config", get(get_config))
-            .route("/health", get(health_check))
             .route("/verify", axum::routing::post(verify_action))
+            .route("/verify/:id", get(get_verification))
+            .route("/policy/evaluate", get(evaluate_policy))
+            // Applies to the routes above only, so health checks work without a token
+            .route_layer(axum::middleware::from_fn_with_state(
+                self.token.clone(),
+                require_bearer,
+            ));
+        router = self.with_operator_routes(router);
+        router
+            .route("/health", get(health_check))
             .layer(Extension(self.port))
+            .layer(Extension(self.pending.clone()))
+            .layer(Extension(self.audit.clone()))
+            .layer(Extension(self.policy.clone()))
+            .layer(Extension(Deciders {
+                policy: self.policy.clone(),
+                prompter: self.prompter.clone(),
+            }))
             .layer(Extension(self.network_config.clone()))
             .with_state(self.context_manager.clone())
     }
+
+    /// Routes for this machine's user rather than its agents: `chase console`, sessions, the audit
+    /// log and, with a command channel, the admin API. Loopback bindings only, and the admin secret instead
+    /// of the binding token, which every agent on the port holds.
+    fn with_operator_routes(
+        &self,
+        router: Router<Arc<Mutex<ContextManager>>>,
+    ) -> Router<Arc<Mutex<ContextManager>>> {
+        if !self.interface.ip_address.is_loopback() {
+            return router;
+        }
+        let mut operator = Router::new()
+            .route("/verify/pending", get(list_console_prompts))
+            .route(
+                "/verify/:id/answer",
+                axum::routing::post(answer_console_prompt),
+            )
+            // Session ids are what agents present to skip the human, and the audit log records them
+            .route("/sessions", get(list_sessions).delete(revoke_all_sessions))
+            .route("/sessions/:id", axum::routing::delete(revoke_session))
+            .route("/audit", get(get_audit))
+            .route("/audit/anchor", get(get_audit_anchor));
+        if let Some(admin) = self.admin.clone() {
+            operator = operator.merge(
+                admin::router()
+                    .merge(admin::context_router())
+                    .layer(Extension(admin)),
+            );
+        }
+        router.merge(operator.route_layer(axum::middleware::from_fn_with_state(
+            self.admin_token.clone(),
+            require_admin,
+        )))
+    }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct VerificationRequest {
-    pub action: String,
-    pub reason: String,
-    pub context: Option<serde_json::Value>,
-    pub buttons: Option<Vec<String>>,
-    pub session_id: Option<String>,
+/// Audit log stored next to the context manager's `contexts.json`
+pub fn default_audit_log(context_manager: &Mutex<ContextManager>) -> AuditLog {
+    AuditLog::in_dir(&data_dir(context_manager))
+}
+
+/// The admin secret beside `contexts.json`, created on first use; `None` if it can't be,
+/// which leaves the admin routes closed
+pub fn default_admin_token(context_manager: &Mutex<ContextManager>) -> Option<String> {
+    admin_token(&data_dir(context_manager))
+        .map_err(|e| eprintln!("Admin API disabled: {}", e))
+        .ok()
 }
 
-#[derive(Debug, serde::Serialize)]
-pub struct VerificationResponse {
-    pub status: String,
-    pub verification_id: String,
-    pub message: Option<String>,
+/// `policy.toml` in the config directory, beside `network.toml` and `contexts.json`
+pub fn default_policy_store(context_manager: &Mutex<ContextManager>) -> PolicyStore {
+    PolicyStore::in_dir(&data_dir(context_manager))
+}
+
+fn data_dir(context_manager: &Mutex<ContextManager>) -> std::path::PathBuf {
+    context_manager
+        .lock()
+        .map(|m| m.data_dir())
+        .unwrap_or_else(|e| e.into_inner().data_dir())
+}
+
+/// Policy and human prompter that decide `/verify` requests
+#[derive(Clone)]
+struct Deciders {
+    policy: Arc<PolicyStore>,
+    prompter: Arc<dyn Prompter>,
+}
+
+/// Outcome of asking the human (or applying a session) for one request
+struct Decision {
+    response: VerificationResponse,
+    chosen_button: Option<String>,
+}
+
+/// Request metadata captured on arrival, completed into an audit record once decided
+struct AuditEntry {
+    requested_at: chrono::DateTime<chrono::Utc>,
+    client_addr: Option<String>,
+    port: u16,
+}
+
+impl AuditEntry {
+    fn record(self, request: VerificationRequest, decision: Decision) -> AuditRecord {
+        let decided_at = chrono::Utc::now();
+        let response = decision.response;
+        AuditRecord {
+            verification_id: response.verification_id,
+            requested_at: self.requested_at,
+            decided_at,
+            duration_ms: (decided_at - self.requested_at).num_milliseconds(),
+            client_addr: self.client_addr,
+            port: self.port,
+            request,
+            chosen_button: decision.chosen_button,
+            status: response.status,
+            message: response.message,
+            session_id: response.session_id,
+            policy_rule: response.policy_rule,
+            decision: response.decision,
+            prev_hash: String::new(),
+            hash: String::new(),
+        }
+    }
 }
 
 async fn verify_action(
     State(manager): State<Arc<Mutex<ContextManager>>>,
-    Json(payload): Json<VerificationRequest>,
-) -> Json<VerificationResponse> {
-    println!("🚨 Verification requested for action: {}", payload.action);
-
-    // 1. Check if we already have an active authorized session for this agent - not ready yet
-    // if let Some(sid) = &payload.session_id {
-    //     let mgr = manager.lock().unwrap();
-    //     if let Some((expires, _allowed)) = mgr.sessions.get(sid) {
-    //         if *expires > chrono::Utc::now() {
-    //             // Check if the current action is within the scope of allowed actions in this session
-    //             // For simplicity, if the session exists, we assume it covers the agent's work
-    //             println!("✅ Action automatically approved via session: {}", sid);
-    //             return Json(VerificationResponse {
-    //                 status: "approved".to_string(),
-    //                 verification_id: sid.clone(),
-    //                 message: Some("Automatically approved via active session".to_string()),
-    //             });
-    //         }
-    //     }
-    // }
+    Extension(pending): Extension<Arc<PendingVerifications>>,
+    Extension(audit): Extension<Arc<AuditLog>>,
+    Extension(deciders): Extension<Deciders>,
+    Extension(port): Extension<u16>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    agent: Agent,
+    Json(mut payload): Json<VerificationRequest>,
+) -> Response {
+    // Only a verified client certificate names the requester, never the request body
+    payload.requester = agent.name.clone().filter(|_| agent.certified);
+    match &payload.requester {
+        Some(agent) => println!(
+            "🚨 Verification requested by {} for action: {}",
+            agent, payload.action
+        ),
+        None => println!("🚨 Verification requested for action: {}", payload.action),
+    }
+
+    let requested_at = chrono::Utc::now();
+    let client_addr = connect_info.map(|ConnectInfo(addr)| addr.to_string());
+    let verification_id = match new_verification_id() {
+        Ok(id) => id,
+        Err(e) => {
+            eprintln!("⚠️ {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let non_blocking = payload.non_blocking;
+    let owner = ContextKey {
+        port,
+        agent: agent.name,
+    };
+
+    // The agent's instruction context is the contract: action types outside it never reach a human
+    let context = manager
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .resolve_context(owner.clone());
+    let action_type = payload.action_type.as_deref();
+    let rejection = match &context {
+        Ok(Some(context)) => context
+            .check_action_type(action_type)
+            .err()
+            .map(|violation| {
+                ActionTypeRejection::new(&verification_id, violation, action_type, context)
+            }),
+        Ok(None) => None,
+        Err(e) => {
+            eprintln!("⚠️ {}", e);
+            Some(ActionTypeRejection::unresolved(
+                &verification_id,
+                action_type,
+            ))
+        }
+    };
+    if let Some(rejection) = rejection {
+        println!("⛔ {}", rejection.message);
+        let decision = Decision {
+            response: VerificationResponse {
+                status: STATUS_FORBIDDEN.to_string(),
+                verification_id: verification_id.clone(),
+                message: Some(rejection.message.clone()),
+                session_id: None,
+                policy_rule: None,
+                decision: Some(ButtonDecision::Reject),
+                button: None,
+            },
+            chosen_button: None,
+        };
+        let record = AuditEntry {
+            requested_at,
+            client_addr,
+            port,
+        }
+        .record(payload, decision);
+        let log = audit.clone();
+        if let Ok(Err(e)) = tokio::task::spawn_blocking(move || log.append(&record)).await {
+            eprintln!(
+                "Failed to write audit record for {}: {}",
+                verification_id, e
+            );
+        }
+        return (StatusCode::FORBIDDEN, Json(rejection)).into_response();
+    }
+    let context = context.ok().flatten();
+
+    pending.register(&verification_id, &payload, owner.clone());
+
+    // The dialog blocks until the human answers, so it must not run on the async workers.
+    // It runs detached so the decision is recorded even if the agent disconnects.
+    let id = verification_id.clone();
+    let registry = pending.clone();
+    tokio::task::spawn_blocking(move || {
+        let policy = deciders.policy.evaluate(&PolicyInput {
+            action: &payload.action,
+            task_id: payload.task_id(),
+            port,
+            time: chrono::Local::now().time(),
+        });
+        let decision = decide_verification(
+            &manager,
+            deciders.prompter.as_ref(),
+            &policy,
+            context.as_ref(),
+            &payload,
+            &owner,
+            &id,
+        );
+        let response = decision.response.clone();
+
+        // Record before releasing the decision so nothing is approved without a trace
+        let record = AuditEntry {
+            requested_at,
+            client_addr,
+            port,
+        }
+        .record(payload, decision);
+        if let Err(e) = audit.append(&record) {
+            eprintln!("Failed to write audit record for {}: {}", id, e);
+        }
+
+        registry.resolve(&id, response);
+    });
+
+    if non_blocking {
+        println!("⏳ Verification {} pending", verification_id);
+        return (
+            StatusCode::ACCEPTED,
+            Json(VerificationResponse::pending(&verification_id)),
+        )
+            .into_response();
+    }
+
+    match pending.wait(&verification_id, BLOCKING_WAIT).await {
+        Some(response) if response.is_pending() => {
+            (StatusCode::ACCEPTED, Json(response)).into_response()
+        }
+        Some(response) => (decision_status_code(&response), Json(response)).into_response(),
+        None => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(VerificationResponse {
+                status: "error".to_string(),
+                verification_id,
+                message: Some("Verification record was lost".to_string()),
+                session_id: None,
+                policy_rule: None,
+                decision: None,
+                button: None,
+            }),
+        )
+            .into_response(),
+    }
+}
+
+async fn get_verification(
+    Extension(pending): Extension<Arc<PendingVerifications>>,
+    Extension(port): Extension<u16>,
+    agent: Agent,
+    Path(verification_id): Path<String>,
+    Query(params): Query<WaitParams>,
+) -> Result<(StatusCode, Json<VerificationResponse>), StatusCode> {
+    let owner = ContextKey {
+        port,
+        agent: agent.name,
+    };
+    if !pending.is_owned_by(&verification_id, &owner) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let wait = match params.wait.as_deref() {
+        Some(value) => parse_wait(value).ok_or(StatusCode::BAD_REQUEST)?,
+        None => Duration::ZERO,
+    };
+
+    pending
+        .wait(&verification_id, wait)
+        .await
+        .map(|response| (decision_status_code(&response), Json(response)))
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Verifications waiting for an answer from `chase console`, oldest first
+async fn list_console_prompts(
+    Extension(pending): Extension<Arc<PendingVerifications>>,
+    Extension(deciders): Extension<Deciders>,
+) -> Response {
+    let Some(console) = deciders.prompter.console() else {
+        return console_required();
+    };
+    console.attach();
+    let waiting: Vec<PendingVerification> = pending
+        .list_pending()
+        .into_iter()
+        .filter(|p| console.is_waiting(&p.verification_id))
+        .collect();
+    Json(waiting).into_response()
+}
+
+/// Answer a console prompt and wait for the resulting decision
+async fn answer_console_prompt(
+    Extension(pending): Extension<Arc<PendingVerifications>>,
+    Extension(deciders): Extension<Deciders>,
+    Path(verification_id): Path<String>,
+    Json(answer): Json<ConsoleAnswer>,
+) -> Response {
+    let Some(console) = deciders.prompter.console() else {
+        return console_required();
+    };
+    let Some(waiting) = pending
+        .list_pending()
+        .into_iter()
+        .find(|p| p.verification_id == verification_id)
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let buttons = waiting.request.buttons();
+    if answer.button >= buttons.len() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(serde_json::json!({
+                "error": "unknown_button",
+                "message": format!(
+                    "Button {} is not offered; the request has {} button(s)",
+                    answer.button,
+                    buttons.len()
+                )
+            })),
+        )
+            .into_response();
+    }
+
+    println!(
+        "🖥 Console answered {} with {}",
+        verification_id,
+        buttons[answer.button].label()
+    );
+    let delivered = console.answer(
+        &verification_id,
+        PromptAnswer {
+            button: Some(answer.button),
+            message: answer.message,
+        },
+    );
+    if !delivered {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    match pending.wait(&verification_id, MAX_WAIT).await {
+        Some(response) => (decision_status_code(&response), Json(response)).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+fn console_required() -> Response {
+    (
+        StatusCode::CONFLICT,
+        Json(serde_json::json!({
+            "error": "console_prompter_required",
+            "message": "This instance does not ask through the console; set kind = \"console\" under [prompter] in network.toml"
+        })),
+    )
+        .into_response()
+}
+
+/// `503` when nobody could be asked, so agents can't mistake it for a human's answer
+fn decision_status_code(response: &VerificationResponse) -> StatusCode {
+    if response.is_unavailable() {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    }
+}
+
+/// Apply the policy, the port context, then a session, then ask the human and translate the clicked
+/// button into a verification status
+fn decide_verification(
+    manager: &Mutex<ContextManager>,
+    prompter: &dyn Prompter,
+    policy: &PolicyDecision,
+    context: Option<&InstructionContext>,
+    payload: &VerificationRequest,
+    owner: &ContextKey,
+    verification_id: &str,
+) -> Decision {
+    // 1. Policy rules decide first; a matching `ask` rule also bypasses sessions
+    let policy_rule = policy.rule.clone();
+    let rule_name = policy_rule.as_deref().unwrap_or_default();
+    let policy_decision = match policy.effect {
+        PolicyEffect::Allow => Some(ButtonDecision::Approve),
+        PolicyEffect::Deny => Some(ButtonDecision::Reject),
+        PolicyEffect::Ask => None,
+    };
+    if let Some(decision) = policy_decision {
+        let status = decision.status();
+        println!("📜 Action {} by policy rule {}", status, rule_name);
+        let message = policy
+            .message
+            .clone()
+            .unwrap_or_else(|| format!("Decided by policy rule {}", rule_name));
+        return Decision {
+            response: VerificationResponse {
+                status: status.to_string(),
+                verification_id: verification_id.to_string(),
+                message: Some(message),
+                session_id: None,
+                policy_rule,
+                decision: Some(decision),
+                button: None,
+            },
+            chosen_button: None,
+        };
+    }
+
+    // 2. The port's context waives verification entirely, unless an `ask` rule insists on a human
+    if let (Some(context), None) = (context, &policy_rule) {
+        if !context.verification_required {
+            println!("✅ Action approved: verification not required by the port context");
+            return Decision {
+                response: VerificationResponse {
+                    status: ButtonDecision::Approve.status().to_string(),
+                    verification_id: verification_id.to_string(),
+                    message: Some(format!(
+                        "Verification is not required for {} actions on this port",
+                        payload.action_type.as_deref().unwrap_or_default()
+                    )),
+                    session_id: None,
+                    policy_rule: None,
+                    decision: Some(ButtonDecision::Approve),
+                    button: None,
+                },
+                chosen_button: None,
+            };
+        }
+    }
+
+    // 3. Auto-approve if the agent presents a session whose scope covers this action,
+    //    unless an `ask` rule insists on a human
+    let session_id = payload
+        .session_id
+        .as_ref()
+        .filter(|_| policy_rule.is_none());
+    if let Some(sid) = session_id {
+        let mut mgr = manager.lock().unwrap_or_else(|e| e.into_inner());
+        match mgr.use_session(sid, owner, &payload.action, payload.task_id()) {
+            Ok(session) => {
+                println!("✅ Action automatically approved via session: {}", sid);
+                let response = VerificationResponse {
+                    status: ButtonDecision::Approve.status().to_string(),
+                    verification_id: verification_id.to_string(),
+                    message: Some(format!(
+                        "Automatically approved via session {} (use {} of {})",
+                        sid,
+                        session.uses,
+                        session
+                            .scope
+                            .max_uses
+                            .map(|m| m.to_string())
+                            .unwrap_or_else(|| "unlimited".to_string())
+                    )),
+                    session_id: Some(sid.clone()),
+                    policy_rule: None,
+                    decision: Some(ButtonDecision::Approve),
+                    button: None,
+                };
+                return Decision {
+                    response,
+                    chosen_button: None,
+                };
+            }
+            Err(e) => println!("⚠️ Session not applied, asking the user: {}", e),
+        }
+    }
 
     let context_str = payload
         .context
@@ -147,76 +736,264 @@ async fn verify_action(
         .map(|c| c.to_string())
         .unwrap_or_else(|| "{}".to_string());
 
-    let buttons = payload.buttons.unwrap_or_else(|| {
-        vec![
-            "Reject".to_string(),
-            "Approve Once".to_string(),
-            "Approve Session".to_string(),
-        ]
-    });
+    let buttons = payload.buttons();
 
-    let task_id = payload
-        .context
-        .as_ref()
-        .and_then(|c| c.get("task_id"))
-        .and_then(|v| v.as_str())
-        .unwrap_or("CHASE-TASK");
-
-    // Show the UI dialog
-    let (approved_idx, message) = crate::ui::dialogs::show_verification_dialog(
-        &payload.action,
-        &payload.reason,
-        &context_str,
-        &buttons,
-        task_id,
-    );
+    let task_id = payload.task_id().unwrap_or("CHASE-TASK");
+    let title = match &payload.requester {
+        Some(agent) => format!("{} — {}", agent, task_id),
+        None => task_id.to_string(),
+    };
 
-    let mut status = if approved_idx < buttons.len() {
-        buttons[approved_idx].to_lowercase()
-    } else {
-        "cancelled".to_string()
+    // Show the scope the human is approving when a session can be granted
+    let session_buttons: Vec<_> = buttons
+        .iter()
+        .filter(|b| b.decision() == Some(ButtonDecision::ApproveSession))
+        .collect();
+    let mut reason = payload.reason.clone();
+    match session_buttons.as_slice() {
+        [] => {}
+        [button] => reason.push_str(&format!(
+            "\n\nSession scope: {}",
+            payload.session_scope_for(button).describe()
+        )),
+        several => {
+            for button in several {
+                reason.push_str(&format!(
+                    "\n\nSession scope for \"{}\": {}",
+                    button.label(),
+                    payload.session_scope_for(button).describe()
+                ));
+            }
+        }
+    }
+    if let Some(rule) = &policy_rule {
+        reason.push_str(&format!(
+            "\n\nPolicy rule {} requires your confirmation.",
+            rule
+        ));
+        if let Some(message) = &policy.message {
+            reason.push_str(&format!(" {}", message));
+        }
+    }
+
+    let prompt = Prompt {
+        verification_id: verification_id.to_string(),
+        title,
+        action: payload.action.clone(),
+        reason,
+        context: context_str,
+        buttons: buttons.iter().map(|b| b.label().to_string()).collect(),
+    };
+    let answer = match prompter.prompt(&prompt) {
+        Ok(answer) => answer,
+        Err(unavailable) => {
+            // Fail closed: without a human nothing is approved, whatever the buttons are
+            println!("⛔ No prompter available: {}", unavailable);
+            return Decision {
+                response: VerificationResponse {
+                    status: STATUS_UNAVAILABLE.to_string(),
+                    verification_id: verification_id.to_string(),
+                    message: Some(format!("Could not ask the user: {}", unavailable)),
+                    session_id: None,
+                    policy_rule,
+                    decision: None,
+                    button: None,
+                },
+                chosen_button: None,
+            };
+        }
     };
+    let message = answer.message;
+    let chosen = answer.button.and_then(|i| buttons.get(i));
 
-    let verification_id = format!("v-{}", chrono::Utc::now().timestamp());
+    // 4. Map the button explicitly; unmapped bare labels decide nothing, whatever they say
+    let decision = chosen.and_then(VerificationButton::decision);
+    let status = match (chosen, decision) {
+        (None, _) => "cancelled",
+        (Some(_), None) => STATUS_NO_DECISION,
+        (Some(_), Some(decision)) => decision.status(),
+    }
+    .to_string();
+    let session_id = match (chosen, decision) {
+        (Some(button), Some(ButtonDecision::ApproveSession)) => start_session(
+            manager,
+            verification_id,
+            owner,
+            payload.session_scope_for(button),
+        ),
+        _ => None,
+    };
 
-    // 2. If user chose "Approve Session", register it in the manager
-    if status.contains("session") {
-        let mut mgr = manager.lock().unwrap();
-        // Session valid for 1 hour
-        let expires = chrono::Utc::now() + chrono::Duration::hours(1);
-        mgr.sessions
-            .insert(verification_id.clone(), (expires, vec![]));
-        println!("🎟 Session created: {}", verification_id);
-        status = "approved_session".to_string();
-    } else if status.contains("approve") {
-        status = "approved".to_string();
+    Decision {
+        response: VerificationResponse {
+            status,
+            verification_id: verification_id.to_string(),
+            message,
+            session_id,
+            policy_rule,
+            decision,
+            button: chosen.map(|b| b.label().to_string()),
+        },
+        chosen_button: chosen.map(|b| b.label().to_string()),
     }
+}
 
-    Json(VerificationResponse {
-        status,
-        verification_id,
-        message,
+/// Register a session for the scope the human just approved; its id is returned to the agent.
+/// Without one the action is still approved, just not the ones after it.
+fn start_session(
+    manager: &Mutex<ContextManager>,
+    verification_id: &str,
+    owner: &ContextKey,
+    scope: SessionScope,
+) -> Option<String> {
+    let mut mgr = manager.lock().unwrap_or_else(|e| e.into_inner());
+    match mgr.create_session(verification_id, owner.clone(), scope) {
+        Ok(session) => {
+            println!(
+                "🎟 Session created: {} for {} ({}, expires {})",
+                session.id,
+                session.owner,
+                session.scope.describe(),
+                session.expires_at
+            );
+            Some(session.id.clone())
+        }
+        Err(e) => {
+            eprintln!("Session for {} not created: {}", verification_id, e);
+            None
+        }
+    }
+}
+
+async fn get_audit(
+    Extension(audit): Extension<Arc<AuditLog>>,
+    Query(filter): Query<AuditFilter>,
+) -> Result<Json<Vec<AuditRecord>>, StatusCode> {
+    let log = audit.clone();
+    tokio::task::spawn_blocking(move || log.query(&filter))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map(Json)
+        .map_err(|e| {
+            eprintln!("Failed to read audit log: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+/// Length and head of the audit chain as this process wrote it, for `chase history verify`
+async fn get_audit_anchor(
+    Extension(audit): Extension<Arc<AuditLog>>,
+) -> Result<Json<ChainAnchor>, StatusCode> {
+    let log = audit.clone();
+    tokio::task::spawn_blocking(move || log.anchor())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map(Json)
+        .map_err(|e| {
+            eprintln!("Failed to read audit log: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+/// Dry-run the policy for a hypothetical request; nothing is shown or recorded
+async fn evaluate_policy(
+    Extension(policy): Extension<Arc<PolicyStore>>,
+    Extension(port): Extension<u16>,
+    Query(query): Query<PolicyQuery>,
+) -> Result<Json<PolicyDecision>, StatusCode> {
+    let time = match query.time.as_deref() {
+        Some(value) => chrono::NaiveTime::parse_from_str(value, "%H:%M")
+            .map_err(|_| StatusCode::BAD_REQUEST)?,
+        None => chrono::Local::now().time(),
+    };
+    let store = policy.clone();
+    tokio::task::spawn_blocking(move || {
+        store.evaluate(&PolicyInput {
+            action: &query.action,
+            task_id: query.task_id.as_deref(),
+            port: query.port.unwrap_or(port),
+            time,
+        })
     })
+    .await
+    .map(Json)
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn list_sessions(
+    State(manager): State<Arc<Mutex<ContextManager>>>,
+) -> Result<Json<Vec<Session>>, StatusCode> {
+    let manager = manager
+        .lock()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(manager.list_sessions().into_iter().cloned().collect()))
+}
+
+async fn revoke_session(
+    State(manager): State<Arc<Mutex<ContextManager>>>,
+    Path(session_id): Path<String>,
+) -> StatusCode {
+    let Ok(mut manager) = manager.lock() else {
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    };
+    if manager.revoke_session(&session_id) {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+async fn revoke_all_sessions(
+    State(manager): State<Arc<Mutex<ContextManager>>>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let mut manager = manager
+        .lock()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let revoked = manager.revoke_all_sessions();
+    Ok(Json(serde_json::json!({ "revoked": revoked })))
 }
 
 async fn get_context(
     State(manager): State<Arc<Mutex<ContextManager>>>,
     Extension(port): Extension<u16>,
+    agent: Agent,
 ) -> Result<Json<InstructionContext>, StatusCode> {
     // In a real high-throughput scenario, we'd want RwLock, but Mutex is fine for MVP
     let manager = manager
         .lock()
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    if let Some(context) = manager.get_context(port) {
-        Ok(Json(context.clone()))
-    } else {
-        Err(StatusCode::NOT_FOUND)
+    // A context that fails to resolve is the server's fault, not a missing one
+    match manager.resolve_context(ContextKey {
+        port,
+        agent: agent.name,
+    }) {
+        Ok(Some(context)) => Ok(Json(context)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            eprintln!("⚠️ {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
     }
 }
 
-async fn health_check() -> StatusCode {
-    StatusCode::OK
+/// Healthy only while a human can actually be asked
+async fn health_check(Extension(deciders): Extension<Deciders>) -> Response {
+    let prompter = deciders.prompter.clone();
+    let check = tokio::task::spawn_blocking(move || prompter.check())
+        .await
+        .unwrap_or_else(|e| Err(Unavailable(e.to_string())));
+    match check {
+        Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "status": "ok" }))).into_response(),
+        Err(unavailable) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({
+                "status": STATUS_UNAVAILABLE,
+                "message": format!("Verifications cannot reach the user: {}", unavailable)
+            })),
+        )
+            .into_response(),
+    }
 }
 
 async fn get_config(
@@ -1,2 +1,5 @@
+pub mod admin;
+pub mod auth;
 pub mod instruction_server;
 pub mod pool;
+pub mod tls;
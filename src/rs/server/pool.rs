@@ -1,53 +1,160 @@
+use crate::command::CommandChannel;
 use crate::config::network_config::NetworkConfig;
 use crate::instruction::manager::ContextManager;
-use crate::server::instruction_server::InstructionServer;
+use crate::instruction::variables::ContextVariables;
+use crate::network::port_config::PortBinding;
+use crate::server::instruction_server::{
+    default_admin_token, default_audit_log, InstructionServer,
+};
+use crate::server::tls::TlsStore;
+use crate::verification::audit::AuditLog;
+use crate::verification::pending::PendingVerifications;
+use crate::verification::prompter::{Prompter, PrompterConfig};
 use anyhow::Result;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::{Arc, Mutex};
 
+/// A server together with the binding it was started from, so changes can be detected
+struct RunningServer {
+    binding: PortBinding,
+    server: InstructionServer,
+}
+
 pub struct ServerPool {
-    servers: HashMap<u16, InstructionServer>,
+    servers: HashMap<u16, RunningServer>,
     context_manager: Arc<Mutex<ContextManager>>,
+    pending: Arc<PendingVerifications>,
+    audit: Arc<AuditLog>,
+    // Enabled bindings that were not started, with the reason shown in the tray
+    refused: BTreeMap<u16, String>,
+    // Prompter the running servers were started with, and the config it was built from
+    prompter: Arc<dyn Prompter>,
+    prompter_config: PrompterConfig,
+    admin: Option<CommandChannel>,
+    /// Secret for the admin API and the console, from the config directory
+    admin_token: Option<String>,
 }
 
 impl ServerPool {
     pub fn new(context_manager: Arc<Mutex<ContextManager>>) -> Self {
+        let audit = Arc::new(default_audit_log(&context_manager));
+        // Take the chain's end now, so rewriting the log before the first verification
+        // shows up in `chase history verify` too
+        if let Err(e) = audit.anchor() {
+            eprintln!("Audit log unreadable: {}", e);
+        }
+        let admin_token = default_admin_token(&context_manager);
         Self {
             servers: HashMap::new(),
             context_manager,
+            pending: Arc::new(PendingVerifications::new()),
+            audit,
+            refused: BTreeMap::new(),
+            prompter: PrompterConfig::default().build(),
+            prompter_config: PrompterConfig::default(),
+            admin: None,
+            admin_token,
         }
     }
 
+    /// Serve the admin API on loopback bindings; applies to servers started afterwards
+    pub fn set_admin(&mut self, admin: CommandChannel) {
+        self.admin = Some(admin);
+    }
+
     pub async fn update(&mut self, config: &NetworkConfig) -> Result<()> {
         let mut active_ports = Vec::new();
+        self.refused.clear();
+
+        // Placeholders render, and agent tokens resolve, from the config the servers run with
+        if let Ok(mut manager) = self.context_manager.lock() {
+            manager.set_variables(ContextVariables::from_config(config));
+            manager.set_agent_tokens(config.agent_tokens.clone());
+        }
 
-        for binding in &config.port_bindings {
-            if binding.enabled {
-                active_ports.push(binding.port);
-
-                if !self.servers.contains_key(&binding.port) {
-                    println!("Starting instruction server on port {}", binding.port);
-                    let server = InstructionServer::new(
-                        binding.port,
-                        binding.interface.clone(),
-                        self.context_manager.clone(),
-                    );
-                    if let Err(e) = server.start().await {
-                        eprintln!("Failed to start server on port {}: {}", binding.port, e);
-                    } else {
-                        self.servers.insert(binding.port, server);
+        // Servers keep the prompter they were started with, so a new one restarts them all
+        let prompter_changed = self.prompter_config != config.prompter;
+        if prompter_changed {
+            self.prompter = config.prompter.build();
+            self.prompter_config = config.prompter.clone();
+        }
+
+        for binding in config.port_bindings.iter().filter(|b| b.enabled) {
+            if let Some(reason) = refusal_reason(binding, config) {
+                eprintln!(
+                    "Refusing to start server on port {}: {}",
+                    binding.port, reason
+                );
+                self.refused.insert(binding.port, reason);
+                continue;
+            }
+
+            // LAN and public bindings are never served over plain HTTP
+            let tls = if binding.uses_tls() {
+                match TlsStore::new().and_then(|store| {
+                    store.server_config(binding.interface.ip_address, binding.mtls)
+                }) {
+                    Ok(tls) => Some(tls),
+                    Err(e) => {
+                        let reason = format!("TLS certificate unavailable: {}", e);
+                        eprintln!(
+                            "Refusing to start server on port {}: {}",
+                            binding.port, reason
+                        );
+                        self.refused.insert(binding.port, reason);
+                        continue;
                     }
                 }
+            } else {
+                None
+            };
+
+            active_ports.push(binding.port);
+
+            // Restart servers whose interface or token changed
+            if let Some(running) = self.servers.get(&binding.port) {
+                if running.binding == *binding && !prompter_changed {
+                    continue;
+                }
+                println!("Restarting instruction server on port {}", binding.port);
+                if let Some(running) = self.servers.remove(&binding.port) {
+                    let _ = running.server.stop().await;
+                }
+            }
+
+            println!("Starting instruction server on port {}", binding.port);
+            let server = InstructionServer::new(
+                binding.port,
+                binding.interface.clone(),
+                self.context_manager.clone(),
+            )
+            .with_pending(self.pending.clone())
+            .with_audit(self.audit.clone())
+            .with_token(binding.token.as_deref())
+            .with_tls(tls)
+            .with_prompter(self.prompter.clone())
+            .with_admin(self.admin.clone())
+            .with_admin_token(self.admin_token.as_deref());
+            if let Err(e) = server.start().await {
+                eprintln!("Failed to start server on port {}: {}", binding.port, e);
+            } else {
+                self.servers.insert(
+                    binding.port,
+                    RunningServer {
+                        binding: binding.clone(),
+                        server,
+                    },
+                );
             }
         }
 
-        // Stop disabled servers
+        // Stop disabled and refused servers
         let running_ports: Vec<u16> = self.servers.keys().cloned().collect();
         for port in running_ports {
             if !active_ports.contains(&port) {
                 println!("Stopping instruction server on port {}", port);
-                if let Some(server) = self.servers.remove(&port) {
-                    let _ = server.stop().await;
+                if let Some(running) = self.servers.remove(&port) {
+                    let _ = running.server.stop().await;
                 }
             }
         }
@@ -56,8 +163,8 @@ impl ServerPool {
     }
 
     pub async fn shutdown(&mut self) {
-        for (_, server) in self.servers.drain() {
-            let _ = server.stop().await;
+        for (_, running) in self.servers.drain() {
+            let _ = running.server.stop().await;
         }
     }
 
@@ -65,7 +172,37 @@ impl ServerPool {
         self.servers.len()
     }
 
+    pub fn pending_verifications(&self) -> Arc<PendingVerifications> {
+        self.pending.clone()
+    }
+
+    pub fn audit_log(&self) -> Arc<AuditLog> {
+        self.audit.clone()
+    }
+
     pub fn has_server(&self, port: u16) -> bool {
         self.servers.contains_key(&port)
     }
+
+    /// Prompter the running servers ask the human with
+    pub fn prompter(&self) -> Arc<dyn Prompter> {
+        self.prompter.clone()
+    }
+
+    /// Enabled bindings the last `update` refused to start, with the reason
+    pub fn refused_bindings(&self) -> &BTreeMap<u16, String> {
+        &self.refused
+    }
+}
+
+/// Why a binding must not be served. Anything reachable beyond this machine needs
+/// a token unless the user explicitly accepted the risk.
+fn refusal_reason(binding: &PortBinding, config: &NetworkConfig) -> Option<String> {
+    if binding.is_loopback() || binding.token.is_some() || config.allow_insecure_public {
+        return None;
+    }
+    Some(format!(
+        "{} is reachable from the network but has no token (set allow_insecure_public = true to override)",
+        binding.interface.ip_address
+    ))
 }
@@ -0,0 +1,436 @@
+use crate::config::network_config::NetworkConfig;
+use anyhow::{anyhow, Context, Result};
+use axum::Extension;
+use axum_server::accept::Accept;
+use axum_server::tls_rustls::{RustlsAcceptor, RustlsConfig};
+use chrono::{Datelike, Duration, Utc};
+use rcgen::{
+    BasicConstraints, CertificateParams, DistinguishedName, DnType, DnValue,
+    ExtendedKeyUsagePurpose, IsCa, KeyPair, KeyUsagePurpose,
+};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::{CertificateDer, PrivateKeyDer};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::future::Future;
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tower_layer::Layer;
+
+pub const CA_CERT_FILE: &str = "ca.crt";
+const CA_KEY_FILE: &str = "ca.key";
+const HOSTS_DIR: &str = "hosts";
+const AGENTS_DIR: &str = "agents";
+/// Fingerprints of agent certificates that were reissued or revoked, one per line
+pub const REVOKED_FILE: &str = "revoked.txt";
+/// Client identity `chase` itself uses on mTLS ports
+pub const CLI_AGENT_NAME: &str = "chase-cli";
+const CA_COMMON_NAME: &str = "ChaseAI Local CA";
+const CA_VALIDITY_DAYS: i64 = 3650;
+// Stay within the 398-day limit clients enforce for leaf certificates
+const HOST_VALIDITY_DAYS: i64 = 397;
+const AGENT_VALIDITY_DAYS: i64 = 397;
+// Reissue host and agent certificates this long before they expire
+const HOST_RENEW_DAYS: i64 = 30;
+
+/// Local certificate authority and per-host certificates under `~/.config/chaseai/tls/`
+#[derive(Debug, Clone)]
+pub struct TlsStore {
+    dir: PathBuf,
+}
+
+impl TlsStore {
+    pub fn new() -> Result<Self> {
+        let config_path = NetworkConfig::config_path()?;
+        let dir = config_path
+            .parent()
+            .context("Config path has no parent directory")?
+            .join("tls");
+        Ok(Self { dir })
+    }
+
+    pub fn in_dir(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn ca_cert_path(&self) -> PathBuf {
+        self.dir.join(CA_CERT_FILE)
+    }
+
+    /// PEM of the local CA, creating the CA on first use
+    pub fn ca_cert_pem(&self) -> Result<String> {
+        self.ensure_ca()?;
+        Ok(fs::read_to_string(self.ca_cert_path())?)
+    }
+
+    /// SHA-256 of the CA certificate (DER), as colon-separated uppercase hex
+    pub fn ca_fingerprint(&self) -> Result<String> {
+        let pem = self.ca_cert_pem()?;
+        let der = CertificateDer::from_pem_slice(pem.as_bytes())
+            .map_err(|e| anyhow!("Invalid CA certificate: {}", e))?;
+        Ok(fingerprint(&der))
+    }
+
+    /// rustls config serving a certificate for `ip`, signed by the local CA.
+    /// With `client_auth`, clients must present a certificate issued by the same CA
+    /// and not revoked since.
+    pub fn server_config(&self, ip: IpAddr, client_auth: bool) -> Result<ServerTls> {
+        let (cert_pem, key_pem) = self.ensure_host(ip)?;
+        let ca_pem = fs::read_to_string(self.ca_cert_path())?;
+
+        let chain = [cert_pem.as_bytes(), ca_pem.as_bytes()]
+            .into_iter()
+            .map(|pem| {
+                CertificateDer::from_pem_slice(pem)
+                    .map_err(|e| anyhow!("Invalid certificate: {}", e))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let key = PrivateKeyDer::from_pem_slice(key_pem.as_bytes())
+            .map_err(|e| anyhow!("Invalid private key: {}", e))?;
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?;
+        let config = if client_auth {
+            let mut roots = RootCertStore::empty();
+            roots.add(chain[1].clone())?;
+            let verifier =
+                WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider).build()?;
+            builder
+                .with_client_cert_verifier(verifier)
+                .with_single_cert(chain, key)?
+        } else {
+            builder.with_no_client_auth().with_single_cert(chain, key)?
+        };
+        Ok(ServerTls {
+            config: Arc::new(config),
+            revoked: client_auth.then(|| self.dir.join(REVOKED_FILE)),
+        })
+    }
+
+    /// Issue a client certificate whose common name identifies the agent on mTLS ports.
+    /// Reissuing revokes the previous certificate for that name, so only the new one
+    /// connects. [`CLI_AGENT_NAME`] is reserved for `chase` itself.
+    pub fn issue_agent(&self, name: &str) -> Result<AgentCredentials> {
+        if name == CLI_AGENT_NAME {
+            return Err(anyhow!(
+                "'{}' is the name chase itself uses; pick another agent name",
+                CLI_AGENT_NAME
+            ));
+        }
+        self.issue(name)
+    }
+
+    fn issue(&self, name: &str) -> Result<AgentCredentials> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        {
+            return Err(anyhow!(
+                "Agent name must be non-empty and use only letters, digits, '-', '_' or '.'"
+            ));
+        }
+        self.ensure_ca()?;
+
+        let mut params = CertificateParams::default();
+        params.distinguished_name.push(DnType::CommonName, name);
+        params
+            .distinguished_name
+            .push(DnType::OrganizationName, "ChaseAI Agents");
+        params.key_usages = vec![KeyUsagePurpose::DigitalSignature];
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+        set_validity(&mut params, AGENT_VALIDITY_DAYS);
+
+        let key = KeyPair::generate()?;
+        let (ca_cert, ca_key) = self.load_ca()?;
+        let cert = params.signed_by(&key, &ca_cert, &ca_key)?;
+
+        let agents = self.dir.join(AGENTS_DIR);
+        fs::create_dir_all(&agents)?;
+        let credentials = AgentCredentials {
+            name: name.to_string(),
+            cert_path: agents.join(format!("{}.crt", name)),
+            key_path: agents.join(format!("{}.key", name)),
+        };
+        self.revoke(&credentials.cert_path)?;
+        write_private(&credentials.key_path, &key.serialize_pem())?;
+        fs::write(&credentials.cert_path, cert.pem())?;
+        Ok(credentials)
+    }
+
+    /// Certificate and key of an issued agent, issuing it first if needed
+    pub fn agent(&self, name: &str) -> Result<AgentCredentials> {
+        let agents = self.dir.join(AGENTS_DIR);
+        let cert_path = agents.join(format!("{}.crt", name));
+        let key_path = agents.join(format!("{}.key", name));
+        match fs::read_to_string(&cert_path) {
+            Ok(cert) if key_path.exists() && !expires_soon(&cert) => Ok(AgentCredentials {
+                name: name.to_string(),
+                cert_path,
+                key_path,
+            }),
+            _ => self.issue(name),
+        }
+    }
+
+    /// Revoke the agent's certificate and delete its files. Returns whether it had one.
+    pub fn revoke_agent(&self, name: &str) -> Result<bool> {
+        let agents = self.dir.join(AGENTS_DIR);
+        let cert_path = agents.join(format!("{}.crt", name));
+        if !self.revoke(&cert_path)? {
+            return Ok(false);
+        }
+        fs::remove_file(&cert_path)?;
+        match fs::remove_file(agents.join(format!("{}.key", name))) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(true),
+        }
+    }
+
+    /// Add the certificate at `cert_path` to [`REVOKED_FILE`]; `false` if there is none
+    fn revoke(&self, cert_path: &Path) -> Result<bool> {
+        let pem = match fs::read(cert_path) {
+            Ok(pem) => pem,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", cert_path)),
+        };
+        let der = CertificateDer::from_pem_slice(&pem)
+            .map_err(|e| anyhow!("Invalid certificate at {:?}: {}", cert_path, e))?;
+        let path = self.dir.join(REVOKED_FILE);
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {:?}", path))?;
+        writeln!(file, "{}", fingerprint(&der))?;
+        file.sync_data()?;
+        Ok(true)
+    }
+
+    fn load_ca(&self) -> Result<(rcgen::Certificate, KeyPair)> {
+        let ca_key = KeyPair::from_pem(&fs::read_to_string(self.dir.join(CA_KEY_FILE))?)?;
+        let ca_cert =
+            CertificateParams::from_ca_cert_pem(&fs::read_to_string(self.ca_cert_path())?)?
+                .self_signed(&ca_key)?;
+        Ok((ca_cert, ca_key))
+    }
+
+    fn ensure_ca(&self) -> Result<()> {
+        if self.ca_cert_path().exists() && self.dir.join(CA_KEY_FILE).exists() {
+            return Ok(());
+        }
+
+        let mut params = CertificateParams::default();
+        let mut name = DistinguishedName::new();
+        name.push(DnType::CommonName, CA_COMMON_NAME);
+        name.push(DnType::OrganizationName, "ChaseAI");
+        params.distinguished_name = name;
+        params.is_ca = IsCa::Ca(BasicConstraints::Constrained(0));
+        params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign];
+        set_validity(&mut params, CA_VALIDITY_DAYS);
+
+        let key = KeyPair::generate()?;
+        let cert = params.self_signed(&key)?;
+
+        fs::create_dir_all(&self.dir)?;
+        write_private(&self.dir.join(CA_KEY_FILE), &key.serialize_pem())?;
+        fs::write(self.ca_cert_path(), cert.pem())?;
+
+        // Certificates signed by a previous CA are no longer trusted
+        for dir in [HOSTS_DIR, AGENTS_DIR] {
+            let dir = self.dir.join(dir);
+            if dir.exists() {
+                fs::remove_dir_all(dir)?;
+            }
+        }
+        match fs::remove_file(self.dir.join(REVOKED_FILE)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Certificate and key PEM for `ip`, reissued when missing or close to expiry
+    fn ensure_host(&self, ip: IpAddr) -> Result<(String, String)> {
+        self.ensure_ca()?;
+
+        let stem = ip.to_string().replace(':', "_");
+        let hosts = self.dir.join(HOSTS_DIR);
+        let cert_path = hosts.join(format!("{}.crt", stem));
+        let key_path = hosts.join(format!("{}.key", stem));
+
+        if let (Ok(cert), Ok(key)) = (
+            fs::read_to_string(&cert_path),
+            fs::read_to_string(&key_path),
+        ) {
+            if !expires_soon(&cert) {
+                return Ok((cert, key));
+            }
+        }
+
+        let (ca_cert, ca_key) = self.load_ca()?;
+
+        let mut params = CertificateParams::new(vec![ip.to_string()])?;
+        params
+            .distinguished_name
+            .push(DnType::CommonName, ip.to_string());
+        params.key_usages = vec![KeyUsagePurpose::DigitalSignature];
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+        set_validity(&mut params, HOST_VALIDITY_DAYS);
+
+        let key = KeyPair::generate()?;
+        let cert = params.signed_by(&key, &ca_cert, &ca_key)?;
+
+        fs::create_dir_all(&hosts)?;
+        write_private(&key_path, &key.serialize_pem())?;
+        fs::write(&cert_path, cert.pem())?;
+        Ok((cert.pem(), key.serialize_pem()))
+    }
+}
+
+/// Client certificate and key written by `issue_agent`
+#[derive(Debug, Clone)]
+pub struct AgentCredentials {
+    pub name: String,
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+/// Common name of the verified client certificate, added to each request on mTLS ports
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientIdentity(pub String);
+
+/// What a TLS binding is served with, from [`TlsStore::server_config`]
+#[derive(Debug, Clone)]
+pub struct ServerTls {
+    pub config: Arc<ServerConfig>,
+    /// [`REVOKED_FILE`] on mTLS ports, read on every connection so revoking needs no restart
+    revoked: Option<PathBuf>,
+}
+
+/// TLS acceptor that records the client certificate's common name as a request extension,
+/// and drops connections whose certificate was revoked
+#[derive(Clone)]
+pub struct IdentityAcceptor {
+    inner: RustlsAcceptor,
+    revoked: Option<PathBuf>,
+}
+
+impl IdentityAcceptor {
+    pub fn new(tls: ServerTls) -> Self {
+        Self {
+            inner: RustlsAcceptor::new(RustlsConfig::from_config(tls.config)),
+            revoked: tls.revoked,
+        }
+    }
+}
+
+impl<I, S> Accept<I, S> for IdentityAcceptor
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Send + 'static,
+{
+    type Stream = <RustlsAcceptor as Accept<I, S>>::Stream;
+    type Service = axum::middleware::AddExtension<S, Option<ClientIdentity>>;
+    type Future = Pin<Box<dyn Future<Output = io::Result<(Self::Stream, Self::Service)>> + Send>>;
+
+    fn accept(&self, stream: I, service: S) -> Self::Future {
+        let acceptor = self.inner.clone();
+        let revoked = self.revoked.clone();
+        Box::pin(async move {
+            let (stream, service) = acceptor.accept(stream, service).await?;
+            let certificate = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|chain| chain.first());
+            if let (Some(path), Some(certificate)) = (revoked, certificate) {
+                // An unreadable list refuses everyone rather than letting revoked agents in
+                let list = match tokio::fs::read_to_string(&path).await {
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+                    list => list?,
+                };
+                let presented = fingerprint(certificate);
+                if list.lines().any(|line| line.trim() == presented) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        "Client certificate has been revoked",
+                    ));
+                }
+            }
+            let identity = certificate.and_then(common_name).map(ClientIdentity);
+            Ok((stream, Extension(identity).layer(service)))
+        })
+    }
+}
+
+fn common_name(der: &CertificateDer<'_>) -> Option<String> {
+    let params = CertificateParams::from_ca_cert_der(der).ok()?;
+    match params.distinguished_name.get(&DnType::CommonName)? {
+        DnValue::Utf8String(name) => Some(name.clone()),
+        DnValue::PrintableString(name) => Some(name.as_str().to_string()),
+        _ => None,
+    }
+}
+
+pub fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+fn set_validity(params: &mut CertificateParams, days: i64) {
+    // Backdate a day so clocks that are slightly off still accept the certificate
+    let from = Utc::now() - Duration::days(1);
+    let until = Utc::now() + Duration::days(days);
+    params.not_before = rcgen::date_time_ymd(from.year(), from.month() as u8, from.day() as u8);
+    params.not_after = rcgen::date_time_ymd(until.year(), until.month() as u8, until.day() as u8);
+}
+
+fn expires_soon(cert_pem: &str) -> bool {
+    let Ok(params) = CertificateParams::from_ca_cert_pem(cert_pem) else {
+        return true;
+    };
+    let renew_at = Utc::now() + Duration::days(HOST_RENEW_DAYS);
+    params.not_after.unix_timestamp() < renew_at.timestamp()
+}
+
+/// Write a private key to a new file that is readable by this user from the moment it
+/// exists, then move it over `path`; a key that is already there is replaced whole
+fn write_private(path: &Path, content: &str) -> Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    match fs::remove_file(&temp) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            return Err(e).with_context(|| format!("Failed to remove {:?}", temp))
+        }
+        _ => {}
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(&temp)
+        .with_context(|| format!("Failed to create {:?}", temp))?;
+    file.write_all(content.as_bytes())?;
+    file.sync_data()?;
+    fs::rename(&temp, path).with_context(|| format!("Failed to write {:?}", path))
+}
@@ -389,35 +389,33 @@ pub fn show_verification_dialog(
     reason: &str,
     context_str: &str,
     buttons: &[String],
-    task_id: &str,
+    title: &str,
 ) -> (usize, Option<String>) {
-    let buttons_list = buttons
-        .iter()
-        .map(|b| format!("\"{}\"", b))
-        .collect::<Vec<_>>()
-        .join(", ");
-
-    let default_button = buttons
-        .last()
-        .cloned()
-        .unwrap_or_else(|| "Approve".to_string());
-
-    // Simplified script: directly use activate without System Events check for maximum compatibility
-    let script = format!(
-        r#"
-        activate
-        set userResponse to display alert "🚨 {} | ChaseAI" message "Action: " & "{}" & "\n\nReason: " & "{}" & "\n\nContext: " & "{}" as critical buttons {{{}}} default button "{}"
-        return button returned of userResponse
-        "#,
-        task_id,
-        action.replace("\"", "\\\""),
-        reason.replace("\"", "\\\""),
-        context_str.replace("\"", "\\\""),
-        buttons_list,
-        default_button
-    );
+    if buttons.is_empty() {
+        return (buttons.len(), Some("No buttons to offer".to_string()));
+    }
 
-    let output = Command::new("osascript").arg("-e").arg(script).output();
+    // Everything shown comes from the agent, so it is passed as arguments and never becomes
+    // part of the script. The title goes first: it starts with the emoji, so osascript can't
+    // take it for an option.
+    let script = r#"
+        on run argv
+            activate
+            set buttonLabels to items 3 thru -1 of argv
+            set userResponse to display alert (item 1 of argv) message (item 2 of argv) as critical buttons buttonLabels default button (last item of buttonLabels)
+            return button returned of userResponse
+        end run
+        "#;
+    let output = Command::new("osascript")
+        .arg("-e")
+        .arg(script)
+        .arg(format!("🚨 {} | ChaseAI", title))
+        .arg(format!(
+            "Action: {}\n\nReason: {}\n\nContext: {}",
+            action, reason, context_str
+        ))
+        .args(buttons)
+        .output();
 
     match output {
         Ok(output) if output.status.success() => {
@@ -438,17 +436,18 @@ pub fn show_verification_dialog(
     }
 }
 
+/// No dialog is available, so no button is chosen (the index is out of range)
 #[cfg(not(target_os = "macos"))]
 pub fn show_verification_dialog(
     _action: &str,
     _reason: &str,
     _context: &str,
-    _buttons: &[String],
-    _task_id: &str,
+    buttons: &[String],
+    _title: &str,
 ) -> (usize, Option<String>) {
     (
-        0,
-        Some("Verification not supported on this platform".to_string()),
+        buttons.len(),
+        Some("Verification dialogs are only available on macOS".to_string()),
     )
 }
 
@@ -1,3 +1,5 @@
 pub mod dialogs;
+#[cfg(feature = "tray")]
 pub mod tray;
+#[cfg(feature = "tray")]
 pub mod tray_menu;
@@ -1,5 +1,5 @@
 use crate::config::network_config::NetworkConfig;
-use crate::ui::tray_menu;
+use crate::ui::tray_menu::{self, TrayState};
 use std::path::Path;
 use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
 
@@ -30,9 +30,9 @@ impl TrayManager {
         Ok(Icon::from_rgba(icon_rgba, icon_width, icon_height)?)
     }
 
-    pub fn setup(&mut self, config: &NetworkConfig) -> anyhow::Result<()> {
+    pub fn setup(&mut self, config: &NetworkConfig, state: &TrayState) -> anyhow::Result<()> {
         println!("Setting up tray icon...");
-        let tray_menu = tray_menu::build_menu(config)?;
+        let tray_menu = tray_menu::build_menu_with_state(config, state)?;
         println!("Menu built successfully");
 
         // Get the executable path and construct paths relative to it
@@ -74,7 +74,7 @@ impl TrayManager {
 
         let mut builder = TrayIconBuilder::new()
             .with_menu(Box::new(tray_menu) as Box<dyn tray_icon::menu::ContextMenu>)
-            .with_tooltip("ChaseAI - Network Management");
+            .with_tooltip(state.tooltip());
 
         if let Some(icon) = icon {
             builder = builder.with_icon(icon);
@@ -93,12 +93,13 @@ impl TrayManager {
         Ok(())
     }
 
-    pub fn update_menu(&mut self, config: &NetworkConfig) -> anyhow::Result<()> {
+    pub fn update_menu(&mut self, config: &NetworkConfig, state: &TrayState) -> anyhow::Result<()> {
         if let Some(tray_icon) = &mut self.tray_icon {
-            let new_menu = tray_menu::build_menu(config)?;
+            let new_menu = tray_menu::build_menu_with_state(config, state)?;
             tray_icon.set_menu(Some(
                 Box::new(new_menu) as Box<dyn tray_icon::menu::ContextMenu>
             ));
+            tray_icon.set_tooltip(Some(state.tooltip()))?;
         }
         Ok(())
     }
@@ -1,9 +1,14 @@
+use crate::command::AppCommand;
+use crate::config::change::ConfigChange;
 use crate::config::network_config::NetworkConfig;
+use crate::instruction::history::ContextRevision;
+use crate::instruction::session::{format_remaining, Session};
 #[cfg(feature = "beta")]
 use crate::network::interface_detector::InterfaceDetector;
+use std::collections::BTreeMap;
 #[cfg(any(feature = "beta", feature = "dev"))]
-use tray_icon::menu::{CheckMenuItem, Submenu};
-use tray_icon::menu::{Menu, MenuItem, PredefinedMenuItem};
+use tray_icon::menu::CheckMenuItem;
+use tray_icon::menu::{Menu, MenuItem, PredefinedMenuItem, Submenu};
 
 pub struct MenuIds {
     pub quit: String,
@@ -11,7 +16,39 @@ pub struct MenuIds {
     pub disable_all: String,
 }
 
+/// Runtime state shown in the tray alongside the configuration
+#[derive(Debug, Clone, Default)]
+pub struct TrayState {
+    pub sessions: Vec<Session>,
+    /// The latest context changes, newest first
+    pub revisions: Vec<ContextRevision>,
+    /// Enabled ports the server pool refused to start, with the reason
+    pub port_errors: BTreeMap<u16, String>,
+    /// Why verifications cannot reach the user, if they can't
+    pub prompter_unavailable: Option<String>,
+}
+
+impl TrayState {
+    /// Tooltip for the tray icon; calls out blocked ports so they're noticed
+    pub fn tooltip(&self) -> String {
+        if let Some(reason) = &self.prompter_unavailable {
+            format!("ChaseAI - verifications unavailable: {}", reason)
+        } else if self.port_errors.is_empty() {
+            "ChaseAI - Network Management".to_string()
+        } else {
+            format!(
+                "ChaseAI - {} port(s) blocked: authentication required",
+                self.port_errors.len()
+            )
+        }
+    }
+}
+
 pub fn build_menu(config: &NetworkConfig) -> anyhow::Result<Menu> {
+    build_menu_with_state(config, &TrayState::default())
+}
+
+pub fn build_menu_with_state(config: &NetworkConfig, state: &TrayState) -> anyhow::Result<Menu> {
     let menu = Menu::new();
 
     // 1. Status Section
@@ -25,14 +62,23 @@ pub fn build_menu(config: &NetworkConfig) -> anyhow::Result<Menu> {
     };
 
     // Main Status Item with Version (Clickable)
-    let status_text = if config.port_bindings.iter().any(|p| p.enabled) {
+    let status_text = if state.prompter_unavailable.is_some() {
+        format!("ChaseAI v{}{}: ⚠ No Prompter", version, env_label)
+    } else if !state.port_errors.is_empty() {
+        format!(
+            "ChaseAI v{}{}: ⚠ {} Port(s) Blocked",
+            version,
+            env_label,
+            state.port_errors.len()
+        )
+    } else if config.port_bindings.iter().any(|p| p.enabled) {
         format!("ChaseAI v{}{}: Running", version, env_label)
     } else {
         format!("ChaseAI v{}{}: Stopped", version, env_label)
     };
     // Make it clickable and enabled
     menu.append(&MenuItem::with_id(
-        "cmd:open_repo",
+        AppCommand::OpenRepo.menu_id(),
         &status_text,
         true,
         None,
@@ -48,6 +94,13 @@ pub fn build_menu(config: &NetworkConfig) -> anyhow::Result<Menu> {
     let interface_type = &config.default_interface;
     let start_text = format!("IP: {} ({:?})", ip, interface_type);
     menu.append(&MenuItem::new(&start_text, true, None))?;
+    if let Some(reason) = &state.prompter_unavailable {
+        menu.append(&MenuItem::new(
+            format!("⚠ Verifications fail: {}", reason),
+            false,
+            None,
+        ))?;
+    }
 
     menu.append(&PredefinedMenuItem::separator())?;
 
@@ -59,7 +112,10 @@ pub fn build_menu(config: &NetworkConfig) -> anyhow::Result<Menu> {
 
         for interface in interfaces {
             let is_selected = interface.interface_type == config.default_interface;
-            let id = format!("interface:{}", interface.name);
+            let id = AppCommand::SelectInterface {
+                name: interface.name.clone(),
+            }
+            .menu_id();
             let label = format!("{} ({})", interface.name, interface.ip_address);
             let item = CheckMenuItem::with_id(id, &label, true, is_selected, None);
             interface_menu.append(&item)?;
@@ -76,22 +132,37 @@ pub fn build_menu(config: &NetworkConfig) -> anyhow::Result<Menu> {
         menu.append(&MenuItem::new("Ports:", true, None))?;
 
         for binding in &config.port_bindings {
-            let status_icon = if binding.enabled { "●" } else { "○" };
+            let error = state.port_errors.get(&binding.port);
+            let status_icon = match (error, binding.enabled) {
+                (Some(_), _) => "⚠",
+                (None, true) => "●",
+                (None, false) => "○",
+            };
             let role_name = format!("{:?}", binding.role);
             let port_label = format!("{} {} • {}", status_icon, binding.port, role_name);
+            let error_item =
+                error.map(|reason| MenuItem::new(format!("⚠ Blocked: {}", reason), false, None));
 
             // In Prod, we just show the label, no submenu for editing unless beta
             #[cfg(not(feature = "beta"))]
             {
                 menu.append(&MenuItem::new(&port_label, false, None))?;
+                if let Some(item) = &error_item {
+                    menu.append(item)?;
+                }
             }
 
             #[cfg(feature = "beta")]
             {
                 let port_submenu = Submenu::new(&port_label, true);
 
+                if let Some(item) = &error_item {
+                    port_submenu.append(item)?;
+                    port_submenu.append(&PredefinedMenuItem::separator())?;
+                }
+
                 // 1. Toggle
-                let toggle_id = format!("port:{}", binding.port);
+                let toggle_id = AppCommand::TogglePort { port: binding.port }.menu_id();
                 let toggle_label = if binding.enabled {
                     "● Disable Port"
                 } else {
@@ -102,7 +173,11 @@ pub fn build_menu(config: &NetworkConfig) -> anyhow::Result<Menu> {
                 port_submenu.append(&PredefinedMenuItem::separator())?;
 
                 // 2. Roles
-                let inst_id = format!("role:{}:Instruction", binding.port);
+                let inst_id = AppCommand::change(ConfigChange::SetPortRole {
+                    port: binding.port,
+                    role: crate::network::port_config::PortRole::Instruction,
+                })
+                .menu_id();
                 let is_inst = matches!(
                     binding.role,
                     crate::network::port_config::PortRole::Instruction
@@ -114,7 +189,11 @@ pub fn build_menu(config: &NetworkConfig) -> anyhow::Result<Menu> {
                 };
                 port_submenu.append(&MenuItem::with_id(inst_id, inst_label, true, None))?;
 
-                let ver_id = format!("role:{}:Verification", binding.port);
+                let ver_id = AppCommand::change(ConfigChange::SetPortRole {
+                    port: binding.port,
+                    role: crate::network::port_config::PortRole::Verification,
+                })
+                .menu_id();
                 let is_ver = matches!(
                     binding.role,
                     crate::network::port_config::PortRole::Verification
@@ -129,7 +208,8 @@ pub fn build_menu(config: &NetworkConfig) -> anyhow::Result<Menu> {
                 port_submenu.append(&PredefinedMenuItem::separator())?;
 
                 // 3. Delete
-                let remove_id = format!("remove_port:{}", binding.port);
+                let remove_id =
+                    AppCommand::change(ConfigChange::RemovePort { port: binding.port }).menu_id();
                 port_submenu.append(&MenuItem::with_id(remove_id, "✕ Remove Port", true, None))?;
 
                 menu.append(&port_submenu)?;
@@ -140,7 +220,12 @@ pub fn build_menu(config: &NetworkConfig) -> anyhow::Result<Menu> {
     // Add new port button (BETA Only)
     #[cfg(feature = "beta")]
     {
-        let add_port = MenuItem::with_id("cmd:add_port", "Add New Port...", true, None);
+        let add_port = MenuItem::with_id(
+            AppCommand::AddPortDialog.menu_id(),
+            "Add New Port...",
+            true,
+            None,
+        );
         menu.append(&add_port)?;
     }
 
@@ -161,14 +246,20 @@ pub fn build_menu(config: &NetworkConfig) -> anyhow::Result<Menu> {
         );
 
         mode_menu.append(&CheckMenuItem::with_id(
-            "mode:port",
+            AppCommand::change(ConfigChange::SetVerificationMode {
+                mode: crate::config::network_config::VerificationMode::Port,
+            })
+            .menu_id(),
             "Port (HTTP)",
             true,
             is_port,
             None,
         ))?;
         mode_menu.append(&CheckMenuItem::with_id(
-            "mode:cli",
+            AppCommand::change(ConfigChange::SetVerificationMode {
+                mode: crate::config::network_config::VerificationMode::Cli,
+            })
+            .menu_id(),
             "CLI (chase --verification)",
             true,
             is_cli,
@@ -184,17 +275,37 @@ pub fn build_menu(config: &NetworkConfig) -> anyhow::Result<Menu> {
     {
         menu.append(&PredefinedMenuItem::separator())?;
         println!("Adding Download Config button to menu");
-        let download_config =
-            MenuItem::with_id("cmd:download_config", "Download Config", true, None);
+        let download_config = MenuItem::with_id(
+            AppCommand::DownloadConfig.menu_id(),
+            "Download Config",
+            true,
+            None,
+        );
         menu.append(&download_config)?;
         println!("Download Config button added successfully");
     }
 
     menu.append(&PredefinedMenuItem::separator())?;
 
+    // 5. Active Sessions (Available in all builds so approvals can always be revoked)
+    menu.append(&build_sessions_menu(&state.sessions)?)?;
+    menu.append(&build_history_menu(&state.revisions)?)?;
+
+    menu.append(&PredefinedMenuItem::separator())?;
+
     // Global Commands (Available in all builds)
-    let enable_all = MenuItem::with_id("cmd:enable_all", "Enable All Services", true, None);
-    let disable_all = MenuItem::with_id("cmd:disable_all", "Disable All Services", true, None);
+    let enable_all = MenuItem::with_id(
+        AppCommand::change(ConfigChange::SetAllEnabled { enabled: true }).menu_id(),
+        "Enable All Services",
+        true,
+        None,
+    );
+    let disable_all = MenuItem::with_id(
+        AppCommand::change(ConfigChange::SetAllEnabled { enabled: false }).menu_id(),
+        "Disable All Services",
+        true,
+        None,
+    );
 
     menu.append(&enable_all)?;
     menu.append(&disable_all)?;
@@ -202,8 +313,94 @@ pub fn build_menu(config: &NetworkConfig) -> anyhow::Result<Menu> {
     menu.append(&PredefinedMenuItem::separator())?;
 
     // Custom quit menu item with lowercase
-    let quit_item = MenuItem::with_id("quit", "Quit ChaseAI", true, None);
+    let quit_item = MenuItem::with_id(AppCommand::Quit.menu_id(), "Quit ChaseAI", true, None);
     menu.append(&quit_item)?;
 
     Ok(menu)
 }
+
+/// One item per recent revision; choosing it brings that revision's context back
+fn build_history_menu(revisions: &[ContextRevision]) -> anyhow::Result<Submenu> {
+    let history_menu = Submenu::new("Context History", true);
+
+    if revisions.is_empty() {
+        history_menu.append(&MenuItem::new("No context changes", false, None))?;
+        return Ok(history_menu);
+    }
+
+    for revision in revisions {
+        let label = format!(
+            "Roll back to #{} • {} • {:?} {}",
+            revision.rev,
+            revision.target(),
+            revision.author,
+            revision.at.with_timezone(&chrono::Local).format("%H:%M")
+        );
+        let id = AppCommand::RollbackContext { rev: revision.rev }.menu_id();
+        history_menu.append(&MenuItem::with_id(id, &label, true, None))?;
+    }
+    Ok(history_menu)
+}
+
+fn build_sessions_menu(sessions: &[Session]) -> anyhow::Result<Submenu> {
+    let title = format!("Active Sessions ({})", sessions.len());
+    let sessions_menu = Submenu::new(&title, true);
+
+    if sessions.is_empty() {
+        sessions_menu.append(&MenuItem::new("No active sessions", false, None))?;
+        return Ok(sessions_menu);
+    }
+
+    for session in sessions {
+        let label = format!(
+            "{} • {} left",
+            session.id,
+            format_remaining(session.remaining())
+        );
+        let session_submenu = Submenu::new(&label, true);
+
+        session_submenu.append(&MenuItem::new(
+            format!("Owner: {}", session.owner),
+            false,
+            None,
+        ))?;
+        session_submenu.append(&MenuItem::new(
+            format!("Actions: {}", session.scope.actions.join(", ")),
+            false,
+            None,
+        ))?;
+        if let Some(task_id) = &session.scope.task_id {
+            session_submenu.append(&MenuItem::new(format!("Task: {}", task_id), false, None))?;
+        }
+        let uses = match session.scope.max_uses {
+            Some(max) => format!("Uses: {} / {}", session.uses, max),
+            None => format!("Uses: {}", session.uses),
+        };
+        session_submenu.append(&MenuItem::new(&uses, false, None))?;
+
+        session_submenu.append(&PredefinedMenuItem::separator())?;
+
+        let revoke_id = AppCommand::RevokeSession {
+            session_id: session.id.clone(),
+        }
+        .menu_id();
+        session_submenu.append(&MenuItem::with_id(
+            revoke_id,
+            "✕ Revoke Session",
+            true,
+            None,
+        ))?;
+
+        sessions_menu.append(&session_submenu)?;
+    }
+
+    sessions_menu.append(&PredefinedMenuItem::separator())?;
+    sessions_menu.append(&MenuItem::with_id(
+        AppCommand::RevokeAllSessions.menu_id(),
+        "Revoke All Sessions",
+        true,
+        None,
+    ))?;
+
+    Ok(sessions_menu)
+}
@@ -0,0 +1,450 @@
+use crate::verification::request::{ButtonDecision, VerificationRequest};
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// File name of the audit log inside the ChaseAI config directory
+pub const AUDIT_FILE_NAME: &str = "audit.jsonl";
+
+/// Key for the audit log's hashes, beside it and readable by this user only
+pub const AUDIT_KEY_FILE_NAME: &str = "audit.key";
+
+// `hash` is the last field, so a sealed line is its unsealed line with this suffix filled in
+const UNSEALED_SUFFIX: &str = r#""hash":""}"#;
+
+/// `prev_hash` of the first record in the chain
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// One `/verify` decision as it was made.
+///
+/// Records form a hash chain: `hash` is an HMAC-SHA256, keyed with `audit.key`, of the
+/// line exactly as written with `hash` left empty. That line includes `prev_hash`, the
+/// `hash` of the record before it. Without the key, editing, reordering or removing any
+/// record breaks every link after it. The key sits beside the log, so whoever can write
+/// both can also rebuild the chain or drop its last records; [`ChainAnchor`] catches that.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditRecord {
+    pub verification_id: String,
+    pub requested_at: DateTime<Utc>,
+    pub decided_at: DateTime<Utc>,
+    pub duration_ms: i64,
+    /// Address of the client that sent the request
+    pub client_addr: Option<String>,
+    /// Port the request arrived on
+    pub port: u16,
+    pub request: VerificationRequest,
+    /// Button the human clicked, if a dialog was shown
+    pub chosen_button: Option<String>,
+    pub status: String,
+    pub message: Option<String>,
+    /// Session that auto-approved the request, or that the request created
+    pub session_id: Option<String>,
+    /// `policy.toml` rule that matched the request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy_rule: Option<String>,
+    /// Decision behind `status`, if one was made
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decision: Option<ButtonDecision>,
+    /// Hash of the previous record ([`GENESIS_HASH`] for the first one)
+    #[serde(default)]
+    pub prev_hash: String,
+    /// Keyed hash of this record's line and its link to the previous record, hex encoded.
+    /// Must stay the last field.
+    #[serde(default)]
+    pub hash: String,
+}
+
+/// First record where the hash chain does not hold
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrokenLink {
+    /// 1-based line number in the log file
+    pub line: usize,
+    pub verification_id: Option<String>,
+    pub reason: String,
+}
+
+/// Result of walking the hash chain
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainReport {
+    /// Records verified before the first broken link (or all of them)
+    pub verified: usize,
+    /// Hash of the last verified record
+    pub head: String,
+    pub broken: Option<BrokenLink>,
+}
+
+/// How long the chain is and where it ends, as the running instance wrote it.
+///
+/// Kept in the instance's memory rather than a file, so an agent that rewrites
+/// `audit.jsonl` and re-seals it with `audit.key` can't make the two agree again.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChainAnchor {
+    pub records: usize,
+    /// Hash of record number `records` ([`GENESIS_HASH`] when there are none)
+    pub head: String,
+}
+
+impl ChainReport {
+    pub fn is_intact(&self) -> bool {
+        self.broken.is_none()
+    }
+}
+
+/// Query parameters accepted by `GET /audit`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuditFilter {
+    /// Only records requested at or after this time (RFC 3339)
+    pub from: Option<DateTime<Utc>>,
+    /// Only records requested at or before this time (RFC 3339)
+    pub to: Option<DateTime<Utc>>,
+    pub status: Option<String>,
+    pub task_id: Option<String>,
+    /// Case-insensitive substring of the action
+    pub action: Option<String>,
+    /// Return at most this many of the most recent matches
+    pub limit: Option<usize>,
+}
+
+impl AuditFilter {
+    pub fn matches(&self, record: &AuditRecord) -> bool {
+        if self.from.is_some_and(|from| record.requested_at < from) {
+            return false;
+        }
+        if self.to.is_some_and(|to| record.requested_at > to) {
+            return false;
+        }
+        if let Some(status) = &self.status {
+            if !record.status.eq_ignore_ascii_case(status) {
+                return false;
+            }
+        }
+        if let Some(task_id) = &self.task_id {
+            if record.request.task_id() != Some(task_id.as_str()) {
+                return false;
+            }
+        }
+        if let Some(action) = &self.action {
+            if !record
+                .request
+                .action
+                .to_lowercase()
+                .contains(&action.to_lowercase())
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Append-only, hash-chained JSONL log of every verification decision
+pub struct AuditLog {
+    path: PathBuf,
+    key_path: PathBuf,
+    // End of the chain as this process knows it; also serializes concurrent appends.
+    // `None` until the existing file has been read.
+    anchor: Mutex<Option<ChainAnchor>>,
+}
+
+impl AuditLog {
+    /// Audit log at `path`, keyed with `audit.key` beside it
+    pub fn with_path(path: PathBuf) -> Self {
+        Self {
+            key_path: path.with_file_name(AUDIT_KEY_FILE_NAME),
+            path,
+            anchor: Mutex::new(None),
+        }
+    }
+
+    /// Audit log stored in `dir` (normally next to `contexts.json`)
+    pub fn in_dir(dir: &Path) -> Self {
+        Self::with_path(dir.join(AUDIT_FILE_NAME))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Chain `record` to the last one and append it.
+    ///
+    /// `prev_hash` and `hash` are filled in here; any values set by the caller are replaced.
+    pub fn append(&self, record: &AuditRecord) -> Result<AuditRecord> {
+        let mut anchor = self.anchor.lock().unwrap_or_else(|e| e.into_inner());
+        let current = match anchor.as_ref() {
+            Some(anchor) => anchor.clone(),
+            None => self.read_anchor()?,
+        };
+
+        if let Some(parent) = self.path.parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        let key = self.key()?;
+
+        let mut record = record.clone();
+        record.prev_hash = current.head;
+        record.hash = String::new();
+        let unsealed = serde_json::to_string(&record)?;
+        record.hash = keyed_hash(&key, &unsealed)?;
+        let line = unsealed
+            .strip_suffix(UNSEALED_SUFFIX)
+            .map(|rest| format!(r#"{}"hash":"{}"}}"#, rest, record.hash))
+            .ok_or_else(|| anyhow!("Audit record does not end with its hash"))?;
+
+        let mut options = OpenOptions::new();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options
+            .open(&self.path)
+            .with_context(|| format!("Failed to open audit log at {:?}", self.path))?;
+
+        writeln!(file, "{}", line)?;
+        file.sync_data()?;
+
+        *anchor = Some(ChainAnchor {
+            records: current.records + 1,
+            head: record.hash.clone(),
+        });
+        Ok(record)
+    }
+
+    /// End of the chain: read from the file the first time, then advanced by every append
+    pub fn anchor(&self) -> Result<ChainAnchor> {
+        let mut anchor = self.anchor.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(anchor) = anchor.as_ref() {
+            return Ok(anchor.clone());
+        }
+        let read = self.read_anchor()?;
+        *anchor = Some(read.clone());
+        Ok(read)
+    }
+
+    /// Walk the chain from the first record and report the first broken link
+    pub fn verify_chain(&self) -> Result<ChainReport> {
+        self.walk(None)
+    }
+
+    /// [`verify_chain`](Self::verify_chain), and also check that record number
+    /// `anchor.records` exists and has the anchor's hash. This catches what the links alone
+    /// can't: records dropped from the end, or a chain rebuilt with the key.
+    pub fn verify_against(&self, anchor: &ChainAnchor) -> Result<ChainReport> {
+        let mut report = self.walk(Some(anchor))?;
+        if report.is_intact() && report.verified < anchor.records {
+            report.broken = Some(BrokenLink {
+                line: report.verified + 1,
+                verification_id: None,
+                reason: format!(
+                    "the running instance wrote {} record(s), but the file ends after {}",
+                    anchor.records, report.verified
+                ),
+            });
+        }
+        Ok(report)
+    }
+
+    fn walk(&self, anchor: Option<&ChainAnchor>) -> Result<ChainReport> {
+        let mut report = ChainReport {
+            verified: 0,
+            head: GENESIS_HASH.to_string(),
+            broken: None,
+        };
+        if !self.path.exists() {
+            return Ok(report);
+        }
+        let key = self.read_key()?.ok_or_else(|| {
+            anyhow!(
+                "No audit key at {:?}; the chain can't be checked without it",
+                self.key_path
+            )
+        })?;
+
+        let file = fs::File::open(&self.path)
+            .with_context(|| format!("Failed to open audit log at {:?}", self.path))?;
+
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            let broken = |verification_id: Option<String>, reason: String| BrokenLink {
+                line: index + 1,
+                verification_id,
+                reason,
+            };
+
+            let record: AuditRecord = match serde_json::from_str(&line) {
+                Ok(record) => record,
+                Err(e) => {
+                    report.broken = Some(broken(None, format!("unreadable record: {}", e)));
+                    break;
+                }
+            };
+            let id = Some(record.verification_id.clone());
+
+            if record.prev_hash != report.head {
+                report.broken = Some(broken(
+                    id,
+                    format!(
+                        "prev_hash {} does not match the previous record's hash {}",
+                        short(&record.prev_hash),
+                        short(&report.head)
+                    ),
+                ));
+                break;
+            }
+            // Hash the bytes on disk, not a re-serialization that could paper over edits
+            let sealed_suffix = format!(r#""hash":"{}"}}"#, record.hash);
+            let expected = match line.strip_suffix(&sealed_suffix) {
+                Some(rest) => Some(keyed_hash(&key, &format!("{}{}", rest, UNSEALED_SUFFIX))?),
+                None => None,
+            };
+            if expected.as_deref() != Some(record.hash.as_str()) {
+                report.broken = Some(broken(
+                    id,
+                    "record content does not match its hash".to_string(),
+                ));
+                break;
+            }
+
+            if let Some(anchor) =
+                anchor.filter(|a| a.records == report.verified + 1 && a.head != record.hash)
+            {
+                report.broken = Some(broken(
+                    id,
+                    format!(
+                        "hash {} is not the {} the running instance wrote here",
+                        short(&record.hash),
+                        short(&anchor.head)
+                    ),
+                ));
+                break;
+            }
+
+            report.verified += 1;
+            report.head = record.hash;
+        }
+        Ok(report)
+    }
+
+    /// The key in `audit.key`, generated when the first record is written
+    fn key(&self) -> Result<String> {
+        if let Some(key) = self.read_key()? {
+            return Ok(key);
+        }
+
+        let mut bytes = [0u8; 32];
+        getrandom::getrandom(&mut bytes)
+            .map_err(|e| anyhow!("Failed to generate the audit key: {}", e))?;
+        let key = hex::encode(bytes);
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        match options.open(&self.key_path) {
+            Ok(mut file) => {
+                writeln!(file, "{}", key)?;
+                file.sync_data()?;
+                Ok(key)
+            }
+            // Another process wrote the first record at the same time
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => self
+                .read_key()?
+                .ok_or_else(|| anyhow!("Audit key at {:?} disappeared", self.key_path)),
+            Err(e) => Err(e)
+                .with_context(|| format!("Failed to create the audit key at {:?}", self.key_path)),
+        }
+    }
+
+    fn read_key(&self) -> Result<Option<String>> {
+        match fs::read_to_string(&self.key_path) {
+            Ok(key) => {
+                let key = key.trim();
+                anyhow::ensure!(
+                    !key.is_empty(),
+                    "Audit key at {:?} is empty, so the chain has nothing to check against",
+                    self.key_path
+                );
+                Ok(Some(key.to_string()))
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e)
+                .with_context(|| format!("Failed to read the audit key at {:?}", self.key_path)),
+        }
+    }
+
+    fn read_anchor(&self) -> Result<ChainAnchor> {
+        let records = self.read_all()?;
+        Ok(ChainAnchor {
+            records: records.len(),
+            head: records
+                .last()
+                .map(|r| r.hash.clone())
+                .unwrap_or_else(|| GENESIS_HASH.to_string()),
+        })
+    }
+
+    /// All records in the order they were written. A line that isn't a record is an
+    /// error, never skipped: it would hide a record from every query.
+    pub fn read_all(&self) -> Result<Vec<AuditRecord>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let file = fs::File::open(&self.path)
+            .with_context(|| format!("Failed to open audit log at {:?}", self.path))?;
+
+        let mut records = Vec::new();
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record = serde_json::from_str(&line).with_context(|| {
+                format!(
+                    "Audit record on line {} of {:?} is malformed; check it with `chase history verify`",
+                    index + 1,
+                    self.path
+                )
+            })?;
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    pub fn query(&self, filter: &AuditFilter) -> Result<Vec<AuditRecord>> {
+        let mut records: Vec<AuditRecord> = self
+            .read_all()?
+            .into_iter()
+            .filter(|r| filter.matches(r))
+            .collect();
+
+        if let Some(limit) = filter.limit {
+            let skip = records.len().saturating_sub(limit);
+            records.drain(..skip);
+        }
+        Ok(records)
+    }
+}
+
+fn short(hash: &str) -> &str {
+    hash.get(..12).unwrap_or(hash)
+}
+
+/// HMAC-SHA256 of `line` under `key`, hex encoded
+fn keyed_hash(key: &str, line: &str) -> Result<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes())
+        .map_err(|e| anyhow!("Audit key can't be used: {}", e))?;
+    mac.update(line.as_bytes());
+    Ok(hex::encode(mac.finalize().into_bytes()))
+}
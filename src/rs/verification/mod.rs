@@ -0,0 +1,5 @@
+pub mod audit;
+pub mod pending;
+pub mod policy;
+pub mod prompter;
+pub mod request;
@@ -0,0 +1,156 @@
+use crate::instruction::storage::ContextKey;
+use crate::verification::request::{VerificationRequest, VerificationResponse};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Upper bound for long-polling via `GET /verify/{id}?wait=...`
+pub const MAX_WAIT: Duration = Duration::from_secs(120);
+
+/// How long a blocking `POST /verify` waits for a decision before answering `202` like a
+/// non-blocking one, so the agent polls instead of holding the connection
+pub const BLOCKING_WAIT: Duration = Duration::from_secs(600);
+
+/// How long decided verifications stay queryable
+const RETENTION_HOURS: i64 = 1;
+
+struct Entry {
+    created_at: DateTime<Utc>,
+    /// The port and agent that asked; nobody else may see the decision
+    owner: ContextKey,
+    request: VerificationRequest,
+    state: watch::Sender<VerificationResponse>,
+}
+
+/// Snapshot of a verification that has not been decided yet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingVerification {
+    pub verification_id: String,
+    pub created_at: DateTime<Utc>,
+    pub request: VerificationRequest,
+}
+
+/// Registry of verifications, shared by all servers in the pool.
+///
+/// Every `/verify` call is registered here before the dialog is shown, so the
+/// decision can be retrieved later even if the original HTTP request is gone.
+#[derive(Default)]
+pub struct PendingVerifications {
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl PendingVerifications {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a new verification in the `pending` state, asked for by `owner`
+    pub fn register(
+        &self,
+        verification_id: &str,
+        request: &VerificationRequest,
+        owner: ContextKey,
+    ) {
+        let (state, _) = watch::channel(VerificationResponse::pending(verification_id));
+        let mut entries = self.lock();
+        Self::prune(&mut entries);
+        entries.insert(
+            verification_id.to_string(),
+            Entry {
+                created_at: Utc::now(),
+                owner,
+                request: request.clone(),
+                state,
+            },
+        );
+    }
+
+    /// Record the final decision and wake up any long-polling clients
+    pub fn resolve(&self, verification_id: &str, response: VerificationResponse) -> bool {
+        match self.lock().get(verification_id) {
+            Some(entry) => {
+                entry.state.send_replace(response);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether `owner` asked for this verification. Its decision can carry a session id,
+    /// so other ports and agents are told it doesn't exist.
+    pub fn is_owned_by(&self, verification_id: &str, owner: &ContextKey) -> bool {
+        self.lock()
+            .get(verification_id)
+            .is_some_and(|e| &e.owner == owner)
+    }
+
+    /// Current state of a verification, if known
+    pub fn get(&self, verification_id: &str) -> Option<VerificationResponse> {
+        self.lock()
+            .get(verification_id)
+            .map(|e| e.state.borrow().clone())
+    }
+
+    /// Wait up to `timeout` for a decision.
+    ///
+    /// Returns the latest known state, which is still `pending` if the timeout
+    /// elapsed first, or `None` for an unknown verification id.
+    pub async fn wait(
+        &self,
+        verification_id: &str,
+        timeout: Duration,
+    ) -> Option<VerificationResponse> {
+        let mut rx = self.lock().get(verification_id)?.state.subscribe();
+
+        let _ = tokio::time::timeout(timeout, rx.wait_for(|r| !r.is_pending())).await;
+        let latest = rx.borrow().clone();
+        Some(latest)
+    }
+
+    /// Verifications still waiting for a human, oldest first
+    pub fn list_pending(&self) -> Vec<PendingVerification> {
+        let mut pending: Vec<PendingVerification> = self
+            .lock()
+            .iter()
+            .filter(|(_, e)| e.state.borrow().is_pending())
+            .map(|(id, e)| PendingVerification {
+                verification_id: id.clone(),
+                created_at: e.created_at,
+                request: e.request.clone(),
+            })
+            .collect();
+        pending.sort_by_key(|p| p.created_at);
+        pending
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Entry>> {
+        // A poisoned lock only means another handler panicked mid-update;
+        // the map itself is still consistent.
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn prune(entries: &mut HashMap<String, Entry>) {
+        let cutoff = Utc::now() - chrono::Duration::hours(RETENTION_HOURS);
+        entries.retain(|_, e| e.state.borrow().is_pending() || e.created_at > cutoff);
+    }
+}
+
+/// Parse a long-poll duration such as `30s`, `500ms`, `2m` or a bare number of seconds.
+///
+/// The result is capped at [`MAX_WAIT`].
+pub fn parse_wait(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let duration = if let Some(ms) = value.strip_suffix("ms") {
+        Duration::from_millis(ms.trim().parse().ok()?)
+    } else if let Some(s) = value.strip_suffix('s') {
+        Duration::from_secs(s.trim().parse().ok()?)
+    } else if let Some(m) = value.strip_suffix('m') {
+        Duration::from_secs(m.trim().parse::<u64>().ok()?.checked_mul(60)?)
+    } else {
+        Duration::from_secs(value.parse().ok()?)
+    };
+    Some(duration.min(MAX_WAIT))
+}
@@ -0,0 +1,238 @@
+use crate::instruction::session::pattern_matches;
+use anyhow::{Context, Result};
+use chrono::NaiveTime;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File name of the policy inside the ChaseAI config directory (beside `network.toml`)
+pub const POLICY_FILE_NAME: &str = "policy.toml";
+
+/// Rule reported when `policy.toml` can't be loaded; it forces a human like an `ask` rule
+pub const POLICY_ERROR_RULE: &str = "policy-error";
+
+/// Characters that chain, substitute or redirect shell commands. An `allow` rule never matches
+/// an action containing one, so `ls; rm -rf ~` can't ride on a rule written for `ls`.
+pub const SHELL_METACHARACTERS: &[char] = &[';', '&', '|', '`', '$', '<', '>', '\n', '\r'];
+
+/// What happens to a request matched by a rule
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyEffect {
+    /// Approve without asking
+    Allow,
+    /// Reject without asking
+    Deny,
+    /// Always ask the human, even when a session would cover the action
+    #[default]
+    Ask,
+}
+
+/// One rule of `policy.toml`. Every condition that is set must hold for the rule to match.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct PolicyRule {
+    /// Name reported in responses; defaults to `#<position>`
+    #[serde(default)]
+    pub name: Option<String>,
+    pub effect: PolicyEffect,
+    /// Regular expression matched against the requested action; anchor it with `^...$`
+    #[serde(default)]
+    pub action: Option<String>,
+    /// Task id pattern (`*` matches anything)
+    #[serde(default)]
+    pub task_id: Option<String>,
+    /// Ports the request must arrive on; empty means any
+    #[serde(default)]
+    pub ports: Vec<u16>,
+    /// Local time window `"HH:MM-HH:MM"`; windows may wrap past midnight
+    #[serde(default)]
+    pub time: Option<String>,
+    /// Message returned to the agent when the rule decides the request
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+/// Ordered rules; the first matching rule wins
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct Policy {
+    #[serde(default)]
+    pub rules: Vec<PolicyRule>,
+}
+
+/// Request attributes a policy is evaluated against
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolicyInput<'a> {
+    pub action: &'a str,
+    pub task_id: Option<&'a str>,
+    pub port: u16,
+    pub time: NaiveTime,
+}
+
+/// Outcome of evaluating a policy
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PolicyDecision {
+    pub effect: PolicyEffect,
+    /// Matched rule, `None` when no rule matched
+    pub rule: Option<String>,
+    pub message: Option<String>,
+}
+
+impl PolicyDecision {
+    /// Decision when no rule matches: the usual session / dialog flow applies
+    pub fn unmatched() -> Self {
+        Self {
+            effect: PolicyEffect::Ask,
+            rule: None,
+            message: None,
+        }
+    }
+}
+
+impl PolicyRule {
+    /// Name of the rule at `index` (0-based) as reported to agents
+    pub fn label(&self, index: usize) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("#{}", index + 1))
+    }
+
+    fn matches(&self, input: &PolicyInput) -> bool {
+        if self.effect == PolicyEffect::Allow && input.action.contains(SHELL_METACHARACTERS) {
+            return false;
+        }
+        if let Some(pattern) = &self.action {
+            match Regex::new(pattern) {
+                Ok(re) if re.is_match(input.action) => {}
+                _ => return false,
+            }
+        }
+        if let Some(pattern) = &self.task_id {
+            match input.task_id {
+                Some(task_id) if pattern_matches(pattern, task_id) => {}
+                _ => return false,
+            }
+        }
+        if !self.ports.is_empty() && !self.ports.contains(&input.port) {
+            return false;
+        }
+        if let Some(window) = &self.time {
+            match parse_time_window(window) {
+                Some(window) if window.contains(input.time) => {}
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+impl Policy {
+    /// Check every rule so mistakes surface when the file is loaded, not when a request arrives
+    pub fn validate(&self) -> Result<()> {
+        for (index, rule) in self.rules.iter().enumerate() {
+            if let Some(pattern) = &rule.action {
+                Regex::new(pattern)
+                    .with_context(|| format!("Rule {}: invalid action regex", rule.label(index)))?;
+            }
+            if let Some(window) = &rule.time {
+                parse_time_window(window).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Rule {}: invalid time window '{}', expected HH:MM-HH:MM",
+                        rule.label(index),
+                        window
+                    )
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn evaluate(&self, input: &PolicyInput) -> PolicyDecision {
+        self.rules
+            .iter()
+            .enumerate()
+            .find(|(_, rule)| rule.matches(input))
+            .map(|(index, rule)| PolicyDecision {
+                effect: rule.effect,
+                rule: Some(rule.label(index)),
+                message: rule.message.clone(),
+            })
+            .unwrap_or_else(PolicyDecision::unmatched)
+    }
+}
+
+/// `policy.toml` on disk, re-read on every evaluation so edits apply immediately
+pub struct PolicyStore {
+    path: PathBuf,
+}
+
+impl PolicyStore {
+    pub fn with_path(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Policy stored as `policy.toml` inside `dir`
+    pub fn in_dir(dir: &Path) -> Self {
+        Self::with_path(dir.join(POLICY_FILE_NAME))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Load and validate the policy; a missing file is an empty policy
+    pub fn load(&self) -> Result<Policy> {
+        if !self.path.exists() {
+            return Ok(Policy::default());
+        }
+
+        let content = fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read policy file at {:?}", self.path))?;
+        let policy: Policy =
+            toml::from_str(&content).with_context(|| "Failed to parse policy TOML")?;
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    /// Evaluate the current policy. A broken policy file could hide a `deny` rule, so it
+    /// decides like an `ask` rule: the human is asked, even inside a session or when the
+    /// context waives verification.
+    pub fn evaluate(&self, input: &PolicyInput) -> PolicyDecision {
+        match self.load() {
+            Ok(policy) => policy.evaluate(input),
+            Err(e) => {
+                eprintln!("⚠️ Ignoring policy {:?}: {:#}", self.path, e);
+                PolicyDecision {
+                    effect: PolicyEffect::Ask,
+                    rule: Some(POLICY_ERROR_RULE.to_string()),
+                    message: Some(format!("Policy not applied: {:#}", e)),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TimeWindow {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl TimeWindow {
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            // Wraps past midnight, e.g. 22:00-06:00
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+fn parse_time_window(value: &str) -> Option<TimeWindow> {
+    let (start, end) = value.split_once('-')?;
+    Some(TimeWindow {
+        start: NaiveTime::parse_from_str(start.trim(), "%H:%M").ok()?,
+        end: NaiveTime::parse_from_str(end.trim(), "%H:%M").ok()?,
+    })
+}
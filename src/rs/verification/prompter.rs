@@ -0,0 +1,502 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// What the human is asked to decide
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Prompt {
+    pub verification_id: String,
+    /// Task and requester, e.g. `build-bot — CHASE-123`
+    pub title: String,
+    pub action: String,
+    pub reason: String,
+    /// Agent-supplied context as JSON text
+    pub context: String,
+    pub buttons: Vec<String>,
+}
+
+/// The human's answer; `button` is an index into `Prompt::buttons`, `None` if nobody chose one
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PromptAnswer {
+    pub button: Option<usize>,
+    pub message: Option<String>,
+}
+
+impl PromptAnswer {
+    pub fn chosen(button: usize) -> Self {
+        Self {
+            button: Some(button),
+            message: None,
+        }
+    }
+
+    pub fn dismissed(message: impl Into<String>) -> Self {
+        Self {
+            button: None,
+            message: Some(message.into()),
+        }
+    }
+}
+
+/// Why no human can be asked right now
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unavailable(pub String);
+
+impl std::fmt::Display for Unavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Asks a human to decide a verification. Implementations block until there is an answer,
+/// and must never pick a button on the human's behalf: without a way to reach a human they
+/// return `Unavailable`.
+pub trait Prompter: Send + Sync {
+    /// Whether a human could be asked now, without asking; reported by `/health` and the tray
+    fn check(&self) -> Result<(), Unavailable> {
+        Ok(())
+    }
+
+    fn prompt(&self, prompt: &Prompt) -> Result<PromptAnswer, Unavailable>;
+
+    /// The console queue, for prompters answered from `chase console`
+    fn console(&self) -> Option<&ConsolePrompter> {
+        None
+    }
+}
+
+/// Which prompter `network.toml` selects, under `[prompter]`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum PrompterConfig {
+    /// AppleScript dialogs on macOS, the terminal elsewhere
+    #[default]
+    Auto,
+    AppleScript,
+    Terminal,
+    /// Queue prompts for `chase console`, e.g. on a headless host reached over SSH
+    Console,
+    /// Run `program args...`, write the prompt as JSON to stdin and read the decision from stdout
+    Command {
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+impl PrompterConfig {
+    pub fn build(&self) -> Arc<dyn Prompter> {
+        match self {
+            Self::Auto if cfg!(target_os = "macos") => Arc::new(AppleScriptPrompter),
+            Self::Auto | Self::Terminal => Arc::new(TerminalPrompter::tty()),
+            Self::AppleScript => Arc::new(AppleScriptPrompter),
+            Self::Console => Arc::new(ConsolePrompter::new()),
+            Self::Command { program, args } => {
+                Arc::new(CommandPrompter::new(program.clone(), args.clone()))
+            }
+        }
+    }
+}
+
+/// Native alert through `osascript` (macOS only)
+pub struct AppleScriptPrompter;
+
+impl Prompter for AppleScriptPrompter {
+    fn check(&self) -> Result<(), Unavailable> {
+        if cfg!(target_os = "macos") {
+            Ok(())
+        } else {
+            Err(Unavailable(
+                "AppleScript dialogs are only available on macOS".to_string(),
+            ))
+        }
+    }
+
+    fn prompt(&self, prompt: &Prompt) -> Result<PromptAnswer, Unavailable> {
+        self.check()?;
+        let (button, message) = crate::ui::dialogs::show_verification_dialog(
+            &prompt.action,
+            &prompt.reason,
+            &prompt.context,
+            &prompt.buttons,
+            &prompt.title,
+        );
+        Ok(PromptAnswer {
+            button: (button < prompt.buttons.len()).then_some(button),
+            message,
+        })
+    }
+}
+
+type TerminalIo = (Box<dyn BufRead + Send>, Box<dyn Write + Send>);
+
+/// Numbered choice on a terminal. Prompts are asked one at a time.
+pub struct TerminalPrompter {
+    // `None` opens the controlling terminal for each prompt
+    io: Mutex<Option<TerminalIo>>,
+}
+
+/// Invalid answers tolerated before the prompt counts as dismissed
+const TERMINAL_ATTEMPTS: usize = 3;
+
+impl TerminalPrompter {
+    /// Ask on the controlling terminal (`/dev/tty`), so piped stdin/stdout are never read as answers
+    pub fn tty() -> Self {
+        Self {
+            io: Mutex::new(None),
+        }
+    }
+
+    pub fn with_io(
+        input: impl BufRead + Send + 'static,
+        output: impl Write + Send + 'static,
+    ) -> Self {
+        Self {
+            io: Mutex::new(Some((Box::new(input), Box::new(output)))),
+        }
+    }
+
+    fn open_tty() -> std::io::Result<TerminalIo> {
+        let tty = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")?;
+        Ok((Box::new(BufReader::new(tty.try_clone()?)), Box::new(tty)))
+    }
+
+    fn ask(
+        prompt: &Prompt,
+        input: &mut dyn BufRead,
+        output: &mut dyn Write,
+    ) -> std::io::Result<PromptAnswer> {
+        writeln!(output, "\n🚨 {} | ChaseAI", prompt.title)?;
+        writeln!(output, "Action:  {}", prompt.action)?;
+        writeln!(output, "Reason:  {}", prompt.reason)?;
+        writeln!(output, "Context: {}", prompt.context)?;
+        for (i, button) in prompt.buttons.iter().enumerate() {
+            writeln!(output, "  {}) {}", i + 1, button)?;
+        }
+
+        for _ in 0..TERMINAL_ATTEMPTS {
+            write!(output, "Choice [1-{}]: ", prompt.buttons.len())?;
+            output.flush()?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(PromptAnswer::dismissed("Terminal closed before an answer"));
+            }
+            if let Some(index) = parse_choice(line.trim(), &prompt.buttons) {
+                return Ok(PromptAnswer::chosen(index));
+            }
+            writeln!(output, "Please enter a number or a button label.")?;
+        }
+        Ok(PromptAnswer::dismissed("No valid answer on the terminal"))
+    }
+}
+
+impl Prompter for TerminalPrompter {
+    fn check(&self) -> Result<(), Unavailable> {
+        if self.io.lock().unwrap_or_else(|e| e.into_inner()).is_some() {
+            return Ok(());
+        }
+        Self::open_tty()
+            .map(|_| ())
+            .map_err(|e| Unavailable(format!("No terminal to ask on: {}", e)))
+    }
+
+    fn prompt(&self, prompt: &Prompt) -> Result<PromptAnswer, Unavailable> {
+        let mut guard = self.io.lock().unwrap_or_else(|e| e.into_inner());
+        let result = match guard.as_mut() {
+            Some((input, output)) => Self::ask(prompt, input, output),
+            None => {
+                let (mut input, mut output) = Self::open_tty()
+                    .map_err(|e| Unavailable(format!("No terminal to ask on: {}", e)))?;
+                Self::ask(prompt, &mut input, &mut output)
+            }
+        };
+        Ok(result
+            .unwrap_or_else(|e| PromptAnswer::dismissed(format!("Terminal prompt failed: {}", e))))
+    }
+}
+
+/// `1`-based number or case-insensitive label
+fn parse_choice(answer: &str, buttons: &[String]) -> Option<usize> {
+    if let Ok(number) = answer.parse::<usize>() {
+        return (1..=buttons.len()).contains(&number).then(|| number - 1);
+    }
+    buttons.iter().position(|b| b.eq_ignore_ascii_case(answer))
+}
+
+/// External program that receives the prompt as JSON on stdin and prints the chosen button label,
+/// either bare or as `{"button": "...", "message": "..."}`
+pub struct CommandPrompter {
+    program: String,
+    args: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct CommandDecision {
+    button: String,
+    message: Option<String>,
+}
+
+impl CommandPrompter {
+    pub fn new(program: impl Into<String>, args: Vec<String>) -> Self {
+        Self {
+            program: program.into(),
+            args,
+        }
+    }
+
+    fn unavailable(&self, reason: impl std::fmt::Display) -> Unavailable {
+        Unavailable(format!("Prompt command {} {}", self.program, reason))
+    }
+
+    fn run(&self, mut child: std::process::Child, prompt: &Prompt) -> anyhow::Result<PromptAnswer> {
+        if let Some(mut stdin) = child.stdin.take() {
+            serde_json::to_writer(&mut stdin, prompt)?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Ok(PromptAnswer::dismissed(format!(
+                "Prompt command exited with {}",
+                output.status
+            )));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let decision =
+            serde_json::from_str::<CommandDecision>(stdout.trim()).unwrap_or(CommandDecision {
+                button: stdout.trim().to_string(),
+                message: None,
+            });
+        Ok(match parse_choice(&decision.button, &prompt.buttons) {
+            Some(index) => PromptAnswer {
+                button: Some(index),
+                message: decision.message,
+            },
+            None => PromptAnswer::dismissed(format!(
+                "Prompt command answered an unknown button: {}",
+                decision.button
+            )),
+        })
+    }
+}
+
+impl Prompter for CommandPrompter {
+    fn check(&self) -> Result<(), Unavailable> {
+        let program = Path::new(&self.program);
+        let found = if program.components().count() > 1 {
+            program.is_file()
+        } else {
+            std::env::var_os("PATH").is_some_and(|paths| {
+                std::env::split_paths(&paths).any(|dir| dir.join(program).is_file())
+            })
+        };
+        if found {
+            Ok(())
+        } else {
+            Err(self.unavailable("was not found"))
+        }
+    }
+
+    fn prompt(&self, prompt: &Prompt) -> Result<PromptAnswer, Unavailable> {
+        let child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| self.unavailable(format_args!("could not be started: {}", e)))?;
+        Ok(self.run(child, prompt).unwrap_or_else(|e| {
+            PromptAnswer::dismissed(format!("Prompt command {} failed: {}", self.program, e))
+        }))
+    }
+}
+
+/// How long after its last poll a console still counts as attached
+pub const CONSOLE_ATTACH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a prompt waits for an answer from an attached console
+pub const CONSOLE_ANSWER_TIMEOUT: Duration = Duration::from_secs(600);
+
+// How often a waiting prompt checks that the console is still there
+const CONSOLE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Prompts waiting on the console, by verification id
+type ConsoleQueue = HashMap<String, mpsc::Sender<PromptAnswer>>;
+
+/// Parks each prompt until an operator answers it from `chase console`.
+///
+/// Only an attached console is asked. A prompt gives up as unavailable when the console
+/// detaches or leaves it unanswered for [`CONSOLE_ANSWER_TIMEOUT`], so no request is held forever.
+pub struct ConsolePrompter {
+    waiting: Mutex<ConsoleQueue>,
+    last_seen: Mutex<Option<Instant>>,
+    answer_timeout: Duration,
+}
+
+impl Default for ConsolePrompter {
+    fn default() -> Self {
+        Self {
+            waiting: Mutex::default(),
+            last_seen: Mutex::default(),
+            answer_timeout: CONSOLE_ANSWER_TIMEOUT,
+        }
+    }
+}
+
+impl ConsolePrompter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Give up on unanswered prompts after `timeout` instead of [`CONSOLE_ANSWER_TIMEOUT`]
+    pub fn with_answer_timeout(mut self, timeout: Duration) -> Self {
+        self.answer_timeout = timeout;
+        self
+    }
+
+    /// Record that a console is polling the queue
+    pub fn attach(&self) {
+        *self.last_seen.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now());
+    }
+
+    /// Whether `verification_id` is waiting for a console answer
+    pub fn is_waiting(&self, verification_id: &str) -> bool {
+        self.lock().contains_key(verification_id)
+    }
+
+    /// Hand the operator's answer to the waiting verification; `false` if it is not waiting
+    pub fn answer(&self, verification_id: &str, answer: PromptAnswer) -> bool {
+        match self.lock().remove(verification_id) {
+            Some(sender) => sender.send(answer).is_ok(),
+            None => false,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ConsoleQueue> {
+        self.waiting.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Prompter for ConsolePrompter {
+    fn check(&self) -> Result<(), Unavailable> {
+        let last_seen = *self.last_seen.lock().unwrap_or_else(|e| e.into_inner());
+        match last_seen {
+            Some(seen) if seen.elapsed() < CONSOLE_ATTACH_TIMEOUT => Ok(()),
+            _ => Err(Unavailable(
+                "No console attached; run `chase console` to answer verifications".to_string(),
+            )),
+        }
+    }
+
+    fn prompt(&self, prompt: &Prompt) -> Result<PromptAnswer, Unavailable> {
+        self.check()?;
+        let id = &prompt.verification_id;
+        let (sender, receiver) = mpsc::channel();
+        self.lock().insert(id.clone(), sender);
+
+        let deadline = Instant::now() + self.answer_timeout;
+        loop {
+            let step = deadline
+                .saturating_duration_since(Instant::now())
+                .min(CONSOLE_POLL_INTERVAL);
+            match receiver.recv_timeout(step) {
+                Ok(answer) => return Ok(answer),
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    return Ok(PromptAnswer::dismissed("Console prompt was dropped"))
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+            }
+            let gave_up = if Instant::now() >= deadline {
+                Some(Unavailable(format!(
+                    "No answer from `chase console` within {}s",
+                    self.answer_timeout.as_secs()
+                )))
+            } else {
+                self.check().err()
+            };
+            if let Some(unavailable) = gave_up {
+                self.lock().remove(id);
+                // The operator may have answered since the last wait
+                return receiver.try_recv().map_err(|_| unavailable);
+            }
+        }
+    }
+
+    fn console(&self) -> Option<&ConsolePrompter> {
+        Some(self)
+    }
+}
+
+/// Deterministic answers for tests: replies with the queued button labels in order and
+/// records every prompt it was shown
+#[derive(Default)]
+pub struct ScriptedPrompter {
+    answers: Mutex<VecDeque<String>>,
+    prompts: Mutex<Vec<Prompt>>,
+    unavailable: Option<String>,
+}
+
+impl ScriptedPrompter {
+    pub fn new<I, S>(answers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            answers: Mutex::new(answers.into_iter().map(Into::into).collect()),
+            prompts: Mutex::new(Vec::new()),
+            unavailable: None,
+        }
+    }
+
+    /// A prompter that can never reach a human, like a missing dialog backend
+    pub fn unavailable(reason: impl Into<String>) -> Self {
+        Self {
+            unavailable: Some(reason.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Prompts shown so far
+    pub fn prompts(&self) -> Vec<Prompt> {
+        self.prompts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+impl Prompter for ScriptedPrompter {
+    fn check(&self) -> Result<(), Unavailable> {
+        match &self.unavailable {
+            Some(reason) => Err(Unavailable(reason.clone())),
+            None => Ok(()),
+        }
+    }
+
+    fn prompt(&self, prompt: &Prompt) -> Result<PromptAnswer, Unavailable> {
+        self.check()?;
+        self.prompts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(prompt.clone());
+        let answer = self
+            .answers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop_front();
+        Ok(match answer {
+            Some(label) => match parse_choice(&label, &prompt.buttons) {
+                Some(index) => PromptAnswer::chosen(index),
+                None => PromptAnswer::dismissed(format!("Scripted button not offered: {}", label)),
+            },
+            None => PromptAnswer::dismissed("No scripted answer left"),
+        })
+    }
+}
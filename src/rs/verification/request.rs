@@ -0,0 +1,279 @@
+use crate::instruction::context::{ActionTypeViolation, InstructionContext};
+use crate::instruction::session::SessionScope;
+use serde::{Deserialize, Serialize};
+
+/// Status reported while a verification is still waiting for a human decision
+pub const STATUS_PENDING: &str = "pending";
+
+/// Status of a request rejected by the port's instruction context before any dialog
+pub const STATUS_FORBIDDEN: &str = "forbidden";
+
+/// Status when a human had to decide but no prompter could reach one; never an approval
+pub const STATUS_UNAVAILABLE: &str = "unavailable";
+
+/// Status when the human chose a custom button that maps to no decision; its label is in
+/// `button`. Never an approval, whatever the label says.
+pub const STATUS_NO_DECISION: &str = "no_decision";
+
+/// What clicking a button means
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ButtonDecision {
+    Approve,
+    /// Approve and open a session for the button's (or the request's) scope
+    ApproveSession,
+    Reject,
+    /// The human wants to decide later; nothing is approved
+    Defer,
+}
+
+impl ButtonDecision {
+    /// Decision of a bare string button. Labels are matched exactly (ignoring case and
+    /// surrounding spaces), so a custom button like "Don't approve" can never approve anything.
+    pub fn for_label(label: &str) -> Option<Self> {
+        match label.trim().to_lowercase().as_str() {
+            "approve" | "approve once" | "allow" | "allow once" => Some(Self::Approve),
+            "approve session" | "allow session" => Some(Self::ApproveSession),
+            "reject" | "deny" | "cancel" => Some(Self::Reject),
+            "defer" | "later" | "decide later" => Some(Self::Defer),
+            _ => None,
+        }
+    }
+
+    /// Verification status reported for this decision
+    pub fn status(self) -> &'static str {
+        match self {
+            Self::Approve => "approved",
+            Self::ApproveSession => "approved_session",
+            Self::Reject => "reject",
+            Self::Defer => "deferred",
+        }
+    }
+}
+
+/// A dialog button: a bare label (`"Approve Once"`) or `{label, decision, scope?}`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum VerificationButton {
+    Label(String),
+    Structured {
+        label: String,
+        decision: ButtonDecision,
+        /// Session scope granted by an `approve_session` button, instead of the request's
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        scope: Option<SessionScope>,
+    },
+}
+
+impl VerificationButton {
+    pub fn label(&self) -> &str {
+        match self {
+            Self::Label(label) | Self::Structured { label, .. } => label,
+        }
+    }
+
+    /// `None` for a bare label outside the strict mapping of [`ButtonDecision::for_label`]
+    pub fn decision(&self) -> Option<ButtonDecision> {
+        match self {
+            Self::Label(label) => ButtonDecision::for_label(label),
+            Self::Structured { decision, .. } => Some(*decision),
+        }
+    }
+
+    fn scope(&self) -> Option<&SessionScope> {
+        match self {
+            Self::Structured { scope, .. } => scope.as_ref(),
+            Self::Label(_) => None,
+        }
+    }
+
+    /// "Reject", "Approve Once" and "Approve Session", shown when the agent sends no buttons
+    pub fn defaults() -> Vec<Self> {
+        ["Reject", "Approve Once", "Approve Session"]
+            .into_iter()
+            .map(|label| Self::Label(label.to_string()))
+            .collect()
+    }
+}
+
+impl From<&str> for VerificationButton {
+    fn from(label: &str) -> Self {
+        Self::Label(label.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VerificationRequest {
+    pub action: String,
+    pub reason: String,
+    pub context: Option<serde_json::Value>,
+    /// Kind of action, checked against the port context's `allowed_actions`. Left out when
+    /// unset, so audit records from before it existed still match their hashes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action_type: Option<String>,
+    /// Bare labels or structured buttons; see [`VerificationButton`]
+    pub buttons: Option<Vec<VerificationButton>>,
+    pub session_id: Option<String>,
+    /// Scope proposed for the session if the human chooses "Approve Session"
+    #[serde(default)]
+    pub session_scope: Option<SessionScope>,
+    /// Return `202 Accepted` immediately and let the agent poll `GET /verify/{id}`
+    #[serde(default)]
+    pub non_blocking: bool,
+    /// Common name of the client certificate on mTLS ports. Set by the server; any value
+    /// sent by the agent is discarded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requester: Option<String>,
+}
+
+impl VerificationRequest {
+    /// Task identifier supplied by the agent in `context.task_id`
+    pub fn task_id(&self) -> Option<&str> {
+        self.context
+            .as_ref()
+            .and_then(|c| c.get("task_id"))
+            .and_then(|v| v.as_str())
+    }
+
+    /// Session scope the human would approve, with defaults taken from this request
+    pub fn proposed_session_scope(&self) -> SessionScope {
+        self.session_scope
+            .clone()
+            .unwrap_or_default()
+            .resolve(&self.action, self.task_id())
+    }
+
+    /// Buttons offered to the human, the defaults if the agent sent none
+    pub fn buttons(&self) -> Vec<VerificationButton> {
+        self.buttons
+            .clone()
+            .unwrap_or_else(VerificationButton::defaults)
+    }
+
+    /// Session scope granted by clicking `button`: its own scope, else the request's
+    pub fn session_scope_for(&self, button: &VerificationButton) -> SessionScope {
+        match button.scope() {
+            Some(scope) => scope.clone().resolve(&self.action, self.task_id()),
+            None => self.proposed_session_scope(),
+        }
+    }
+}
+
+/// Answer from `chase console` to a waiting verification, `POST /verify/{id}/answer`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ConsoleAnswer {
+    /// Index into the request's buttons
+    pub button: usize,
+    /// Comment passed on to the agent, e.g. why the action was rejected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VerificationResponse {
+    pub status: String,
+    pub verification_id: String,
+    pub message: Option<String>,
+    /// Session that approved (or was created by) this verification
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// `policy.toml` rule that decided (or forced a dialog for) this verification
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy_rule: Option<String>,
+    /// What was decided; absent while pending and when nobody decided (cancelled,
+    /// unavailable, an unmapped custom label)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decision: Option<ButtonDecision>,
+    /// Label of the button the human chose
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub button: Option<String>,
+}
+
+impl VerificationResponse {
+    pub fn pending(verification_id: &str) -> Self {
+        Self {
+            status: STATUS_PENDING.to_string(),
+            verification_id: verification_id.to_string(),
+            message: Some(format!(
+                "Waiting for human decision. Poll GET /verify/{} for the result.",
+                verification_id
+            )),
+            session_id: None,
+            policy_rule: None,
+            decision: None,
+            button: None,
+        }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.status == STATUS_PENDING
+    }
+
+    pub fn is_unavailable(&self) -> bool {
+        self.status == STATUS_UNAVAILABLE
+    }
+}
+
+/// `403 Forbidden` body for a request outside the port's instruction context
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ActionTypeRejection {
+    pub status: String,
+    pub verification_id: String,
+    pub error: ActionTypeViolation,
+    pub message: String,
+    pub action_type: Option<String>,
+    pub allowed_actions: Vec<String>,
+}
+
+impl ActionTypeRejection {
+    pub fn new(
+        verification_id: &str,
+        error: ActionTypeViolation,
+        action_type: Option<&str>,
+        context: &InstructionContext,
+    ) -> Self {
+        let allowed = context.allowed_actions.join(", ");
+        let message = match (error, action_type) {
+            (ActionTypeViolation::ActionTypeNotAllowed, Some(t)) => format!(
+                "Action type '{}' is not allowed on this port (allowed: {})",
+                t, allowed
+            ),
+            (ActionTypeViolation::ActionTypeDenied, Some(t)) => format!(
+                "Action type '{}' is denied on this port (allowed: {})",
+                t, allowed
+            ),
+            _ => format!("This port requires an action_type (allowed: {})", allowed),
+        };
+        Self {
+            status: STATUS_FORBIDDEN.to_string(),
+            verification_id: verification_id.to_string(),
+            error,
+            message,
+            action_type: action_type.map(str::to_string),
+            allowed_actions: context.allowed_actions.clone(),
+        }
+    }
+
+    /// Refusal for any request while the port's stored context fails to resolve; an unreadable
+    /// contract allows nothing rather than everything
+    pub fn unresolved(verification_id: &str, action_type: Option<&str>) -> Self {
+        Self {
+            status: STATUS_FORBIDDEN.to_string(),
+            verification_id: verification_id.to_string(),
+            error: ActionTypeViolation::ContextUnresolved,
+            message: "This port's instruction context does not resolve; fix it with \
+                      `chase context` before verifying actions"
+                .to_string(),
+            action_type: action_type.map(str::to_string),
+            allowed_actions: Vec::new(),
+        }
+    }
+}
+
+/// A new random verification id, so nobody can walk the ids of other agents' requests
+pub fn new_verification_id() -> anyhow::Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| anyhow::anyhow!("Failed to generate verification id: {}", e))?;
+    Ok(format!("v-{}", hex::encode(bytes)))
+}
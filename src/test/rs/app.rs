@@ -1,3 +1,5 @@
+use app::command::{AppCommand, CommandError, CommandOutcome};
+use app::config::change::ConfigChange;
 use app::version;
 use app::App;
 
@@ -27,24 +29,54 @@ fn test_app_initialization() {
 }
 
 #[test]
-fn test_process_menu_event_quit() {
+fn test_quit_command() {
     let mut app = App::new();
-    assert!(app.process_menu_event("quit"));
+    assert_eq!(app.execute(AppCommand::Quit), Ok(CommandOutcome::Exit));
+
+    // Through the queue, as the tray submits it
+    app.commands().submit(AppCommand::RevokeAllSessions);
+    assert!(!app.process_commands());
+    app.commands().submit(AppCommand::Quit);
+    assert!(app.process_commands());
 }
 
 #[test]
-fn test_process_menu_event_unknown() {
-    let mut app = App::new();
-    assert!(!app.process_menu_event("unknown_event"));
+fn test_menu_ids_round_trip() {
+    let commands = [
+        AppCommand::TogglePort { port: 8888 },
+        AppCommand::change(ConfigChange::SetPortRole {
+            port: 8888,
+            role: app::network::port_config::PortRole::Verification,
+        }),
+        AppCommand::SelectInterface {
+            name: "en0".to_string(),
+        },
+        AppCommand::RevokeSession {
+            session_id: "v-1".to_string(),
+        },
+        AppCommand::AddPortDialog,
+        AppCommand::RollbackContext { rev: 3 },
+        AppCommand::Quit,
+    ];
+    for command in commands {
+        assert_eq!(AppCommand::from_menu_id(&command.menu_id()), Some(command));
+    }
+    assert_eq!(AppCommand::from_menu_id("unknown_event"), None);
 }
 
 #[test]
-fn test_process_menu_event_toggle_all() {
+fn test_toggle_all_command() {
     let mut app = App::new();
-    app.process_menu_event("cmd:enable_all");
+    let result = app.execute(AppCommand::change(ConfigChange::SetAllEnabled {
+        enabled: true,
+    }));
+    assert!(matches!(result, Ok(CommandOutcome::ConfigChanged(_))));
     assert!(app.config.port_bindings.iter().all(|b| b.enabled));
 
-    app.process_menu_event("cmd:disable_all");
+    app.execute(AppCommand::change(ConfigChange::SetAllEnabled {
+        enabled: false,
+    }))
+    .unwrap();
     assert!(app.config.port_bindings.iter().all(|b| !b.enabled));
 }
 
@@ -65,9 +97,25 @@ fn test_reload_config() {
 }
 
 #[test]
-fn test_process_menu_event_interface() {
+fn test_select_interface_command() {
     let mut app = App::new();
-    app.process_menu_event("interface:lo0");
+    let loopback = app::network::interface_detector::InterfaceDetector::detect_loopback()
+        .unwrap()
+        .remove(0);
+    app.execute(AppCommand::SelectInterface {
+        name: loopback.name,
+    })
+    .unwrap();
+    assert_eq!(
+        app.execute(AppCommand::SelectInterface {
+            name: "no-such-interface".to_string()
+        }),
+        Err(CommandError::Rejected(
+            app::config::change::ConfigChangeError::UnknownInterface(
+                "no-such-interface".to_string()
+            )
+        ))
+    );
     assert_eq!(
         app.config.default_interface,
         app::network::interface_detector::InterfaceType::Loopback
@@ -75,7 +123,7 @@ fn test_process_menu_event_interface() {
 }
 
 #[test]
-fn test_process_menu_event_port_toggle() {
+fn test_toggle_port_command() {
     let mut app = App::new();
     if app.config.port_bindings.is_empty() {
         app.config
@@ -89,18 +137,19 @@ fn test_process_menu_event_port_toggle() {
                 },
                 role: app::network::port_config::PortRole::Instruction,
                 enabled: true,
+                token: None,
+                mtls: false,
             });
     }
     let port = app.config.port_bindings[0].port;
-    let id = format!("port:{}", port);
 
     let initial_enabled = app.config.port_bindings[0].enabled;
-    app.process_menu_event(&id);
+    app.execute(AppCommand::TogglePort { port }).unwrap();
     assert_eq!(app.config.port_bindings[0].enabled, !initial_enabled);
 }
 
 #[test]
-fn test_process_menu_event_remove_port() {
+fn test_remove_port_command() {
     let mut app = App::new();
     if app.config.port_bindings.is_empty() {
         app.config
@@ -114,17 +163,21 @@ fn test_process_menu_event_remove_port() {
                 },
                 role: app::network::port_config::PortRole::Instruction,
                 enabled: true,
+                token: None,
+                mtls: false,
             });
     }
     let port = app.config.port_bindings[0].port;
-    let id = format!("remove_port:{}", port);
-
-    app.process_menu_event(&id);
+    app.execute(AppCommand::change(ConfigChange::RemovePort { port }))
+        .unwrap();
     assert!(!app.config.port_bindings.iter().any(|b| b.port == port));
+    assert!(app
+        .execute(AppCommand::change(ConfigChange::RemovePort { port }))
+        .is_err());
 }
 
 #[test]
-fn test_process_menu_event_role_change() {
+fn test_port_role_command() {
     let mut app = App::new();
     if app.config.port_bindings.is_empty() {
         app.config
@@ -138,14 +191,577 @@ fn test_process_menu_event_role_change() {
                 },
                 role: app::network::port_config::PortRole::Instruction,
                 enabled: true,
+                token: None,
+                mtls: false,
             });
     }
     let port = app.config.port_bindings[0].port;
-    let id = format!("role:{}:Verification", port);
-
-    app.process_menu_event(&id);
+    app.execute(AppCommand::change(ConfigChange::SetPortRole {
+        port,
+        role: app::network::port_config::PortRole::Verification,
+    }))
+    .unwrap();
     assert_eq!(
         app.config.port_bindings[0].role,
         app::network::port_config::PortRole::Verification
     );
 }
+
+#[test]
+fn test_revoke_session_commands() {
+    use app::instruction::session::SessionScope;
+
+    let mut app = App::new();
+    let id = {
+        let mut manager = app.context_manager.lock().unwrap();
+        manager
+            .create_session(
+                "v-tray-2",
+                3000.into(),
+                SessionScope::default().resolve("pwd", None),
+            )
+            .unwrap();
+        manager
+            .create_session(
+                "v-tray-1",
+                3000.into(),
+                SessionScope::default().resolve("ls", None),
+            )
+            .unwrap()
+            .id
+            .clone()
+    };
+
+    let revoke = |id: &str| AppCommand::RevokeSession {
+        session_id: id.to_string(),
+    };
+    assert!(app.execute(revoke(&id)).is_ok());
+    assert_eq!(app.active_sessions().len(), 1);
+    assert_eq!(
+        app.execute(revoke(&id)),
+        Err(CommandError::UnknownSession(id.clone()))
+    );
+
+    assert!(app.execute(AppCommand::RevokeAllSessions).is_ok());
+    assert!(app.active_sessions().is_empty());
+}
+
+#[test]
+fn test_rollback_context_command() {
+    use app::instruction::history::ContextAuthor;
+    use app::instruction::manager::ContextManager;
+    use app::instruction::storage::ContextStorage;
+    use app::instruction::template::ContextTemplate;
+    use std::sync::{Arc, Mutex};
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
+    let mut app = App::new();
+    app.context_manager = Arc::new(Mutex::new(
+        ContextManager::new_with_storage(storage).unwrap(),
+    ));
+    for role in ["reviewer", "lead"] {
+        let base = ContextTemplate {
+            system: Some("sys".to_string()),
+            role: Some(role.to_string()),
+            ..Default::default()
+        };
+        app.context_manager
+            .lock()
+            .unwrap()
+            .set_base("review", base, ContextAuthor::Cli)
+            .unwrap();
+    }
+
+    assert!(app.execute(AppCommand::RollbackContext { rev: 1 }).is_ok());
+    assert!(matches!(
+        app.execute(AppCommand::RollbackContext { rev: 1 }),
+        Err(CommandError::Failed(_))
+    ));
+    let revisions = app.recent_revisions();
+    assert_eq!(revisions[0].author, ContextAuthor::Tray);
+    assert_eq!(revisions[0].rollback_of, Some(1));
+}
+
+fn daemon_config(port: u16) -> app::config::network_config::NetworkConfig {
+    use app::network::interface_detector::{InterfaceType, NetworkInterface};
+    use app::network::port_config::{PortBinding, PortRole};
+    use app::verification::prompter::PrompterConfig;
+
+    let mut config = app::config::network_config::NetworkConfig::new();
+    config.port_bindings = vec![PortBinding {
+        port,
+        interface: NetworkInterface {
+            name: "lo".to_string(),
+            ip_address: "127.0.0.1".parse().unwrap(),
+            interface_type: InterfaceType::Loopback,
+        },
+        role: PortRole::Verification,
+        enabled: true,
+        token: Some("chai_daemon".to_string()),
+        mtls: false,
+    }];
+    config.prompter = PrompterConfig::Console;
+    config
+}
+
+#[test]
+fn test_daemon_writes_and_removes_runtime_files() -> anyhow::Result<()> {
+    use app::daemon::{Daemon, RuntimeFiles};
+
+    let temp_dir = tempfile::tempdir()?;
+    let files = || RuntimeFiles::in_dir(temp_dir.path());
+
+    // A stale PID file from a crashed daemon doesn't block the start
+    std::fs::write(files().pid_path(), "2147483000\n")?;
+    assert_eq!(files().running_pid(), None);
+
+    let daemon = Daemon::new(daemon_config(8110))?.with_runtime_files(files());
+    let info = daemon.start()?;
+    assert_eq!(info.pid, std::process::id());
+    assert_eq!(info.ports.len(), 1);
+    assert_eq!(info.ports[0].base_url, "http://127.0.0.1:8110");
+    assert_eq!(files().read()?, Some(info));
+    assert_eq!(files().running_pid(), Some(std::process::id()));
+
+    let resp = reqwest::blocking::get("http://127.0.0.1:8110/health")?;
+    assert_eq!(resp.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+
+    daemon.stop();
+    assert!(!files().pid_path().exists());
+    assert!(!files().runtime_path().exists());
+    assert!(reqwest::blocking::get("http://127.0.0.1:8110/health").is_err());
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_daemon_refuses_to_start_twice() -> anyhow::Result<()> {
+    use app::daemon::{Daemon, RuntimeFiles};
+
+    let temp_dir = tempfile::tempdir()?;
+    let files = RuntimeFiles::in_dir(temp_dir.path());
+    // The test runner's parent stands in for a daemon that is still alive
+    std::fs::write(
+        files.pid_path(),
+        format!("{}\n", std::os::unix::process::parent_id()),
+    )?;
+
+    let daemon = Daemon::new(daemon_config(8111))?.with_runtime_files(files);
+    let err = daemon.start().unwrap_err();
+    assert!(err.to_string().contains("already running"));
+    assert!(!temp_dir.path().join("runtime.json").exists());
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_daemon_shuts_down_on_sigterm() -> anyhow::Result<()> {
+    use app::daemon::RuntimeFiles;
+    use std::time::{Duration, Instant};
+
+    let config_dir = tempfile::tempdir()?;
+    let chaseai_dir = config_dir.path().join("chaseai");
+    std::fs::create_dir_all(&chaseai_dir)?;
+    std::fs::write(
+        chaseai_dir.join("network.toml"),
+        toml::to_string(&daemon_config(8112))?,
+    )?;
+
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_chase-ai"))
+        .arg("daemon")
+        .env("CHASEAI_TEST_CONFIG_DIR", config_dir.path())
+        .stdout(std::process::Stdio::null())
+        .spawn()?;
+
+    let files = RuntimeFiles::in_dir(&chaseai_dir);
+    let started = Instant::now();
+    while files.read().ok().flatten().is_none() {
+        assert!(child.try_wait()?.is_none(), "daemon exited early");
+        assert!(
+            started.elapsed() < Duration::from_secs(20),
+            "daemon never started"
+        );
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    assert_eq!(files.running_pid(), Some(child.id()));
+
+    let pid = libc::pid_t::try_from(child.id())?;
+    assert_eq!(unsafe { libc::kill(pid, libc::SIGTERM) }, 0);
+    let status = child.wait()?;
+    assert!(status.success());
+    assert!(!files.pid_path().exists());
+    assert!(!files.runtime_path().exists());
+    Ok(())
+}
+
+#[test]
+fn test_app_applies_admin_requests() {
+    use app::config::network_config::VerificationMode;
+
+    let mut app = App::new();
+    let mode = match app.config.verification_mode {
+        VerificationMode::Port => VerificationMode::Cli,
+        VerificationMode::Cli => VerificationMode::Port,
+    };
+
+    // The request waits on a server thread until the tray's loop applies it
+    let commands = app.commands().clone();
+    let request = std::thread::spawn(move || {
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(
+                commands.send(AppCommand::change(ConfigChange::SetVerificationMode {
+                    mode,
+                })),
+            )
+    });
+    while !request.is_finished() {
+        app.process_commands();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+
+    let applied = request.join().unwrap().unwrap().unwrap();
+    assert_eq!(applied.config().unwrap().verification_mode, mode);
+    assert_eq!(app.config.verification_mode, mode);
+    assert_eq!(
+        app::config::network_config::NetworkConfig::load()
+            .unwrap()
+            .verification_mode,
+        mode
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_daemon_applies_admin_changes() -> anyhow::Result<()> {
+    use app::daemon::RuntimeFiles;
+    use app::network::port_config::{PortBinding, PortRole};
+    use std::time::{Duration, Instant};
+
+    let config_dir = tempfile::tempdir()?;
+    let chaseai_dir = config_dir.path().join("chaseai");
+    std::fs::create_dir_all(&chaseai_dir)?;
+    let mut config = daemon_config(8114);
+    config.port_bindings.push(PortBinding {
+        port: 8115,
+        enabled: false,
+        ..config.port_bindings[0].clone()
+    });
+    std::fs::write(chaseai_dir.join("network.toml"), toml::to_string(&config)?)?;
+
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_chase-ai"))
+        .args(["daemon", "--prompt-command", "false"])
+        .env("CHASEAI_TEST_CONFIG_DIR", config_dir.path())
+        .stdout(std::process::Stdio::null())
+        .spawn()?;
+
+    let files = RuntimeFiles::in_dir(&chaseai_dir);
+    let wait_for = |ports: usize| -> anyhow::Result<()> {
+        let started = Instant::now();
+        while files.read().ok().flatten().map(|i| i.ports.len()) != Some(ports) {
+            anyhow::ensure!(
+                started.elapsed() < Duration::from_secs(20),
+                "daemon never served {} port(s)",
+                ports
+            );
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        Ok(())
+    };
+    let result = wait_for(1).and_then(|()| {
+        // The daemon's admin secret, not the binding token its agents hold
+        let admin_token = app::server::auth::admin_token(&chaseai_dir)?;
+        let resp = reqwest::blocking::Client::new()
+            .patch("http://127.0.0.1:8114/admin/ports/8115")
+            .bearer_auth(&admin_token)
+            .json(&serde_json::json!({ "enabled": true, "role": "Instruction" }))
+            .send()?;
+        assert_eq!(resp.status(), reqwest::StatusCode::OK);
+        wait_for(2)?;
+
+        let saved: app::config::network_config::NetworkConfig =
+            toml::from_str(&std::fs::read_to_string(chaseai_dir.join("network.toml"))?)?;
+        let binding = saved.binding(8115).unwrap();
+        assert!(binding.enabled);
+        assert_eq!(binding.role, PortRole::Instruction);
+        // The --prompter override is not written back
+        assert_eq!(saved.prompter, config.prompter);
+        // Answers, healthy or not depending on the prompter
+        reqwest::blocking::get("http://127.0.0.1:8115/health")?;
+        Ok(())
+    });
+
+    let pid = libc::pid_t::try_from(child.id())?;
+    unsafe { libc::kill(pid, libc::SIGTERM) };
+    child.wait()?;
+    result
+}
+
+fn chase(config_dir: &std::path::Path, args: &[&str]) -> std::io::Result<std::process::Output> {
+    std::process::Command::new(env!("CARGO_BIN_EXE_chase-ai"))
+        .args(args)
+        .env("CHASEAI_TEST_CONFIG_DIR", config_dir)
+        .env("RUST_BACKTRACE", "0")
+        .output()
+}
+
+#[test]
+fn test_cli_edits_config_offline() -> anyhow::Result<()> {
+    use app::config::network_config::{NetworkConfig, VerificationMode};
+    use app::network::port_config::PortRole;
+    use app::server::admin::AdminPort;
+
+    let config_dir = tempfile::tempdir()?;
+    let chaseai_dir = config_dir.path().join("chaseai");
+    std::fs::create_dir_all(&chaseai_dir)?;
+    // Nothing listens on 9113, so the CLI falls back to network.toml
+    std::fs::write(
+        chaseai_dir.join("network.toml"),
+        toml::to_string(&daemon_config(9113))?,
+    )?;
+
+    let added = chase(
+        config_dir.path(),
+        &[
+            "ports",
+            "add",
+            "9114",
+            "--role",
+            "instruction",
+            "--disabled",
+        ],
+    )?;
+    assert!(added.status.success(), "{:?}", added);
+    assert!(String::from_utf8_lossy(&added.stdout).contains("ChaseAI is not running"));
+
+    let reserved = chase(config_dir.path(), &["ports", "add", "80"])?;
+    assert!(!reserved.status.success());
+    assert!(String::from_utf8_lossy(&reserved.stderr).contains("reserved"));
+
+    let missing = chase(config_dir.path(), &["ports", "enable", "9200"])?;
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("Port 9200 is not configured"));
+
+    assert!(chase(config_dir.path(), &["mode", "cli"])?.status.success());
+    let issued = chase(config_dir.path(), &["agents", "token", "ci"])?;
+    assert!(issued.status.success(), "{:?}", issued);
+    let token = String::from_utf8_lossy(&issued.stdout).trim().to_string();
+
+    let listed = chase(config_dir.path(), &["ports", "list", "--json"])?;
+    let ports: Vec<AdminPort> = serde_json::from_slice(&listed.stdout)?;
+    assert_eq!(
+        ports
+            .iter()
+            .map(|p| (p.port, p.enabled))
+            .collect::<Vec<_>>(),
+        vec![(9113, true), (9114, false)]
+    );
+
+    let saved: NetworkConfig =
+        toml::from_str(&std::fs::read_to_string(chaseai_dir.join("network.toml"))?)?;
+    assert_eq!(saved.verification_mode, VerificationMode::Cli);
+    let binding = saved.binding(9114).unwrap();
+    assert_eq!(binding.role, PortRole::Instruction);
+    assert!(binding.token.is_some());
+    assert_eq!(saved.agent_tokens.get("ci"), Some(&token));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cli_leaves_config_alone_while_chaseai_runs() -> anyhow::Result<()> {
+    use app::daemon::{RuntimeFiles, RuntimeInfo};
+
+    let config_dir = tempfile::tempdir()?;
+    let chaseai_dir = config_dir.path().join("chaseai");
+    std::fs::create_dir_all(&chaseai_dir)?;
+    let config = daemon_config(9113);
+    std::fs::write(chaseai_dir.join("network.toml"), toml::to_string(&config)?)?;
+
+    // The tray app records itself like the daemon; its admin port is refused here
+    let refused = [(9113, "no token".to_string())].into_iter().collect();
+    let mut info = RuntimeInfo::serving(&config, chrono::Utc::now(), refused);
+    assert!(info.ports.is_empty());
+    // The test runner's parent stands in for an instance that is still alive
+    info.pid = std::os::unix::process::parent_id();
+    RuntimeFiles::in_dir(&chaseai_dir).write(&info)?;
+
+    let removed = chase(config_dir.path(), &["ports", "remove", "9113"])?;
+    assert!(!removed.status.success());
+    assert!(String::from_utf8_lossy(&removed.stderr).contains("is running"));
+    let saved: app::config::network_config::NetworkConfig =
+        toml::from_str(&std::fs::read_to_string(chaseai_dir.join("network.toml"))?)?;
+    assert!(saved.binding(9113).is_some());
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cli_edits_running_daemon() -> anyhow::Result<()> {
+    use app::daemon::RuntimeFiles;
+    use app::network::port_config::PortBinding;
+    use std::time::{Duration, Instant};
+
+    let config_dir = tempfile::tempdir()?;
+    let chaseai_dir = config_dir.path().join("chaseai");
+    std::fs::create_dir_all(&chaseai_dir)?;
+    let mut config = daemon_config(8116);
+    config.port_bindings.push(PortBinding {
+        port: 8117,
+        enabled: false,
+        ..config.port_bindings[0].clone()
+    });
+    std::fs::write(chaseai_dir.join("network.toml"), toml::to_string(&config)?)?;
+
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_chase-ai"))
+        .args(["daemon", "--prompt-command", "false"])
+        .env("CHASEAI_TEST_CONFIG_DIR", config_dir.path())
+        .stdout(std::process::Stdio::null())
+        .spawn()?;
+
+    let files = RuntimeFiles::in_dir(&chaseai_dir);
+    let wait_for = |ports: usize| -> anyhow::Result<()> {
+        let started = Instant::now();
+        while files.read().ok().flatten().map(|i| i.ports.len()) != Some(ports) {
+            anyhow::ensure!(
+                started.elapsed() < Duration::from_secs(20),
+                "daemon never served {} port(s)",
+                ports
+            );
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        Ok(())
+    };
+    let result = wait_for(1).and_then(|()| {
+        let enabled = chase(config_dir.path(), &["ports", "enable", "8117"])?;
+        assert!(enabled.status.success(), "{:?}", enabled);
+        assert!(String::from_utf8_lossy(&enabled.stdout).contains("the running instance"));
+        wait_for(2)?;
+
+        // Rejected by the instance with the same message as offline
+        let reserved = chase(config_dir.path(), &["ports", "add", "80"])?;
+        assert!(String::from_utf8_lossy(&reserved.stderr).contains("reserved"));
+        Ok(())
+    });
+
+    let pid = libc::pid_t::try_from(child.id())?;
+    unsafe { libc::kill(pid, libc::SIGTERM) };
+    child.wait()?;
+    result
+}
+
+#[test]
+fn test_cli_sets_context_from_any_format() -> anyhow::Result<()> {
+    use app::instruction::context::InstructionContext;
+    use app::instruction::storage::ContextStorage;
+
+    let config_dir = tempfile::tempdir()?;
+    let chaseai_dir = config_dir.path().join("chaseai");
+    std::fs::create_dir_all(&chaseai_dir)?;
+    std::fs::write(
+        chaseai_dir.join("network.toml"),
+        toml::to_string(&daemon_config(9113))?,
+    )?;
+    let yaml = config_dir.path().join("context.yml");
+    std::fs::write(
+        &yaml,
+        "system: ChaseAI\nrole: execution-agent\nbase_instruction: Ask first\n\
+         allowed_actions: [deploy]\nverification_required: true\n",
+    )?;
+    let invalid = config_dir.path().join("invalid.toml");
+    std::fs::write(
+        &invalid,
+        "system = \"\"\nrole = \"\"\nbase_instruction = \"x\"\n\
+         allowed_actions = [\"deploy\"]\nverification_required = false\n",
+    )?;
+
+    let set = chase(
+        config_dir.path(),
+        &["context", "set", "9113", yaml.to_str().unwrap()],
+    )?;
+    assert!(set.status.success(), "{:?}", set);
+
+    // Both problems are listed, and nothing is saved
+    let rejected = chase(
+        config_dir.path(),
+        &["context", "set", "9113", invalid.to_str().unwrap()],
+    )?;
+    assert!(!rejected.status.success());
+    let stderr = String::from_utf8_lossy(&rejected.stderr);
+    assert!(
+        stderr.contains("system: ") && stderr.contains("role: "),
+        "{}",
+        stderr
+    );
+
+    let get = chase(
+        config_dir.path(),
+        &["context", "get", "9113", "--format", "toml"],
+    )?;
+    let served: InstructionContext = toml::from_str(&String::from_utf8_lossy(&get.stdout))?;
+    assert_eq!(served.system, "ChaseAI");
+    let stored = ContextStorage::with_path(chaseai_dir.join("contexts.json")).load_all()?;
+    assert_eq!(stored.contexts.get(&9113.into()), Some(&served.into()));
+
+    assert!(chase(config_dir.path(), &["context", "delete", "9113"])?
+        .status
+        .success());
+    assert!(!chase(config_dir.path(), &["context", "delete", "9113"])?
+        .status
+        .success());
+
+    // A port context that extends a base resolves over it
+    assert!(chase(
+        config_dir.path(),
+        &["context", "base", "set", "ops", yaml.to_str().unwrap()]
+    )?
+    .status
+    .success());
+    let port = config_dir.path().join("port.json");
+    std::fs::write(
+        &port,
+        r#"{"extends": "ops", "role": "release-agent", "allowed_actions": ["rollback"]}"#,
+    )?;
+    let set = chase(
+        config_dir.path(),
+        &["context", "set", "9113", port.to_str().unwrap()],
+    )?;
+    assert!(set.status.success(), "{:?}", set);
+    let ci = config_dir.path().join("ci.json");
+    std::fs::write(&ci, r#"{"extends": "ops", "role": "ci-agent"}"#)?;
+    assert!(chase(
+        config_dir.path(),
+        &[
+            "context",
+            "set",
+            "9113",
+            ci.to_str().unwrap(),
+            "--agent",
+            "ci"
+        ]
+    )?
+    .status
+    .success());
+    for (agent, role) in [("ci", "ci-agent"), ("other", "release-agent")] {
+        let show = chase(
+            config_dir.path(),
+            &["context", "show", "9113", "--agent", agent, "--resolved"],
+        )?;
+        let resolved: InstructionContext = serde_json::from_slice(&show.stdout)?;
+        assert_eq!(resolved.role, role);
+    }
+    let show = chase(
+        config_dir.path(),
+        &["context", "show", "9113", "--resolved"],
+    )?;
+    let resolved: InstructionContext = serde_json::from_slice(&show.stdout)?;
+    assert_eq!(resolved.system, "ChaseAI");
+    assert_eq!(resolved.role, "release-agent");
+    assert_eq!(resolved.allowed_actions, vec!["deploy", "rollback"]);
+    assert!(
+        !chase(config_dir.path(), &["context", "base", "delete", "ops"])?
+            .status
+            .success()
+    );
+    Ok(())
+}
@@ -71,6 +71,16 @@ fn test_real_save_load() -> Result<()> {
     let loaded = NetworkConfig::load()?;
     assert_eq!(loaded.port_bindings[0].port, 7777);
 
+    // Saved whole under its final name, and only this user can read the tokens in it
+    let path = NetworkConfig::config_path()?;
+    assert!(!path.with_extension("toml.tmp").exists());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path)?.permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
     std::env::set_var(
         "CHASEAI_TEST_CONFIG_DIR",
         temp_dir.path().join("non_existent"),
@@ -114,3 +124,116 @@ fn test_verification_mode_documentation() {
     let rule = ConfigurationGenerator::generate_agent_rule(&config).unwrap();
     assert!(rule.contains("chase --verification"));
 }
+
+#[test]
+fn test_tokens_are_generated_but_never_exported() -> Result<()> {
+    let mut config = NetworkConfig::new();
+    assert!(config.ensure_tokens()?);
+    assert!(!config.ensure_tokens()?);
+
+    let tokens: Vec<&str> = config
+        .port_bindings
+        .iter()
+        .map(|b| b.token.as_deref().unwrap())
+        .collect();
+    assert!(tokens
+        .iter()
+        .all(|t| t.starts_with("chai_") && t.len() == 69));
+    let first = tokens[0].to_string();
+    assert!(!config
+        .port_bindings
+        .iter()
+        .skip(1)
+        .any(|b| b.token.as_deref() == Some(&first)));
+
+    let json = ConfigurationGenerator::generate_json(&config)?;
+    let port = config.port_bindings[0].port;
+    assert_eq!(json["ports"][0]["auth"]["type"], "bearer");
+    assert_eq!(
+        json["ports"][0]["auth"]["token_command"],
+        format!("chase token --port {}", port)
+    );
+    for output in [
+        json.to_string(),
+        ConfigurationGenerator::generate_markdown(&config)?,
+        ConfigurationGenerator::generate_agent_rule(&config)?,
+    ] {
+        assert!(!output.contains(&first));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_lan_ports_advertise_https_and_ca_fingerprint() -> Result<()> {
+    use app::network::interface_detector::{InterfaceType, NetworkInterface};
+    use app::server::tls::TlsStore;
+
+    let mut config = NetworkConfig::new();
+    let json = ConfigurationGenerator::generate_json(&config)?;
+    assert!(json.get("tls").is_none());
+    assert!(json["ports"][0]["base_url"]
+        .as_str()
+        .unwrap()
+        .starts_with("http://"));
+
+    config.port_bindings[0].interface = NetworkInterface {
+        name: "en0".to_string(),
+        ip_address: "192.168.1.20".parse()?,
+        interface_type: InterfaceType::Lan,
+    };
+    let json = ConfigurationGenerator::generate_json(&config)?;
+    let port = config.port_bindings[0].port;
+    assert_eq!(
+        json["ports"][0]["base_url"],
+        format!("https://192.168.1.20:{}", port)
+    );
+    assert_eq!(json["ports"][0]["tls"], true);
+    assert_eq!(
+        json["tls"]["ca_fingerprint_sha256"],
+        TlsStore::new()?.ca_fingerprint()?
+    );
+
+    let markdown = ConfigurationGenerator::generate_markdown(&config)?;
+    assert!(markdown.contains(&format!("`https://192.168.1.20:{}`", port)));
+    Ok(())
+}
+
+#[test]
+fn test_config_changes_are_checked() {
+    use app::config::change::{ConfigChange, ConfigChangeError};
+    use app::network::port_config::PortRole;
+
+    let mut config = NetworkConfig::new();
+    let port = config.port_bindings[0].port;
+
+    let missing = ConfigChange::SetPortEnabled {
+        port: 1,
+        enabled: true,
+    };
+    assert_eq!(
+        config.apply(&missing),
+        Err(ConfigChangeError::UnknownPort(1))
+    );
+    assert_eq!(
+        config.apply(&ConfigChange::RemovePort { port: 1 }),
+        Err(ConfigChangeError::UnknownPort(1))
+    );
+
+    let binding = config.new_binding(port, PortRole::Instruction, true);
+    assert!(binding.token.is_some());
+    assert_eq!(
+        config.apply(&ConfigChange::AddPort { binding }),
+        Err(ConfigChangeError::PortExists(port))
+    );
+
+    let binding = config.new_binding(port + 1, PortRole::Instruction, false);
+    config.apply(&ConfigChange::AddPort { binding }).unwrap();
+    config
+        .apply(&ConfigChange::SetAllEnabled { enabled: true })
+        .unwrap();
+    assert!(config.binding(port + 1).unwrap().enabled);
+    config
+        .apply(&ConfigChange::RemovePort { port: port + 1 })
+        .unwrap();
+    assert!(config.binding(port + 1).is_none());
+}
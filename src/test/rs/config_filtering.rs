@@ -7,6 +7,10 @@ fn create_test_config() -> NetworkConfig {
     NetworkConfig {
         default_interface: InterfaceType::Loopback,
         verification_mode: app::config::network_config::VerificationMode::Port,
+        allow_insecure_public: false,
+        prompter: Default::default(),
+        context_variables: Default::default(),
+        agent_tokens: Default::default(),
         port_bindings: vec![
             PortBinding {
                 port: 8090,
@@ -17,6 +21,8 @@ fn create_test_config() -> NetworkConfig {
                 },
                 role: PortRole::Instruction,
                 enabled: true,
+                token: None,
+                mtls: false,
             },
             PortBinding {
                 port: 8091,
@@ -27,6 +33,8 @@ fn create_test_config() -> NetworkConfig {
                 },
                 role: PortRole::Verification,
                 enabled: true,
+                token: None,
+                mtls: false,
             },
             PortBinding {
                 port: 8092,
@@ -37,6 +45,8 @@ fn create_test_config() -> NetworkConfig {
                 },
                 role: PortRole::Verification,
                 enabled: false, // Disabled port
+                token: None,
+                mtls: false,
             },
         ],
     }
@@ -1,8 +1,13 @@
 use anyhow::Result;
 use app::config::network_config::NetworkConfig;
-use app::instruction::context::InstructionContext;
+use app::instruction::actions::ActionPattern;
+use app::instruction::context::{ActionTypeViolation, ContextErrors, InstructionContext};
+use app::instruction::history::{diff, ContextAuthor, RevisionTarget};
 use app::instruction::manager::ContextManager;
-use app::instruction::storage::ContextStorage;
+use app::instruction::session::{pattern_matches, SessionScope};
+use app::instruction::storage::{ContextKey, ContextStorage, StoredContexts};
+use app::instruction::template::ContextTemplate;
+use app::instruction::variables::ContextVariables;
 use app::network::interface_detector::{InterfaceType, NetworkInterface};
 use app::network::port_config::{PortBinding, PortRole};
 use std::collections::HashMap;
@@ -18,6 +23,8 @@ fn create_test_config() -> NetworkConfig {
         },
         role: PortRole::Instruction,
         enabled: true,
+        token: None,
+        mtls: false,
     });
     config
 }
@@ -42,23 +49,251 @@ fn test_context_lifecycle() -> Result<()> {
 
     let context = create_test_context();
 
-    manager.set_context(3000, context.clone(), &config)?;
-    assert_eq!(manager.get_context(3000), Some(&context));
+    manager.set_context(3000, context.clone(), &config, ContextAuthor::Api)?;
+    assert_eq!(manager.get_context(3000), Some(context.clone()));
 
     let list = manager.list_contexts();
     assert_eq!(list.len(), 1);
 
     let mut new_context = context.clone();
     new_context.role = "updated".to_string();
-    manager.set_context(3000, new_context.clone(), &config)?;
+    manager.set_context(3000, new_context.clone(), &config, ContextAuthor::Api)?;
     assert_eq!(manager.get_context(3000).unwrap().role, "updated");
 
-    manager.delete_context(3000)?;
+    manager.delete_context(3000, ContextAuthor::Api)?;
     assert!(manager.get_context(3000).is_none());
 
     Ok(())
 }
 
+#[test]
+fn test_context_history_and_rollback() -> Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
+    let mut manager = ContextManager::new_with_storage(storage)?;
+    let config = create_test_config();
+
+    let context = create_test_context();
+    let mut updated = context.clone();
+    updated.role = "reviewer".to_string();
+    updated.allowed_actions.push("deploy".to_string());
+
+    manager.set_context(3000, context.clone(), &config, ContextAuthor::Api)?;
+    // The same context again is not a new revision
+    assert!(manager
+        .set_context(3000, context.clone(), &config, ContextAuthor::Api)?
+        .is_none());
+    let rev = manager
+        .set_context(3000, updated.clone(), &config, ContextAuthor::Cli)?
+        .unwrap();
+    assert_eq!((rev.rev, rev.author), (2, ContextAuthor::Cli));
+
+    let fields: Vec<String> = diff(Some(&context), Some(&updated))
+        .into_iter()
+        .map(|c| c.field)
+        .collect();
+    assert_eq!(fields, vec!["allowed_actions", "role"]);
+
+    let rolled_back = manager.rollback(1, ContextAuthor::Api)?.unwrap();
+    assert_eq!((rolled_back.rev, rolled_back.rollback_of), (3, Some(1)));
+    assert_eq!(manager.get_context(3000), Some(context.clone()));
+    assert!(manager.rollback(1, ContextAuthor::Api)?.is_none());
+    assert!(manager.rollback(99, ContextAuthor::Api).is_err());
+
+    manager.delete_context(3000, ContextAuthor::Api)?;
+    // History survives a restart, and a damaged line, and rolls back a deletion too
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(temp_dir.path().join("context_history.jsonl"))?;
+    std::io::Write::write_all(&mut file, b"{\"rev\":\n")?;
+    let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
+    let mut manager = ContextManager::new_with_storage(storage)?;
+    let history = manager.history(Some(3000))?;
+    assert_eq!(history.len(), 4);
+    assert!(history[3].context.is_none());
+    assert!(manager.history(Some(3001))?.is_empty());
+    let rolled_back = manager.rollback(2, ContextAuthor::Cli)?.unwrap();
+    assert_eq!(rolled_back.rev, 5);
+    assert_eq!(manager.get_context(3000), Some(updated));
+    Ok(())
+}
+
+#[test]
+fn test_template_resolves_over_its_bases() {
+    let mut bases = HashMap::new();
+    bases.insert(
+        "agent".to_string(),
+        ContextTemplate {
+            system: Some("ChaseAI".to_string()),
+            role: Some("agent".to_string()),
+            base_instruction: Some("Ask first".to_string()),
+            allowed_actions: vec!["read".to_string()],
+            ..Default::default()
+        },
+    );
+    bases.insert(
+        "deployer".to_string(),
+        ContextTemplate {
+            extends: Some("agent".to_string()),
+            role: Some("deployer".to_string()),
+            allowed_actions: vec!["deploy".to_string(), "read".to_string()],
+            verification_required: Some(false),
+            ..Default::default()
+        },
+    );
+
+    let port = ContextTemplate {
+        extends: Some("deployer".to_string()),
+        allowed_actions: vec!["rollback".to_string()],
+        verification_required: Some(true),
+        ..Default::default()
+    };
+    let resolved = port.resolve(&bases, &ContextVariables::default()).unwrap();
+    assert_eq!(resolved.system, "ChaseAI");
+    assert_eq!(resolved.role, "deployer");
+    assert_eq!(resolved.allowed_actions, vec!["read", "deploy", "rollback"]);
+    assert!(resolved.verification_required);
+
+    // Unset all the way down, verification stays on
+    let plain = ContextTemplate {
+        extends: Some("agent".to_string()),
+        ..Default::default()
+    };
+    assert!(
+        plain
+            .resolve(&bases, &ContextVariables::default())
+            .unwrap()
+            .verification_required
+    );
+
+    let missing = ContextTemplate {
+        extends: Some("nope".to_string()),
+        ..Default::default()
+    };
+    let ContextErrors(errors) = missing
+        .resolve(&bases, &ContextVariables::default())
+        .unwrap_err();
+    assert_eq!(errors[0].field, "extends");
+
+    bases.get_mut("agent").unwrap().extends = Some("deployer".to_string());
+    let ContextErrors(errors) = port
+        .resolve(&bases, &ContextVariables::default())
+        .unwrap_err();
+    assert!(errors[0].message.contains("deployer -> agent -> deployer"));
+}
+
+#[test]
+fn test_base_changes_are_checked_against_ports() -> Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
+    let mut manager = ContextManager::new_with_storage(storage)?;
+    let config = create_test_config();
+
+    let base = ContextTemplate::from(create_test_context());
+    manager.set_base("reviewer", base.clone(), ContextAuthor::Api)?;
+    assert!(manager
+        .set_base("Not A Name", base.clone(), ContextAuthor::Api)
+        .is_err());
+
+    let port = ContextTemplate {
+        extends: Some("reviewer".to_string()),
+        role: Some("senior-reviewer".to_string()),
+        ..Default::default()
+    };
+    manager.set_context(3000, port.clone(), &config, ContextAuthor::Api)?;
+    assert_eq!(manager.template(3000), Some(&port));
+    assert_eq!(
+        manager.get_context(3000).map(|c| c.role),
+        Some("senior-reviewer".to_string())
+    );
+
+    // Emptying the base would leave port 3000 without a system
+    let err = manager
+        .set_base("reviewer", ContextTemplate::default(), ContextAuthor::Api)
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("Context of port 3000: "),
+        "{}",
+        err
+    );
+    assert!(manager.delete_base("reviewer", ContextAuthor::Api).is_err());
+    assert_eq!(manager.base_users("reviewer"), vec!["port 3000"]);
+
+    manager.delete_context(3000, ContextAuthor::Api)?;
+    assert!(manager.delete_base("reviewer", ContextAuthor::Cli)?);
+    assert!(!manager.delete_base("reviewer", ContextAuthor::Cli)?);
+
+    // Base changes are revisions too, kept out of the port's history
+    let base_revisions: Vec<_> = manager
+        .history(None)?
+        .into_iter()
+        .filter(|r| r.target() == RevisionTarget::Base("reviewer".to_string()))
+        .collect();
+    assert_eq!(base_revisions.len(), 2);
+    assert_eq!(base_revisions[0].context, Some(base.clone()));
+    assert_eq!(base_revisions[1].context, None);
+    assert!(manager
+        .history(Some(3000))?
+        .iter()
+        .all(|r| r.base.is_none()));
+
+    manager.rollback(base_revisions[0].rev, ContextAuthor::Cli)?;
+    assert_eq!(manager.list_bases(), vec![("reviewer", &base)]);
+    Ok(())
+}
+
+#[test]
+fn test_base_instruction_placeholders_render_per_port() -> Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
+    let mut manager = ContextManager::new_with_storage(storage)?;
+    let mut config = create_test_config();
+    config
+        .context_variables
+        .insert("team".to_string(), "infra".to_string());
+
+    let mut context = create_test_context();
+    context.base_instruction =
+        "Port {{port}} for {{ team }}: verify at {{verify_url}} first".to_string();
+    manager.set_context(3000, context.clone(), &config, ContextAuthor::Api)?;
+    assert_eq!(
+        manager.get_context(3000).unwrap().base_instruction,
+        "Port 3000 for infra: verify at http://127.0.0.1:9999/verify first"
+    );
+    // The stored template keeps its placeholders
+    assert_eq!(
+        manager
+            .template(3000)
+            .and_then(|t| t.base_instruction.clone()),
+        Some(context.base_instruction)
+    );
+
+    let variables = ContextVariables::from_config(&config);
+    let mut unknown = create_test_context();
+    unknown.base_instruction = "Hello {{owner}}, see {{team}}".to_string();
+    let errors = unknown.errors(&variables);
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].message.contains("'{{owner}}'"));
+    // ChaseAI's own directory is no agent's project; only a configured one is known
+    unknown.base_instruction = "Work in {{project_dir}}".to_string();
+    assert_eq!(unknown.errors(&variables).len(), 1);
+    config
+        .context_variables
+        .insert("project_dir".to_string(), "/src/app".to_string());
+    assert!(unknown
+        .errors(&ContextVariables::from_config(&config))
+        .is_empty());
+    unknown.base_instruction = "Hello {{team".to_string();
+    assert!(manager
+        .set_context(3000, unknown, &config, ContextAuthor::Api)
+        .is_err());
+
+    // Without the custom variable the context is no longer served
+    manager.set_variables(ContextVariables::default());
+    assert!(manager.get_context(3000).is_none());
+    Ok(())
+}
+
 #[test]
 fn test_invalid_port() -> Result<()> {
     let temp_dir = tempfile::tempdir()?;
@@ -68,7 +303,7 @@ fn test_invalid_port() -> Result<()> {
 
     let context = create_test_context();
 
-    let result = manager.set_context(4000, context, &config);
+    let result = manager.set_context(4000, context, &config, ContextAuthor::Api);
     assert!(result.is_err());
     Ok(())
 }
@@ -89,15 +324,99 @@ fn test_save_load_storage() -> Result<()> {
     let config_path = temp_dir.path().join("contexts.json");
     let storage = ContextStorage::with_path(config_path.clone());
 
-    let mut contexts = HashMap::new();
-    let context = create_test_context();
-    contexts.insert(3000, context);
+    let mut stored = StoredContexts::default();
+    stored
+        .contexts
+        .insert(3000.into(), create_test_context().into());
+    stored.contexts.insert(
+        ContextKey::agent(3000, "ci"),
+        ContextTemplate {
+            extends: Some("reviewer".to_string()),
+            ..Default::default()
+        },
+    );
+    stored.bases.insert(
+        "reviewer".to_string(),
+        ContextTemplate {
+            role: Some("reviewer".to_string()),
+            ..Default::default()
+        },
+    );
 
-    storage.save_all(&contexts)?;
+    storage.save_all(&stored)?;
     assert!(config_path.exists());
 
-    let loaded = storage.load_all()?;
-    assert_eq!(loaded.len(), 1);
+    assert_eq!(storage.load_all()?, stored);
+    Ok(())
+}
+
+#[test]
+fn test_storage_loads_contexts_saved_before_agents() -> Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let config_path = temp_dir.path().join("contexts.json");
+    std::fs::write(
+        &config_path,
+        r#"{"contexts":[{"port":3000,"context":{"system":"sys","role":"role",
+            "base_instruction":"inst","allowed_actions":["action"],"verification_required":false}}]}"#,
+    )?;
+
+    let loaded = ContextStorage::with_path(config_path).load_all()?;
+    assert_eq!(
+        loaded.contexts.get(&3000.into()),
+        Some(&create_test_context().into())
+    );
+    Ok(())
+}
+
+#[test]
+fn test_agent_contexts_fall_back_to_the_port() -> Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
+    let mut manager = ContextManager::new_with_storage(storage)?;
+    let config = create_test_config();
+
+    let port = create_test_context();
+    let mut ci = create_test_context();
+    ci.role = "ci-agent".to_string();
+    manager.set_context(3000, port.clone(), &config, ContextAuthor::Api)?;
+    let revision = manager
+        .set_context(
+            ContextKey::agent(3000, "ci"),
+            ci.clone(),
+            &config,
+            ContextAuthor::Cli,
+        )?
+        .unwrap();
+    assert_eq!(
+        revision.target(),
+        RevisionTarget::Context(ContextKey::agent(3000, "ci"))
+    );
+
+    assert_eq!(manager.get_context(ContextKey::agent(3000, "ci")), Some(ci));
+    assert_eq!(
+        manager.get_context(ContextKey::agent(3000, "other")),
+        Some(port.clone())
+    );
+    assert_eq!(manager.template(ContextKey::agent(3000, "other")), None);
+
+    // Rolling back the agent's first revision leaves the port's context alone
+    manager.delete_context(ContextKey::agent(3000, "ci"), ContextAuthor::Cli)?;
+    assert_eq!(
+        manager.get_context(ContextKey::agent(3000, "ci")),
+        Some(port.clone())
+    );
+    manager.rollback(revision.rev, ContextAuthor::Cli)?;
+    assert_eq!(
+        manager
+            .get_context(ContextKey::agent(3000, "ci"))
+            .map(|c| c.role),
+        Some("ci-agent".to_string())
+    );
+    assert_eq!(manager.get_context(3000), Some(port));
+
+    manager.set_agent_tokens([("ci".to_string(), "chai_ci".to_string())].into());
+    assert_eq!(manager.agent_for_token("chai_ci"), Some("ci"));
+    assert_eq!(manager.agent_for_token("chai_other"), None);
     Ok(())
 }
 
@@ -124,3 +443,205 @@ fn test_empty_system_fails() {
     );
     assert!(ctx.is_err());
 }
+
+#[test]
+fn test_validate_reports_every_error() {
+    let ctx = InstructionContext {
+        system: " ".to_string(),
+        role: "role".to_string(),
+        base_instruction: String::new(),
+        allowed_actions: vec!["ok".to_string(), "Bad".to_string()],
+        verification_required: false,
+    };
+    let fields: Vec<String> = ctx
+        .errors(&ContextVariables::default())
+        .into_iter()
+        .map(|e| e.field)
+        .collect();
+    assert_eq!(
+        fields,
+        vec!["system", "base_instruction", "allowed_actions"]
+    );
+
+    let err = ctx.validate(&ContextVariables::default()).unwrap_err();
+    let errors = err.downcast_ref::<ContextErrors>().unwrap();
+    assert_eq!(errors.0.len(), 3);
+    assert!(err
+        .to_string()
+        .contains("; Base instruction cannot be empty;"));
+}
+
+#[test]
+fn test_session_scope_defaults_to_request() {
+    let scope = SessionScope::default().resolve("git push origin main", Some("CHASE-7"));
+    assert_eq!(scope.actions, vec!["git push origin main".to_string()]);
+    assert_eq!(scope.task_id.as_deref(), Some("CHASE-7"));
+    assert_eq!(scope.ttl(), chrono::Duration::minutes(60));
+}
+
+#[test]
+fn test_session_pattern_matching() {
+    assert!(pattern_matches("rm -rf build/*", "rm -rf build/cache"));
+    assert!(!pattern_matches("rm -rf build/*", "rm -rf /"));
+    assert!(pattern_matches("cargo test", "cargo test"));
+    assert!(!pattern_matches("cargo test", "cargo test; rm -rf ~"));
+}
+
+#[test]
+fn test_session_auto_approval_respects_scope() -> Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
+    let mut manager = ContextManager::new_with_storage(storage)?;
+
+    let scope = SessionScope {
+        actions: vec!["npm run *".to_string()],
+        max_uses: Some(2),
+        ..Default::default()
+    }
+    .resolve("npm run build", Some("CHASE-1"));
+    let ci = ContextKey::agent(3000, "ci");
+    let id = manager.create_session("v-1", ci.clone(), scope)?.id.clone();
+    // The verification id is not the session's credential
+    assert_ne!(id, "v-1");
+    assert!(manager
+        .use_session("v-1", &ci, "npm run test", Some("CHASE-1"))
+        .is_err());
+
+    assert!(manager
+        .use_session(&id, &ci, "npm run test", Some("CHASE-1"))
+        .is_ok());
+    // Wrong task, wrong action and unknown sessions are never approved
+    assert!(manager
+        .use_session(&id, &ci, "npm run test", Some("CHASE-2"))
+        .is_err());
+    assert!(manager
+        .use_session(&id, &ci, "rm -rf node_modules", Some("CHASE-1"))
+        .is_err());
+    assert!(manager
+        .use_session("v-missing", &ci, "npm run test", Some("CHASE-1"))
+        .is_err());
+    // Only the port and agent the session was approved for may use it
+    for other in [ContextKey::from(3000), ContextKey::agent(3001, "ci")] {
+        assert!(manager
+            .use_session(&id, &other, "npm run test", Some("CHASE-1"))
+            .is_err());
+    }
+
+    // Second use exhausts the session
+    let session = manager.use_session(&id, &ci, "npm run lint", Some("CHASE-1"))?;
+    assert_eq!(session.uses, 2);
+    assert!(manager
+        .use_session(&id, &ci, "npm run lint", Some("CHASE-1"))
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn test_expired_session_is_removed() -> Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
+    let mut manager = ContextManager::new_with_storage(storage)?;
+
+    let scope = SessionScope::default().resolve("ls", None);
+    let id = manager
+        .create_session("v-2", 3000.into(), scope)?
+        .id
+        .clone();
+    manager.sessions.get_mut(&id).unwrap().expires_at = chrono::Utc::now();
+
+    assert!(manager.use_session(&id, &3000.into(), "ls", None).is_err());
+    assert!(manager.sessions.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_check_action_type() {
+    let context = create_test_context();
+    assert_eq!(context.check_action_type(Some("action")), Ok(()));
+    assert_eq!(
+        context.check_action_type(Some("other")),
+        Err(ActionTypeViolation::ActionTypeNotAllowed)
+    );
+    assert_eq!(
+        context.check_action_type(None),
+        Err(ActionTypeViolation::ActionTypeRequired)
+    );
+}
+
+#[test]
+fn test_namespaced_wildcard_and_denied_actions() {
+    let mut context = create_test_context();
+    context.allowed_actions = vec![
+        "fs.*".to_string(),
+        "!fs.delete".to_string(),
+        "net.http.get".to_string(),
+    ];
+    assert!(context.validate(&ContextVariables::default()).is_ok());
+
+    assert_eq!(context.check_action_type(Some("fs.write")), Ok(()));
+    assert_eq!(context.check_action_type(Some("fs.write.tmp")), Ok(()));
+    assert_eq!(context.check_action_type(Some("net.http.get")), Ok(()));
+    // `fs.*` covers what is under `fs`, not `fs` itself
+    assert_eq!(
+        context.check_action_type(Some("fs")),
+        Err(ActionTypeViolation::ActionTypeNotAllowed)
+    );
+    assert_eq!(
+        context.check_action_type(Some("net.http.post")),
+        Err(ActionTypeViolation::ActionTypeNotAllowed)
+    );
+    // A deny wins over the grant that covers it
+    assert_eq!(
+        context.check_action_type(Some("fs.delete")),
+        Err(ActionTypeViolation::ActionTypeDenied)
+    );
+    assert!(!context.permits("fs.delete"));
+    assert!(context.permits("fs.read"));
+
+    context.allowed_actions = vec!["*".to_string(), "!net.*".to_string()];
+    assert!(context.permits("anything"));
+    assert_eq!(
+        context.check_action_type(Some("net.http.get")),
+        Err(ActionTypeViolation::ActionTypeDenied)
+    );
+}
+
+#[test]
+fn test_action_pattern_errors() {
+    for (pattern, message) in [
+        ("fs..write", "Empty segment at position 4 in 'fs..write'"),
+        ("fs.Write", "Segment 'Write' at position 4 in 'fs.Write'"),
+        (
+            "fs.*.x",
+            "'*' at position 4 in 'fs.*.x' must be the last segment",
+        ),
+        (
+            "fs.wr*",
+            "'*' at position 6 in 'fs.wr*' must be a whole segment",
+        ),
+        (
+            "fs.write!",
+            "Invalid character '!' at position 9 in 'fs.write!'",
+        ),
+        ("!", "'!' must be followed by an action name or '*'"),
+    ] {
+        let err = ActionPattern::parse(pattern).unwrap_err();
+        assert!(err.starts_with(message), "{}: {}", pattern, err);
+    }
+
+    let mut context = create_test_context();
+    context.allowed_actions = vec!["fs..write".to_string()];
+    let errors = context.errors(&ContextVariables::default());
+    assert!(
+        errors
+            .iter()
+            .any(|e| e.field == "allowed_actions"
+                && e.message.contains("Empty segment at position 4"))
+    );
+
+    context.allowed_actions = vec!["!fs.delete".to_string()];
+    let errors = context.errors(&ContextVariables::default());
+    assert!(errors
+        .iter()
+        .any(|e| e.message == "Allowed actions only deny; grant at least one action"));
+}
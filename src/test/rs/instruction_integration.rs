@@ -1,5 +1,6 @@
 use app::config::network_config::NetworkConfig;
 use app::instruction::context::InstructionContext;
+use app::instruction::history::ContextAuthor;
 use app::instruction::manager::ContextManager;
 use app::instruction::storage::ContextStorage;
 use app::network::interface_detector::{InterfaceType, NetworkInterface};
@@ -25,6 +26,8 @@ async fn test_full_instruction_flow() -> anyhow::Result<()> {
         },
         role: PortRole::Instruction,
         enabled: true,
+        token: None,
+        mtls: false,
     });
 
     // 3. Setup Context Manager & Persistence
@@ -43,7 +46,7 @@ async fn test_full_instruction_flow() -> anyhow::Result<()> {
     manager
         .lock()
         .unwrap()
-        .set_context(port, context.clone(), &net_config)?;
+        .set_context(port, context.clone(), &net_config, ContextAuthor::Api)?;
 
     // 5. Start Server Pool
     let mut pool = ServerPool::new(manager.clone());
@@ -90,6 +93,8 @@ async fn test_multiple_instruction_servers() -> anyhow::Result<()> {
         },
         role: PortRole::Instruction,
         enabled: true,
+        token: None,
+        mtls: false,
     });
 
     net_config.port_bindings.push(PortBinding {
@@ -101,6 +106,8 @@ async fn test_multiple_instruction_servers() -> anyhow::Result<()> {
         },
         role: PortRole::Instruction,
         enabled: true,
+        token: None,
+        mtls: false,
     });
 
     // 3. Setup Context Manager & Persistence
@@ -123,14 +130,18 @@ async fn test_multiple_instruction_servers() -> anyhow::Result<()> {
         true,
     )?;
 
-    manager
-        .lock()
-        .unwrap()
-        .set_context(port1, context1.clone(), &net_config)?;
-    manager
-        .lock()
-        .unwrap()
-        .set_context(port2, context2.clone(), &net_config)?;
+    manager.lock().unwrap().set_context(
+        port1,
+        context1.clone(),
+        &net_config,
+        ContextAuthor::Api,
+    )?;
+    manager.lock().unwrap().set_context(
+        port2,
+        context2.clone(),
+        &net_config,
+        ContextAuthor::Api,
+    )?;
 
     // 5. Start Server Pool
     let mut pool = ServerPool::new(manager.clone());
@@ -182,6 +193,8 @@ async fn test_context_update_reflection() -> anyhow::Result<()> {
         },
         role: PortRole::Instruction,
         enabled: true,
+        token: None,
+        mtls: false,
     });
 
     // 3. Setup Context Manager & Persistence
@@ -200,7 +213,7 @@ async fn test_context_update_reflection() -> anyhow::Result<()> {
     manager
         .lock()
         .unwrap()
-        .set_context(port, context1.clone(), &net_config)?;
+        .set_context(port, context1.clone(), &net_config, ContextAuthor::Api)?;
 
     // 5. Start Server Pool
     let mut pool = ServerPool::new(manager.clone());
@@ -230,7 +243,7 @@ async fn test_context_update_reflection() -> anyhow::Result<()> {
     manager
         .lock()
         .unwrap()
-        .set_context(port, context2.clone(), &net_config)?;
+        .set_context(port, context2.clone(), &net_config, ContextAuthor::Api)?;
 
     // 8. Verify update is reflected
     let resp2 = client
@@ -63,6 +63,8 @@ fn test_add_binding() {
         interface: mock_interface(),
         role: PortRole::Instruction,
         enabled: true,
+        token: None,
+        mtls: false,
     };
     config.add_binding(binding).unwrap();
     assert!(config.get_binding(3000).is_some());
@@ -83,6 +85,8 @@ fn test_remove_binding() {
         interface: mock_interface(),
         role: PortRole::Instruction,
         enabled: true,
+        token: None,
+        mtls: false,
     };
     config.add_binding(binding).unwrap();
     assert!(config.remove_binding(3000).is_ok());
@@ -98,6 +102,8 @@ fn test_list_bindings() {
         interface: mock_interface(),
         role: PortRole::Instruction,
         enabled: true,
+        token: None,
+        mtls: false,
     };
     config.add_binding(binding).unwrap();
     let bindings = config.list_bindings();
@@ -113,6 +119,8 @@ fn test_duplicate_binding() {
         interface: mock_interface(),
         role: PortRole::Instruction,
         enabled: true,
+        token: None,
+        mtls: false,
     };
     config.add_binding(binding.clone()).unwrap();
     assert!(config.add_binding(binding).is_err());
@@ -15,6 +15,8 @@ fn test_detect_configure_persist_load_flow() -> anyhow::Result<()> {
         interface: interfaces[0].clone(),
         role: PortRole::Instruction,
         enabled: true,
+        token: None,
+        mtls: false,
     };
     port_config.add_binding(binding.clone())?;
 
@@ -53,6 +55,8 @@ fn test_network_config_persistence_multiple() -> anyhow::Result<()> {
         interface: iface.clone(),
         role: PortRole::Instruction,
         enabled: true,
+        token: None,
+        mtls: false,
     });
 
     network_config.port_bindings.push(PortBinding {
@@ -60,6 +64,8 @@ fn test_network_config_persistence_multiple() -> anyhow::Result<()> {
         interface: iface.clone(),
         role: PortRole::Verification,
         enabled: false,
+        token: None,
+        mtls: false,
     });
 
     // Serialize and deserialize
@@ -1,16 +1,21 @@
 use app::config::network_config::NetworkConfig;
 use app::instruction::context::InstructionContext;
+use app::instruction::history::{ContextAuthor, ContextRevision};
 use app::instruction::manager::ContextManager;
+use app::instruction::session::SessionScope;
 use app::instruction::storage::ContextStorage;
 use app::network::interface_detector::{InterfaceType, NetworkInterface};
 use app::network::port_config::{PortBinding, PortRole};
 use app::server::instruction_server::InstructionServer;
 use app::server::pool::ServerPool;
+use app::server::{admin, auth};
+use app::verification::prompter::{ConsolePrompter, ScriptedPrompter};
 use axum::http::StatusCode;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
 
-async fn create_test_pool() -> (ServerPool, Arc<Mutex<ContextManager>>) {
-    let temp_dir = tempfile::tempdir().unwrap();
+async fn create_test_pool(
+    temp_dir: &tempfile::TempDir,
+) -> (ServerPool, Arc<Mutex<ContextManager>>) {
     let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
     let context_manager = Arc::new(Mutex::new(
         ContextManager::new_with_storage(storage).unwrap(),
@@ -18,6 +23,17 @@ async fn create_test_pool() -> (ServerPool, Arc<Mutex<ContextManager>>) {
     (ServerPool::new(context_manager.clone()), context_manager)
 }
 
+/// Send `network.toml` saves from `configure` to a temp dir shared by this binary's tests,
+/// never the user's own config directory
+fn isolate_config_dir() {
+    static CONFIG_DIR: OnceLock<tempfile::TempDir> = OnceLock::new();
+    CONFIG_DIR.get_or_init(|| {
+        let dir = tempfile::tempdir().unwrap();
+        std::env::set_var("CHASEAI_TEST_CONFIG_DIR", dir.path());
+        dir
+    });
+}
+
 fn create_test_config(port: u16, enabled: bool) -> NetworkConfig {
     let mut config = NetworkConfig::new();
     config.port_bindings.clear();
@@ -30,13 +46,16 @@ fn create_test_config(port: u16, enabled: bool) -> NetworkConfig {
         },
         role: PortRole::Instruction,
         enabled,
+        token: None,
+        mtls: false,
     });
     config
 }
 
 #[tokio::test]
 async fn test_pool_update_start_stop() {
-    let (mut pool, _) = create_test_pool().await;
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (mut pool, _) = create_test_pool(&temp_dir).await;
 
     // Start server
     let config_on = create_test_config(3011, true);
@@ -50,9 +69,46 @@ async fn test_pool_update_start_stop() {
     assert_eq!(pool.server_count(), 0);
 }
 
+#[tokio::test]
+async fn test_pool_refuses_unauthenticated_public_bindings() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (mut pool, _) = create_test_pool(&temp_dir).await;
+    let mut config = create_test_config(3013, true);
+    config.port_bindings[0].interface = NetworkInterface {
+        name: "any".to_string(),
+        ip_address: "0.0.0.0".parse().unwrap(),
+        interface_type: InterfaceType::Public,
+    };
+
+    pool.update(&config).await.unwrap();
+    assert_eq!(pool.server_count(), 0);
+    assert!(pool.refused_bindings()[&3013].contains("no token"));
+
+    // A token makes the binding acceptable
+    config.port_bindings[0].token = Some("chai_test".to_string());
+    pool.update(&config).await.unwrap();
+    assert!(pool.has_server(3013));
+    assert!(pool.refused_bindings().is_empty());
+
+    // Dropping the token stops the running server again
+    config.port_bindings[0].token = None;
+    pool.update(&config).await.unwrap();
+    assert_eq!(pool.server_count(), 0);
+    assert!(pool.refused_bindings().contains_key(&3013));
+
+    // ...unless the user explicitly accepts the risk
+    config.allow_insecure_public = true;
+    pool.update(&config).await.unwrap();
+    assert!(pool.has_server(3013));
+    assert!(pool.refused_bindings().is_empty());
+
+    pool.shutdown().await;
+}
+
 #[tokio::test]
 async fn test_pool_shutdown() {
-    let (mut pool, _) = create_test_pool().await;
+    let temp_dir = tempfile::tempdir().unwrap();
+    let (mut pool, _) = create_test_pool(&temp_dir).await;
     let config = create_test_config(3012, true);
     pool.update(&config).await.unwrap();
     assert_eq!(pool.server_count(), 1);
@@ -91,15 +147,18 @@ async fn test_server_startup_and_request() {
         interface: interface.clone(),
         role: PortRole::Instruction,
         enabled: true,
+        token: None,
+        mtls: false,
     });
 
     manager
         .lock()
         .unwrap()
-        .set_context(port, context, &config)
+        .set_context(port, context, &config, ContextAuthor::Api)
         .unwrap();
 
-    let server = InstructionServer::new(port, interface, manager.clone());
+    let server = InstructionServer::new(port, interface, manager.clone())
+        .with_prompter(Arc::new(ScriptedPrompter::default()));
     server.start().await.unwrap();
 
     tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
@@ -145,6 +204,8 @@ async fn test_config_endpoint_json() {
         interface: interface.clone(),
         role: PortRole::Instruction,
         enabled: true,
+        token: None,
+        mtls: false,
     });
 
     let network_config = Arc::new(Mutex::new(config));
@@ -184,3 +245,1910 @@ async fn test_config_endpoint_json() {
 
     server.stop().await.unwrap();
 }
+
+#[tokio::test]
+async fn test_non_blocking_verification_can_be_polled() {
+    use app::verification::pending::PendingVerifications;
+
+    let (port, other_port) = (8097, 8121);
+    let interface = NetworkInterface {
+        name: "lo".to_string(),
+        ip_address: "127.0.0.1".parse().unwrap(),
+        interface_type: InterfaceType::Loopback,
+    };
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
+    let manager = Arc::new(Mutex::new(
+        ContextManager::new_with_storage(storage).unwrap(),
+    ));
+
+    let pending = Arc::new(PendingVerifications::new());
+    let server = InstructionServer::new(port, interface.clone(), manager.clone())
+        .with_pending(pending.clone())
+        .with_prompter(Arc::new(ScriptedPrompter::new(["Approve Once"])));
+    server.start().await.unwrap();
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+    let client = reqwest::Client::new();
+    let resp = client
+        .post(format!("http://127.0.0.1:{}/verify", port))
+        .json(&serde_json::json!({
+            "action": "rm -rf build/",
+            "reason": "cleanup",
+            "non_blocking": true
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["status"], "pending");
+    let id = body["verification_id"].as_str().unwrap().to_string();
+
+    let resp = client
+        .get(format!("http://127.0.0.1:{}/verify/{}?wait=5s", port, id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["verification_id"], id.as_str());
+    assert_eq!(body["status"], "approved");
+
+    let resp = client
+        .get(format!("http://127.0.0.1:{}/verify/v-unknown", port))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    // Another agent, or the same id on another port, can't see the decision
+    manager
+        .lock()
+        .unwrap()
+        .set_agent_tokens([("ci".to_string(), "chai_ci".to_string())].into());
+    let resp = client
+        .get(format!("http://127.0.0.1:{}/verify/{}", port, id))
+        .header(auth::AGENT_TOKEN_HEADER, "chai_ci")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let other = InstructionServer::new(other_port, interface, manager).with_pending(pending);
+    other.start().await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    let resp = client
+        .get(format!("http://127.0.0.1:{}/verify/{}", other_port, id))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    other.stop().await.unwrap();
+
+    let resp = client
+        .get(format!(
+            "http://127.0.0.1:{}/verify/{}?wait=later",
+            port, id
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_verification_session_scope() {
+    let port = 8098;
+    let interface = NetworkInterface {
+        name: "lo".to_string(),
+        ip_address: "127.0.0.1".parse().unwrap(),
+        interface_type: InterfaceType::Loopback,
+    };
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
+    let manager = Arc::new(Mutex::new(
+        ContextManager::new_with_storage(storage).unwrap(),
+    ));
+
+    let scope = SessionScope {
+        actions: vec!["cargo *".to_string()],
+        ..Default::default()
+    }
+    .resolve("cargo build", Some("CHASE-9"));
+    let session_id = manager
+        .lock()
+        .unwrap()
+        .create_session("v-approving", port.into(), scope)
+        .unwrap()
+        .id
+        .clone();
+    assert!(session_id.starts_with("s-"));
+
+    let prompter = Arc::new(ScriptedPrompter::new(["Reject", "Reject", "Reject"]));
+    let server = InstructionServer::new(port, interface, manager.clone())
+        .with_prompter(prompter.clone())
+        .with_admin_token(Some("chai_admin"));
+    server.start().await.unwrap();
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+    let client = reqwest::Client::new();
+    let resp = client
+        .post(format!("http://127.0.0.1:{}/verify", port))
+        .json(&serde_json::json!({
+            "action": "cargo test",
+            "reason": "run tests",
+            "session_id": session_id,
+            "context": { "task_id": "CHASE-9" }
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["status"], "approved");
+    assert_eq!(body["session_id"], session_id.as_str());
+
+    // Outside the approved scope the session does not apply
+    let resp = client
+        .post(format!("http://127.0.0.1:{}/verify", port))
+        .json(&serde_json::json!({
+            "action": "rm -rf target",
+            "reason": "cleanup",
+            "session_id": session_id,
+            "context": { "task_id": "CHASE-9" }
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["status"], "reject");
+    assert_eq!(prompter.prompts().len(), 1);
+
+    // Another agent on the same port can't use a session it wasn't approved for
+    manager
+        .lock()
+        .unwrap()
+        .set_agent_tokens([("ci".to_string(), "chai_ci".to_string())].into());
+    let resp = client
+        .post(format!("http://127.0.0.1:{}/verify", port))
+        .header(auth::AGENT_TOKEN_HEADER, "chai_ci")
+        .json(&serde_json::json!({
+            "action": "cargo test",
+            "reason": "run tests",
+            "session_id": session_id,
+            "context": { "task_id": "CHASE-9" }
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["status"], "reject");
+    assert_eq!(prompter.prompts().len(), 2);
+
+    // A policy.toml that doesn't parse could be hiding a deny rule, so the session is not enough
+    std::fs::write(temp_dir.path().join("policy.toml"), "[[rules]\n").unwrap();
+    let resp = client
+        .post(format!("http://127.0.0.1:{}/verify", port))
+        .json(&serde_json::json!({
+            "action": "cargo test",
+            "reason": "run tests",
+            "session_id": session_id,
+            "context": { "task_id": "CHASE-9" }
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["status"], "reject");
+    assert_eq!(body["policy_rule"], "policy-error");
+    assert_eq!(prompter.prompts().len(), 3);
+    assert_eq!(manager.lock().unwrap().sessions[&session_id].uses, 1);
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_session_management_endpoints() {
+    let port = 8099;
+    let interface = NetworkInterface {
+        name: "lo".to_string(),
+        ip_address: "127.0.0.1".parse().unwrap(),
+        interface_type: InterfaceType::Loopback,
+    };
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
+    let manager = Arc::new(Mutex::new(
+        ContextManager::new_with_storage(storage).unwrap(),
+    ));
+    let id = {
+        let mut mgr = manager.lock().unwrap();
+        let id = mgr
+            .create_session(
+                "v-a",
+                port.into(),
+                SessionScope::default().resolve("ls", None),
+            )
+            .unwrap()
+            .id
+            .clone();
+        mgr.create_session(
+            "v-b",
+            port.into(),
+            SessionScope::default().resolve("pwd", None),
+        )
+        .unwrap();
+        mgr.create_session(
+            "v-c",
+            port.into(),
+            SessionScope::default().resolve("whoami", None),
+        )
+        .unwrap();
+        id
+    };
+
+    let server = InstructionServer::new(port, interface, manager.clone())
+        .with_token(Some("chai_port"))
+        .with_admin_token(Some("chai_admin"));
+    server.start().await.unwrap();
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+    let client = reqwest::Client::new();
+    let base = format!("http://127.0.0.1:{}", port);
+
+    // Session ids let an agent skip the human, so agents never see the list
+    let resp = client
+        .get(format!("{}/sessions", base))
+        .bearer_auth("chai_port")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let sessions: Vec<serde_json::Value> = client
+        .get(format!("{}/sessions", base))
+        .bearer_auth("chai_admin")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(sessions.len(), 3);
+    assert!(sessions.iter().any(|s| s["verification_id"] == "v-a"));
+    assert!(sessions.iter().all(|s| s["owner"]["port"] == port));
+
+    for status in [StatusCode::NO_CONTENT, StatusCode::NOT_FOUND] {
+        let resp = client
+            .delete(format!("{}/sessions/{}", base, id))
+            .bearer_auth("chai_admin")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), status);
+    }
+
+    let body: serde_json::Value = client
+        .delete(format!("{}/sessions", base))
+        .bearer_auth("chai_admin")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["revoked"], 2);
+    assert!(manager.lock().unwrap().list_sessions().is_empty());
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_verification_is_audited() {
+    let port = 8100;
+    let interface = NetworkInterface {
+        name: "lo".to_string(),
+        ip_address: "127.0.0.1".parse().unwrap(),
+        interface_type: InterfaceType::Loopback,
+    };
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
+    let manager = Arc::new(Mutex::new(
+        ContextManager::new_with_storage(storage).unwrap(),
+    ));
+
+    let server = InstructionServer::new(port, interface, manager)
+        .with_admin_token(Some("chai_admin"))
+        .with_prompter(Arc::new(ScriptedPrompter::new(["Reject"])));
+    server.start().await.unwrap();
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+    let client = reqwest::Client::new();
+    let base = format!("http://127.0.0.1:{}", port);
+    let body: serde_json::Value = client
+        .post(format!("{}/verify", base))
+        .json(&serde_json::json!({
+            "action": "drop table users",
+            "reason": "audit test",
+            "context": { "task_id": "AUDIT-1" }
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let id = body["verification_id"].as_str().unwrap();
+
+    let records: Vec<serde_json::Value> = client
+        .get(format!("{}/audit?task_id=AUDIT-1&action=TABLE", base))
+        .bearer_auth("chai_admin")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["verification_id"], id);
+    assert_eq!(records[0]["port"], port);
+    assert_eq!(records[0]["status"], body["status"]);
+    assert!(records[0]["client_addr"]
+        .as_str()
+        .unwrap()
+        .starts_with("127.0.0.1:"));
+    assert!(temp_dir.path().join("audit.jsonl").exists());
+
+    let anchor: serde_json::Value = client
+        .get(format!("{}/audit/anchor", base))
+        .bearer_auth("chai_admin")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(anchor["records"], 1);
+    assert_eq!(anchor["head"], records[0]["hash"]);
+
+    let resp = client
+        .get(format!("{}/audit?from=yesterday", base))
+        .bearer_auth("chai_admin")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_policy_decides_before_dialog() {
+    let port = 8101;
+    let interface = NetworkInterface {
+        name: "lo".to_string(),
+        ip_address: "127.0.0.1".parse().unwrap(),
+        interface_type: InterfaceType::Loopback,
+    };
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        temp_dir.path().join("policy.toml"),
+        r#"
+[[rules]]
+name = "no-root-wipe"
+effect = "deny"
+action = "^rm -rf /$"
+message = "Never wipe the root filesystem"
+
+[[rules]]
+name = "read-only"
+effect = "allow"
+action = "^(ls|cat) "
+ports = [8101]
+"#,
+    )
+    .unwrap();
+    let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
+    let manager = Arc::new(Mutex::new(
+        ContextManager::new_with_storage(storage).unwrap(),
+    ));
+
+    let prompter = Arc::new(ScriptedPrompter::new(["Approve Once"]));
+    let server = InstructionServer::new(port, interface, manager).with_prompter(prompter.clone());
+    server.start().await.unwrap();
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+    let client = reqwest::Client::new();
+    let base = format!("http://127.0.0.1:{}", port);
+    let verify = |action: &str| {
+        client
+            .post(format!("{}/verify", base))
+            .json(&serde_json::json!({ "action": action, "reason": "policy test" }))
+            .send()
+    };
+
+    let body: serde_json::Value = verify("rm -rf /").await.unwrap().json().await.unwrap();
+    assert_eq!(body["status"], "reject");
+    assert_eq!(body["policy_rule"], "no-root-wipe");
+    assert_eq!(body["message"], "Never wipe the root filesystem");
+
+    let body: serde_json::Value = verify("ls -la").await.unwrap().json().await.unwrap();
+    assert_eq!(body["status"], "approved");
+    assert_eq!(body["policy_rule"], "read-only");
+
+    // Unmatched actions go to the dialog and carry no rule
+    let body: serde_json::Value = verify("git push").await.unwrap().json().await.unwrap();
+    assert_eq!(body["status"], "approved");
+    assert!(body.get("policy_rule").is_none());
+    assert_eq!(prompter.prompts().len(), 1);
+    assert_eq!(prompter.prompts()[0].action, "git push");
+
+    // Dry run on another port does not match the port-restricted rule
+    let decision: serde_json::Value = client
+        .get(format!("{}/policy/evaluate?action=cat%20x&port=9999", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(decision["effect"], "ask");
+    assert!(decision["rule"].is_null());
+
+    let decision: serde_json::Value = client
+        .get(format!("{}/policy/evaluate?action=cat%20x", base))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(decision["effect"], "allow");
+    assert_eq!(decision["rule"], "read-only");
+
+    let resp = client
+        .get(format!("{}/policy/evaluate?action=ls&time=noon", base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_verify_enforces_port_context() {
+    let port = 8102;
+    let interface = NetworkInterface {
+        name: "lo".to_string(),
+        ip_address: "127.0.0.1".parse().unwrap(),
+        interface_type: InterfaceType::Loopback,
+    };
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
+    let manager = Arc::new(Mutex::new(
+        ContextManager::new_with_storage(storage).unwrap(),
+    ));
+
+    let mut config = NetworkConfig::new();
+    config.port_bindings.push(PortBinding {
+        port,
+        interface: interface.clone(),
+        role: PortRole::Verification,
+        enabled: true,
+        token: None,
+        mtls: false,
+    });
+    let mut context = InstructionContext::new(
+        "sys".to_string(),
+        "execution-agent".to_string(),
+        "inst".to_string(),
+        vec!["read-file".to_string(), "run-tests".to_string()],
+        true,
+    )
+    .unwrap();
+    manager
+        .lock()
+        .unwrap()
+        .set_context(port, context.clone(), &config, ContextAuthor::Api)
+        .unwrap();
+
+    let server = InstructionServer::new(port, interface, manager.clone())
+        .with_admin_token(Some("chai_admin"))
+        .with_prompter(Arc::new(ScriptedPrompter::new(["Reject"])));
+    server.start().await.unwrap();
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+    let client = reqwest::Client::new();
+    let base = format!("http://127.0.0.1:{}", port);
+    let verify = |action_type: Option<&str>| {
+        let mut body = serde_json::json!({ "action": "cargo test", "reason": "contract test" });
+        if let Some(action_type) = action_type {
+            body["action_type"] = action_type.into();
+        }
+        client.post(format!("{}/verify", base)).json(&body).send()
+    };
+
+    let resp = verify(None).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["status"], "forbidden");
+    assert_eq!(body["error"], "action_type_required");
+
+    let resp = verify(Some("delete-repo")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "action_type_not_allowed");
+    assert_eq!(body["action_type"], "delete-repo");
+    assert_eq!(
+        body["allowed_actions"],
+        serde_json::json!(["read-file", "run-tests"])
+    );
+
+    // Allowed, but the context still requires a human
+    let resp = verify(Some("run-tests")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["status"], "reject");
+
+    context.verification_required = false;
+    manager
+        .lock()
+        .unwrap()
+        .set_context(port, context, &config, ContextAuthor::Api)
+        .unwrap();
+    let body: serde_json::Value = verify(Some("run-tests"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["status"], "approved");
+
+    // Waiving verification does not widen the contract
+    let resp = verify(Some("delete-repo")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let records: Vec<serde_json::Value> = client
+        .get(format!("{}/audit?status=forbidden", base))
+        .bearer_auth("chai_admin")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(records.len(), 3);
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_verify_fails_closed_on_unresolved_context() {
+    let port = 8120;
+    let interface = NetworkInterface {
+        name: "lo".to_string(),
+        ip_address: "127.0.0.1".parse().unwrap(),
+        interface_type: InterfaceType::Loopback,
+    };
+
+    // Its base was removed by hand, so the stored context no longer resolves
+    let temp_dir = tempfile::tempdir().unwrap();
+    let contexts_path = temp_dir.path().join("contexts.json");
+    std::fs::write(
+        &contexts_path,
+        r#"{"contexts":[{"port":8120,"context":{"extends":"gone","allowed_actions":["read-file"]}}]}"#,
+    )
+    .unwrap();
+    let storage = ContextStorage::with_path(contexts_path);
+    let manager = Arc::new(Mutex::new(
+        ContextManager::new_with_storage(storage).unwrap(),
+    ));
+
+    let server = InstructionServer::new(port, interface, manager)
+        .with_prompter(Arc::new(ScriptedPrompter::new(["Approve"])));
+    server.start().await.unwrap();
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+    let client = reqwest::Client::new();
+    let base = format!("http://127.0.0.1:{}", port);
+    let resp = client
+        .post(format!("{}/verify", base))
+        .json(&serde_json::json!({
+            "action": "cat README.md",
+            "action_type": "read-file",
+            "reason": "unresolved context test"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "context_unresolved");
+
+    let resp = client
+        .get(format!("{}/context", base))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_bearer_token_required_except_health() {
+    let port = 8103;
+    let interface = NetworkInterface {
+        name: "lo".to_string(),
+        ip_address: "127.0.0.1".parse().unwrap(),
+        interface_type: InterfaceType::Loopback,
+    };
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
+    let manager = Arc::new(Mutex::new(
+        ContextManager::new_with_storage(storage).unwrap(),
+    ));
+
+    let server = InstructionServer::new(port, interface, manager)
+        .with_token(Some("chai_secret"))
+        .with_prompter(Arc::new(ScriptedPrompter::default()));
+    server.start().await.unwrap();
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+    let client = reqwest::Client::new();
+    let base = format!("http://127.0.0.1:{}", port);
+
+    let resp = client.get(format!("{}/health", base)).send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    for path in ["/sessions", "/audit", "/config", "/verify/v-1"] {
+        let resp = client
+            .get(format!("{}{}", base, path))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED, "{}", path);
+    }
+
+    let resp = client
+        .post(format!("{}/verify", base))
+        .bearer_auth("chai_wrong")
+        .json(&serde_json::json!({ "action": "ls", "reason": "auth test" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(resp.headers()["www-authenticate"], "Bearer");
+
+    let resp = client
+        .get(format!("{}/config", base))
+        .bearer_auth("chai_secret")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_lan_binding_is_served_over_tls() {
+    use app::server::tls::TlsStore;
+
+    let port = 8104;
+    let interface = NetworkInterface {
+        name: "lo".to_string(),
+        ip_address: "127.0.0.1".parse().unwrap(),
+        interface_type: InterfaceType::Lan,
+    };
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
+    let manager = Arc::new(Mutex::new(
+        ContextManager::new_with_storage(storage).unwrap(),
+    ));
+
+    let store = TlsStore::in_dir(temp_dir.path().join("tls"));
+    let tls = store.server_config(interface.ip_address, false).unwrap();
+    let server = InstructionServer::new(port, interface, manager)
+        .with_tls(Some(tls))
+        .with_prompter(Arc::new(ScriptedPrompter::default()));
+    server.start().await.unwrap();
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+    let url = format!("https://127.0.0.1:{}/health", port);
+
+    // The local CA is not trusted by default
+    assert!(reqwest::get(&url).await.is_err());
+
+    let ca = reqwest::Certificate::from_pem(store.ca_cert_pem().unwrap().as_bytes()).unwrap();
+    let client = reqwest::Client::builder()
+        .add_root_certificate(ca)
+        .build()
+        .unwrap();
+    let resp = client.get(&url).send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Plain HTTP is not served on the TLS port
+    let plain = format!("http://127.0.0.1:{}/health", port);
+    assert!(reqwest::get(&plain)
+        .await
+        .map_or(true, |r| !r.status().is_success()));
+
+    server.stop().await.unwrap();
+}
+
+#[test]
+fn test_tls_store_persists_local_ca() {
+    use app::server::tls::TlsStore;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let store = TlsStore::in_dir(temp_dir.path());
+    let fingerprint = store.ca_fingerprint().unwrap();
+    assert_eq!(fingerprint.split(':').count(), 32);
+
+    let ip = "192.168.1.20".parse().unwrap();
+    store.server_config(ip, false).unwrap();
+    let host_cert = std::fs::read(temp_dir.path().join("hosts/192.168.1.20.crt")).unwrap();
+
+    // A second store over the same directory reuses the CA and host certificate
+    let reloaded = TlsStore::in_dir(temp_dir.path());
+    assert_eq!(reloaded.ca_fingerprint().unwrap(), fingerprint);
+    reloaded.server_config(ip, false).unwrap();
+    assert_eq!(
+        std::fs::read(temp_dir.path().join("hosts/192.168.1.20.crt")).unwrap(),
+        host_cert
+    );
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        for key in ["ca.key", "hosts/192.168.1.20.key"] {
+            let mode = std::fs::metadata(temp_dir.path().join(key))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600, "{}", key);
+        }
+
+        // Reissuing replaces a key whose mode was loosened instead of writing into it
+        let agent = store.issue_agent("build-bot").unwrap();
+        std::fs::set_permissions(&agent.key_path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let agent = store.issue_agent("build-bot").unwrap();
+        let mode = std::fs::metadata(&agent.key_path)
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(!agent.key_path.with_extension("key.tmp").exists());
+    }
+}
+
+#[tokio::test]
+async fn test_mtls_refuses_revoked_certificates() {
+    use app::server::tls::{AgentCredentials, TlsStore};
+
+    let port = 8122;
+    let interface = NetworkInterface {
+        name: "lo".to_string(),
+        ip_address: "127.0.0.1".parse().unwrap(),
+        interface_type: InterfaceType::Loopback,
+    };
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
+    let manager = Arc::new(Mutex::new(
+        ContextManager::new_with_storage(storage).unwrap(),
+    ));
+
+    let store = TlsStore::in_dir(temp_dir.path().join("tls"));
+    let ca = reqwest::Certificate::from_pem(store.ca_cert_pem().unwrap().as_bytes()).unwrap();
+    let client_for = |agent: AgentCredentials| {
+        let identity = reqwest::Identity::from_pkcs8_pem(
+            &std::fs::read(&agent.cert_path).unwrap(),
+            &std::fs::read(&agent.key_path).unwrap(),
+        )
+        .unwrap();
+        reqwest::Client::builder()
+            .add_root_certificate(ca.clone())
+            .identity(identity)
+            .build()
+            .unwrap()
+    };
+    let first = client_for(store.issue_agent("build-bot").unwrap());
+    let issued = store.issue_agent("build-bot").unwrap();
+    let second = client_for(issued.clone());
+    // Revoking applies to new connections, so this one's first request comes after it
+    let second_later = client_for(issued);
+
+    let tls = store.server_config(interface.ip_address, true).unwrap();
+    let server = InstructionServer::new(port, interface, manager).with_tls(Some(tls));
+    server.start().await.unwrap();
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+    let connects = |client: &reqwest::Client| {
+        client
+            .get(format!("https://127.0.0.1:{}/health", port))
+            .send()
+    };
+
+    // Reissuing revokes the certificate it replaces, without a restart
+    assert!(connects(&first).await.is_err());
+    assert!(connects(&second).await.is_ok());
+
+    assert!(store.revoke_agent("build-bot").unwrap());
+    assert!(!store.revoke_agent("build-bot").unwrap());
+    assert!(connects(&second_later).await.is_err());
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_mtls_identifies_the_requesting_agent() {
+    use app::command::CommandChannel;
+    use app::server::tls::{TlsStore, CLI_AGENT_NAME};
+
+    let port = 8105;
+    let interface = NetworkInterface {
+        name: "lo".to_string(),
+        ip_address: "127.0.0.1".parse().unwrap(),
+        interface_type: InterfaceType::Loopback,
+    };
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
+    let manager = Arc::new(Mutex::new(
+        ContextManager::new_with_storage(storage).unwrap(),
+    ));
+
+    let store = TlsStore::in_dir(temp_dir.path().join("tls"));
+    assert!(store.issue_agent("../escape").is_err());
+    let agent = store.issue_agent("build-bot").unwrap();
+    assert!(store.issue_agent(CLI_AGENT_NAME).is_err());
+    let cli = store.agent(CLI_AGENT_NAME).unwrap();
+
+    let tls = store.server_config(interface.ip_address, true).unwrap();
+    let prompter = Arc::new(ScriptedPrompter::new(["Reject"]));
+    let (commands, _requests) = CommandChannel::new();
+    let server = InstructionServer::new(port, interface, manager.clone())
+        .with_admin_token(Some("chai_admin"))
+        .with_admin(Some(commands))
+        .with_tls(Some(tls))
+        .with_prompter(prompter.clone());
+    server.start().await.unwrap();
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+    let base = format!("https://127.0.0.1:{}", port);
+    let ca = reqwest::Certificate::from_pem(store.ca_cert_pem().unwrap().as_bytes()).unwrap();
+
+    // Without a client certificate the handshake is refused
+    let anonymous = reqwest::Client::builder()
+        .add_root_certificate(ca.clone())
+        .build()
+        .unwrap();
+    assert!(anonymous
+        .get(format!("{}/health", base))
+        .send()
+        .await
+        .map_or(true, |r| !r.status().is_success()));
+
+    let client_for = |agent: &app::server::tls::AgentCredentials| {
+        let identity = reqwest::Identity::from_pkcs8_pem(
+            &std::fs::read(&agent.cert_path).unwrap(),
+            &std::fs::read(&agent.key_path).unwrap(),
+        )
+        .unwrap();
+        reqwest::Client::builder()
+            .add_root_certificate(ca.clone())
+            .identity(identity)
+            .build()
+            .unwrap()
+    };
+    let client = client_for(&agent);
+
+    // A requester claimed in the body is replaced by the certificate's name
+    let body: serde_json::Value = client
+        .post(format!("{}/verify", base))
+        .json(&serde_json::json!({
+            "action": "deploy",
+            "reason": "mtls test",
+            "requester": "someone-else",
+            "context": { "task_id": "MTLS-1" }
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let id = body["verification_id"].as_str().unwrap();
+
+    let records: Vec<serde_json::Value> = client
+        .get(format!("{}/audit?task_id=MTLS-1", base))
+        .bearer_auth("chai_admin")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["verification_id"], id);
+    assert_eq!(records[0]["request"]["requester"], "build-bot");
+    assert_eq!(prompter.prompts()[0].title, "build-bot — MTLS-1");
+
+    // Context changes are the CLI's only when it sends the CLI secret, whatever certificate
+    // or header the caller shows
+    let cli_token = auth::cli_token(temp_dir.path()).unwrap();
+    for (client, token, role) in [
+        (&client_for(&cli), "not-the-secret", "reviewer"),
+        (&client, cli_token.as_str(), "lead"),
+    ] {
+        let resp = client
+            .put(format!("{}/contexts/bases/reviewer", base))
+            .bearer_auth("chai_admin")
+            .header("x-chaseai-author", "cli")
+            .header(auth::CLI_TOKEN_HEADER, token)
+            .json(&serde_json::json!({ "system": "sys", "role": role }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+    let authors: Vec<ContextAuthor> = manager
+        .lock()
+        .unwrap()
+        .history(None)
+        .unwrap()
+        .iter()
+        .map(|r| r.author)
+        .collect();
+    assert_eq!(authors, vec![ContextAuthor::Api, ContextAuthor::Cli]);
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_verify_fails_closed_without_prompter() {
+    let port = 8106;
+    let interface = NetworkInterface {
+        name: "lo".to_string(),
+        ip_address: "127.0.0.1".parse().unwrap(),
+        interface_type: InterfaceType::Loopback,
+    };
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
+    let manager = Arc::new(Mutex::new(
+        ContextManager::new_with_storage(storage).unwrap(),
+    ));
+
+    let server = InstructionServer::new(port, interface, manager)
+        .with_admin_token(Some("chai_admin"))
+        .with_prompter(Arc::new(ScriptedPrompter::unavailable("no display")));
+    server.start().await.unwrap();
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+    let client = reqwest::Client::new();
+    let base = format!("http://127.0.0.1:{}", port);
+
+    let resp = client.get(format!("{}/health", base)).send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["status"], "unavailable");
+
+    // No button order, including approval first, decides without a human
+    for buttons in [
+        serde_json::json!(["Approve", "Reject"]),
+        serde_json::json!(["Reject", "Approve"]),
+        serde_json::json!(["Approve Session"]),
+        serde_json::json!(["Approve Once", "Approve Session", "Reject"]),
+        serde_json::Value::Null,
+    ] {
+        let resp = client
+            .post(format!("{}/verify", base))
+            .json(&serde_json::json!({
+                "action": "git push --force",
+                "reason": "fail closed test",
+                "buttons": buttons,
+                "context": { "task_id": "CLOSED-1" }
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            resp.status(),
+            StatusCode::SERVICE_UNAVAILABLE,
+            "{}",
+            buttons
+        );
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(body["status"], "unavailable");
+        assert!(body.get("session_id").is_none());
+    }
+
+    // Polling reports the same outcome
+    let body: serde_json::Value = client
+        .post(format!("{}/verify", base))
+        .json(&serde_json::json!({
+            "action": "git push --force",
+            "reason": "fail closed test",
+            "non_blocking": true,
+            "context": { "task_id": "CLOSED-1" }
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let resp = client
+        .get(format!(
+            "{}/verify/{}?wait=5s",
+            base,
+            body["verification_id"].as_str().unwrap()
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let records: Vec<serde_json::Value> = client
+        .get(format!("{}/audit?task_id=CLOSED-1", base))
+        .bearer_auth("chai_admin")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(records.len(), 6);
+    assert!(records.iter().all(|r| r["status"] == "unavailable"));
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_custom_button_decisions_are_explicit() {
+    let port = 8107;
+    let interface = NetworkInterface {
+        name: "lo".to_string(),
+        ip_address: "127.0.0.1".parse().unwrap(),
+        interface_type: InterfaceType::Loopback,
+    };
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
+    let manager = Arc::new(Mutex::new(
+        ContextManager::new_with_storage(storage).unwrap(),
+    ));
+
+    let prompter = Arc::new(ScriptedPrompter::new([
+        "Don't approve",
+        "approved_session",
+        "Approve",
+        "Approve",
+        "Not now",
+        "Ship it",
+    ]));
+    let server = InstructionServer::new(port, interface, manager.clone())
+        .with_prompter(prompter.clone())
+        .with_admin_token(Some("chai_admin"));
+    server.start().await.unwrap();
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+    let client = reqwest::Client::new();
+    let verify = |buttons: serde_json::Value| {
+        client
+            .post(format!("http://127.0.0.1:{}/verify", port))
+            .json(&serde_json::json!({
+                "action": "npm publish",
+                "reason": "custom buttons",
+                "buttons": buttons
+            }))
+            .send()
+    };
+    let bare = serde_json::json!(["Don't approve", "Approve", "approved_session"]);
+    let structured = serde_json::json!([
+        { "label": "Approve", "decision": "reject" },
+        { "label": "Not now", "decision": "defer" },
+        {
+            "label": "Ship it",
+            "decision": "approve_session",
+            "scope": { "actions": ["npm publish*"], "max_uses": 2 }
+        }
+    ]);
+
+    // Bare labels: only the strict mapping decides, and an unmapped label never reads as
+    // a decision, even one named like an approval status
+    for label in ["Don't approve", "approved_session"] {
+        let body: serde_json::Value = verify(bare.clone()).await.unwrap().json().await.unwrap();
+        assert_eq!(body["status"], "no_decision");
+        assert_eq!(body["button"], label);
+        assert!(body.get("decision").is_none());
+    }
+
+    let body: serde_json::Value = verify(bare.clone()).await.unwrap().json().await.unwrap();
+    assert_eq!(body["status"], "approved");
+    assert_eq!(body["decision"], "approve");
+
+    // Structured buttons: the declared decision wins over the label
+    let body: serde_json::Value = verify(structured.clone())
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["status"], "reject");
+    assert_eq!(body["decision"], "reject");
+
+    let body: serde_json::Value = verify(structured.clone())
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["status"], "deferred");
+    assert_eq!(body["decision"], "defer");
+    assert!(body.get("session_id").is_none());
+
+    let body: serde_json::Value = verify(structured.clone())
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["status"], "approved_session");
+    assert_eq!(body["decision"], "approve_session");
+    let session_id = body["session_id"].as_str().unwrap();
+    {
+        let mgr = manager.lock().unwrap();
+        let session = mgr
+            .list_sessions()
+            .into_iter()
+            .find(|s| s.id == session_id)
+            .unwrap();
+        assert_eq!(session.scope.actions, vec!["npm publish*".to_string()]);
+        assert_eq!(session.scope.max_uses, Some(2));
+    }
+    let prompts = prompter.prompts();
+    assert_eq!(prompts[3].buttons, vec!["Approve", "Not now", "Ship it"]);
+    assert!(prompts[3].reason.contains("npm publish*"));
+
+    // Out of scripted answers: nobody chose, so nothing is approved
+    let body: serde_json::Value = verify(bare).await.unwrap().json().await.unwrap();
+    assert_eq!(body["status"], "cancelled");
+    assert!(body.get("decision").is_none());
+
+    // A structured button must say what it decides
+    let resp = verify(serde_json::json!([{ "label": "Approve" }]))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    // Only a console prompter has a queue for `chase console`
+    let resp = client
+        .get(format!("http://127.0.0.1:{}/verify/pending", port))
+        .bearer_auth("chai_admin")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_console_answers_waiting_verifications() {
+    let port = 8108;
+    let interface = NetworkInterface {
+        name: "lo".to_string(),
+        ip_address: "127.0.0.1".parse().unwrap(),
+        interface_type: InterfaceType::Loopback,
+    };
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
+    let manager = Arc::new(Mutex::new(
+        ContextManager::new_with_storage(storage).unwrap(),
+    ));
+
+    let server = InstructionServer::new(port, interface, manager)
+        .with_prompter(Arc::new(ConsolePrompter::new()))
+        .with_token(Some("chai_port"))
+        .with_admin_token(Some("chai_admin"));
+    server.start().await.unwrap();
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+    let client = reqwest::Client::new();
+    let base = format!("http://127.0.0.1:{}", port);
+
+    // Nobody is watching yet, so nobody is asked
+    let resp = client.get(format!("{}/health", base)).send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let resp = client
+        .post(format!("{}/verify", base))
+        .bearer_auth("chai_port")
+        .json(&serde_json::json!({ "action": "ls", "reason": "no console" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    // Polling the queue attaches the console
+    let resp = client
+        .get(format!("{}/verify/pending", base))
+        .bearer_auth("chai_admin")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = client.get(format!("{}/health", base)).send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: serde_json::Value = client
+        .post(format!("{}/verify", base))
+        .bearer_auth("chai_port")
+        .json(&serde_json::json!({
+            "action": "systemctl restart nginx",
+            "reason": "config reload",
+            "non_blocking": true,
+            "context": { "task_id": "OPS-7", "host": "build-1" }
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let id = body["verification_id"].as_str().unwrap().to_string();
+
+    // The prompt reaches the console queue shortly after the request is accepted
+    let mut waiting: Vec<serde_json::Value> = Vec::new();
+    for _ in 0..50 {
+        waiting = client
+            .get(format!("{}/verify/pending", base))
+            .bearer_auth("chai_admin")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if !waiting.is_empty() {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(waiting.len(), 1);
+    assert_eq!(waiting[0]["verification_id"], id.as_str());
+    assert_eq!(waiting[0]["request"]["context"]["host"], "build-1");
+
+    // The agent that asked holds the port's token; it can neither see the queue nor answer
+    let resp = client
+        .get(format!("{}/verify/pending", base))
+        .bearer_auth("chai_port")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let resp = client
+        .post(format!("{}/verify/{}/answer", base, id))
+        .bearer_auth("chai_port")
+        .json(&serde_json::json!({ "button": 2 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = client
+        .post(format!("{}/verify/{}/answer", base, id))
+        .bearer_auth("chai_admin")
+        .json(&serde_json::json!({ "button": 3 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    // Default buttons: 0 is "Reject"
+    let resp = client
+        .post(format!("{}/verify/{}/answer", base, id))
+        .bearer_auth("chai_admin")
+        .json(&serde_json::json!({ "button": 0, "message": "not during the release" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["status"], "reject");
+    assert_eq!(body["decision"], "reject");
+    assert_eq!(body["message"], "not during the release");
+
+    let body: serde_json::Value = client
+        .get(format!("{}/verify/{}", base, id))
+        .bearer_auth("chai_port")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["status"], "reject");
+
+    let resp = client
+        .post(format!("{}/verify/{}/answer", base, id))
+        .bearer_auth("chai_admin")
+        .json(&serde_json::json!({ "button": 1 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    server.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_admin_api_mirrors_tray_actions() {
+    use app::command::{configure, CommandChannel};
+    use app::config::network_config::VerificationMode;
+
+    isolate_config_dir();
+    let port = 8113;
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
+    let manager = Arc::new(Mutex::new(
+        ContextManager::new_with_storage(storage).unwrap(),
+    ));
+
+    // Stands in for the tray app: owns the config and applies what the API sends
+    let (commands, mut requests) = CommandChannel::new();
+    // Bindings the owner only records, nothing listens on 9113
+    let mut config = create_test_config(port, true);
+    config.port_bindings.push(PortBinding {
+        port: 9113,
+        role: PortRole::Instruction,
+        enabled: false,
+        ..config.port_bindings[0].clone()
+    });
+    let owned = Arc::new(Mutex::new(config));
+    let owner = owned.clone();
+    tokio::spawn(async move {
+        while let Some(request) = requests.recv().await {
+            let mut config = owner.lock().unwrap();
+            let result = request
+                .command
+                .config_changes(&config)
+                .and_then(|changes| configure(&mut config, &changes));
+            request.respond(&result);
+        }
+    });
+
+    let interface = create_test_config(port, true).port_bindings[0]
+        .interface
+        .clone();
+    let server = InstructionServer::new(port, interface, manager)
+        .with_token(Some("chai_port"))
+        .with_admin_token(Some("chai_admin"))
+        .with_admin(Some(commands));
+    server.start().await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+    let client = reqwest::Client::new();
+    let base = format!("http://127.0.0.1:{}/admin", port);
+
+    let resp = client.get(format!("{}/ports", base)).send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    // Agents on the port hold its token; it does not open the admin API
+    for (method, path) in [
+        ("GET", "/ports"),
+        ("PATCH", "/ports"),
+        ("PUT", "/mode"),
+        ("PUT", "/agents/ci/token"),
+    ] {
+        let resp = client
+            .request(method.parse().unwrap(), format!("{}{}", base, path))
+            .bearer_auth("chai_port")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            resp.status(),
+            StatusCode::UNAUTHORIZED,
+            "{} {}",
+            method,
+            path
+        );
+    }
+
+    let ports: serde_json::Value = client
+        .get(format!("{}/ports", base))
+        .bearer_auth("chai_admin")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(ports.as_array().unwrap().len(), 2);
+    assert_eq!(ports[1]["port"], 9113);
+    assert_eq!(ports[1]["base_url"], "http://127.0.0.1:9113");
+    assert!(ports[0].get("token").is_none());
+
+    // Toggle and change role in one request
+    let resp = client
+        .patch(format!("{}/ports/9113", base))
+        .bearer_auth("chai_admin")
+        .json(&serde_json::json!({ "enabled": true, "role": "Verification" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["enabled"], true);
+    assert_eq!(body["role"], "Verification");
+
+    for (method, path, body, status) in [
+        (
+            "PATCH",
+            "/ports/9999",
+            r#"{"enabled": true}"#,
+            StatusCode::NOT_FOUND,
+        ),
+        (
+            "PATCH",
+            "/ports/9113",
+            r#"{}"#,
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ),
+        ("DELETE", "/ports/9999", "", StatusCode::NOT_FOUND),
+        (
+            "POST",
+            "/ports",
+            r#"{"port": 9113, "role": "Instruction"}"#,
+            StatusCode::CONFLICT,
+        ),
+        (
+            "PUT",
+            "/interface",
+            r#"{"name": "no-such-interface"}"#,
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ),
+    ] {
+        let resp = client
+            .request(method.parse().unwrap(), format!("{}{}", base, path))
+            .bearer_auth("chai_admin")
+            .header("content-type", "application/json")
+            .body(body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), status, "{} {}", method, path);
+    }
+
+    let resp = client
+        .post(format!("{}/ports", base))
+        .bearer_auth("chai_admin")
+        .json(&serde_json::json!({ "port": 9114, "role": "Instruction", "enabled": false }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["port"], 9114);
+    assert_eq!(body["enabled"], false);
+
+    let ports: serde_json::Value = client
+        .delete(format!("{}/ports/9114", base))
+        .bearer_auth("chai_admin")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(ports.as_array().unwrap().len(), 2);
+
+    let ports: serde_json::Value = client
+        .patch(format!("{}/ports", base))
+        .bearer_auth("chai_admin")
+        .json(&serde_json::json!({ "enabled": false }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(ports
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|p| p["enabled"] == false));
+
+    let body: serde_json::Value = client
+        .put(format!("{}/mode", base))
+        .bearer_auth("chai_admin")
+        .json(&serde_json::json!({ "mode": "Cli" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["mode"], "Cli");
+
+    let body: serde_json::Value = client
+        .get(format!("{}/interface", base))
+        .bearer_auth("chai_admin")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(body["default_interface"], "Loopback");
+    assert!(body["available"].is_array());
+
+    server.stop().await.unwrap();
+    let config = owned.lock().unwrap();
+    assert_eq!(config.verification_mode, VerificationMode::Cli);
+    assert_eq!(config.binding(9113).unwrap().role, PortRole::Verification);
+    assert!(config.port_bindings.iter().all(|b| !b.enabled));
+    assert!(config.binding(9114).is_none());
+}
+
+#[tokio::test]
+async fn test_admin_api_needs_an_owner() {
+    use app::command::CommandChannel;
+
+    let port = 8114;
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
+    let manager = Arc::new(Mutex::new(
+        ContextManager::new_with_storage(storage).unwrap(),
+    ));
+    let interface = create_test_config(port, true).port_bindings[0]
+        .interface
+        .clone();
+
+    // Without a channel there is no admin API at all
+    let server = InstructionServer::new(port, interface.clone(), manager.clone());
+    server.start().await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    let url = format!("http://127.0.0.1:{}/admin/mode", port);
+    let resp = reqwest::get(&url).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    server.stop().await.unwrap();
+
+    // Nobody applies requests any more
+    let (commands, requests) = CommandChannel::new();
+    drop(requests);
+    let server = InstructionServer::new(port, interface.clone(), manager.clone())
+        .with_admin_token(Some("chai_admin"))
+        .with_admin(Some(commands.clone()));
+    server.start().await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    let client = reqwest::Client::new();
+    let resp = client
+        .get(&url)
+        .bearer_auth("chai_admin")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "admin_unavailable");
+    server.stop().await.unwrap();
+
+    // Without an admin secret the routes stay closed, whatever is presented
+    let server = InstructionServer::new(port, interface, manager).with_admin(Some(commands));
+    server.start().await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    let resp = reqwest::get(&url).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    server.stop().await.unwrap();
+}
+
+#[test]
+fn test_admin_token_is_created_once_and_private() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path().join("chaseai");
+
+    let token = auth::admin_token(&dir).unwrap();
+    assert!(token.starts_with("chai_"));
+    assert_eq!(auth::admin_token(&dir).unwrap(), token);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(dir.join(auth::ADMIN_TOKEN_FILE_NAME))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
+
+#[tokio::test]
+async fn test_context_can_be_written_on_admin_ports() {
+    use app::command::{configure, CommandChannel};
+
+    isolate_config_dir();
+    let port = 8119;
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = ContextStorage::with_path(temp_dir.path().join("contexts.json"));
+    let manager = Arc::new(Mutex::new(
+        ContextManager::new_with_storage(storage).unwrap(),
+    ));
+
+    let (commands, mut requests) = CommandChannel::new();
+    let mut config = create_test_config(port, true);
+    // Recorded but disabled; nothing listens on 9113
+    config.port_bindings.push(PortBinding {
+        port: 9113,
+        enabled: false,
+        ..config.port_bindings[0].clone()
+    });
+    tokio::spawn(async move {
+        while let Some(request) = requests.recv().await {
+            let result = request
+                .command
+                .config_changes(&config)
+                .and_then(|changes| configure(&mut config, &changes));
+            request.respond(&result);
+        }
+    });
+
+    let interface = create_test_config(port, true).port_bindings[0]
+        .interface
+        .clone();
+    let server = InstructionServer::new(port, interface, manager.clone())
+        .with_token(Some("chai_port"))
+        .with_admin_token(Some("chai_admin"))
+        .with_admin(Some(commands));
+    server.start().await.unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+
+    let client = reqwest::Client::new();
+    let url = format!("http://127.0.0.1:{}/context", port);
+    let context = serde_json::json!({
+        "system": "ChaseAI",
+        "role": "execution-agent",
+        "base_instruction": "Ask before you act",
+        "allowed_actions": ["deploy"],
+        "verification_required": true
+    });
+
+    let resp = client.put(&url).json(&context).send().await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    // An agent can read its context with the port's token, but not rewrite it
+    let mut waived = context.clone();
+    waived["verification_required"] = false.into();
+    let resp = client
+        .put(&url)
+        .bearer_auth("chai_port")
+        .json(&waived)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    for (method, path) in [
+        ("DELETE", "/context"),
+        ("POST", "/context/rollback"),
+        ("PUT", "/contexts/bases/shared"),
+        ("DELETE", "/contexts/bases/shared"),
+    ] {
+        let resp = client
+            .request(
+                method.parse().unwrap(),
+                format!("http://127.0.0.1:{}{}", port, path),
+            )
+            .bearer_auth("chai_port")
+            .json(&context)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            resp.status(),
+            StatusCode::UNAUTHORIZED,
+            "{} {}",
+            method,
+            path
+        );
+    }
+
+    // Every validation error at once
+    let resp = client
+        .put(&url)
+        .bearer_auth("chai_admin")
+        .json(&serde_json::json!({
+            "system": "",
+            "role": "",
+            "base_instruction": "x",
+            "allowed_actions": ["Deploy"],
+            "verification_required": true
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(body["error"], "invalid_context");
+    let fields: Vec<&str> = body["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["field"].as_str().unwrap())
+        .collect();
+    assert_eq!(fields, vec!["system", "role", "allowed_actions"]);
+
+    for (target, status, code) in [
+        ("?port=9999", StatusCode::NOT_FOUND, "unknown_port"),
+        (
+            "?port=9113",
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "port_disabled",
+        ),
+    ] {
+        let resp = client
+            .put(format!("{}{}", url, target))
+            .bearer_auth("chai_admin")
+            .json(&context)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), status, "{}", target);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(body["error"], code);
+    }
+
+    let resp = client
+        .put(&url)
+        .bearer_auth("chai_admin")
+        .json(&context)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let served: InstructionContext = client
+        .get(&url)
+        .bearer_auth("chai_port")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(served.allowed_actions, vec!["deploy".to_string()]);
+    let listed: serde_json::Value = client
+        .get(format!("http://127.0.0.1:{}/contexts", port))
+        .bearer_auth("chai_admin")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(listed[0]["port"], port);
+
+    for status in [StatusCode::NO_CONTENT, StatusCode::NOT_FOUND] {
+        let resp = client
+            .delete(&url)
+            .bearer_auth("chai_admin")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), status);
+    }
+    assert!(manager.lock().unwrap().get_context(port).is_none());
+
+    // Put back by rolling back to the first revision; claiming to be the CLI changes nothing
+    let resp = client
+        .post(format!("{}/rollback", url))
+        .bearer_auth("chai_admin")
+        .header("x-chaseai-author", "cli")
+        .json(&serde_json::json!({ "rev": 1 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    for (rev, status, code) in [
+        (1, StatusCode::CONFLICT, "unchanged"),
+        (9, StatusCode::NOT_FOUND, "unknown_revision"),
+    ] {
+        let resp = client
+            .post(format!("{}/rollback", url))
+            .bearer_auth("chai_admin")
+            .json(&serde_json::json!({ "rev": rev }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), status);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(body["error"], code);
+    }
+
+    let history: Vec<ContextRevision> = client
+        .get(format!("{}/history?port={}", url, port))
+        .bearer_auth("chai_admin")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let authors: Vec<ContextAuthor> = history.iter().map(|r| r.author).collect();
+    assert_eq!(
+        authors,
+        vec![ContextAuthor::Api, ContextAuthor::Api, ContextAuthor::Api]
+    );
+    assert!(history[1].context.is_none());
+    assert_eq!(history[2].rollback_of, Some(1));
+    assert!(manager.lock().unwrap().get_context(port).is_some());
+
+    // One agent gets its own context; the others keep the port's
+    let mut own = context.clone();
+    own["role"] = "ci-agent".into();
+    let resp = client
+        .put(format!("{}?agent=ci", url))
+        .bearer_auth("chai_admin")
+        .json(&own)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let created: admin::PortContext = resp.json().await.unwrap();
+    assert_eq!(created.agent.as_deref(), Some("ci"));
+    manager
+        .lock()
+        .unwrap()
+        .set_agent_tokens([("ci".to_string(), "chai_ci".to_string())].into());
+
+    for (header, value, role) in [
+        // A claimed name is not an identity
+        ("x-chaseai-agent", "ci", "execution-agent"),
+        (auth::AGENT_TOKEN_HEADER, "chai_ci", "ci-agent"),
+    ] {
+        let served: InstructionContext = client
+            .get(&url)
+            .bearer_auth("chai_port")
+            .header(header, value)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(served.role, role, "{}: {}", header, value);
+    }
+    let resp = client
+        .get(&url)
+        .bearer_auth("chai_port")
+        .header(auth::AGENT_TOKEN_HEADER, "chai_wrong")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    server.stop().await.unwrap();
+}
@@ -113,3 +113,81 @@ fn test_download_config_with_filtered_ports() {
         assert!(port["endpoints"].is_array());
     }
 }
+
+#[test]
+fn test_build_menu_with_sessions() {
+    use app::instruction::session::{format_remaining, Session, SessionScope};
+    use app::instruction::storage::ContextKey;
+
+    let config = NetworkConfig::new();
+    let session = Session::new(
+        "s-1",
+        "v-1",
+        ContextKey::agent(8888, "ci"),
+        SessionScope {
+            max_uses: Some(5),
+            ..Default::default()
+        }
+        .resolve("cargo build", Some("CHASE-1")),
+    );
+    let state = app::ui::tray_menu::TrayState {
+        sessions: vec![session],
+        ..Default::default()
+    };
+    let menu = app::ui::tray_menu::build_menu_with_state(&config, &state);
+    assert!(menu.is_ok());
+
+    assert_eq!(format_remaining(chrono::Duration::seconds(30)), "<1m");
+    assert_eq!(format_remaining(chrono::Duration::minutes(42)), "42m");
+    assert_eq!(format_remaining(chrono::Duration::minutes(65)), "1h 05m");
+}
+
+#[test]
+fn test_build_menu_with_blocked_ports() {
+    use app::ui::tray_menu::TrayState;
+
+    let config = NetworkConfig::new();
+    let mut state = TrayState::default();
+    assert!(!state.tooltip().contains("blocked"));
+
+    state
+        .port_errors
+        .insert(config.port_bindings[0].port, "no token".to_string());
+    assert!(state.tooltip().contains("1 port(s) blocked"));
+    assert!(app::ui::tray_menu::build_menu_with_state(&config, &state).is_ok());
+}
+
+#[test]
+fn test_build_menu_with_unavailable_prompter() {
+    use app::ui::tray_menu::TrayState;
+
+    let config = NetworkConfig::new();
+    let state = TrayState {
+        prompter_unavailable: Some("no display".to_string()),
+        ..Default::default()
+    };
+    assert!(state.tooltip().contains("verifications unavailable"));
+    assert!(app::ui::tray_menu::build_menu_with_state(&config, &state).is_ok());
+}
+
+#[test]
+fn test_build_menu_with_context_history() {
+    use app::instruction::history::{ContextAuthor, ContextRevision};
+    use app::ui::tray_menu::TrayState;
+
+    let config = NetworkConfig::new();
+    let state = TrayState {
+        revisions: vec![ContextRevision {
+            rev: 2,
+            port: Some(8888),
+            agent: None,
+            base: None,
+            at: chrono::Utc::now(),
+            author: ContextAuthor::Cli,
+            context: None,
+            rollback_of: None,
+        }],
+        ..Default::default()
+    };
+    assert!(app::ui::tray_menu::build_menu_with_state(&config, &state).is_ok());
+}
@@ -0,0 +1,773 @@
+use app::instruction::storage::ContextKey;
+use app::verification::audit::{
+    AuditFilter, AuditLog, AuditRecord, AUDIT_KEY_FILE_NAME, GENESIS_HASH,
+};
+use app::verification::pending::{parse_wait, PendingVerifications, MAX_WAIT};
+use app::verification::policy::{
+    Policy, PolicyDecision, PolicyEffect, PolicyInput, PolicyStore, POLICY_ERROR_RULE,
+};
+use app::verification::prompter::{
+    AppleScriptPrompter, CommandPrompter, ConsolePrompter, Prompt, PromptAnswer, Prompter,
+    PrompterConfig, ScriptedPrompter, TerminalPrompter,
+};
+use app::verification::request::{
+    new_verification_id, ButtonDecision, VerificationButton, VerificationRequest,
+    VerificationResponse,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn create_test_request(action: &str) -> VerificationRequest {
+    serde_json::from_value(serde_json::json!({
+        "action": action,
+        "reason": "test",
+        "context": { "task_id": "CHASE-1" },
+        "non_blocking": true
+    }))
+    .unwrap()
+}
+
+fn approved(id: &str) -> VerificationResponse {
+    VerificationResponse {
+        status: "approved".to_string(),
+        verification_id: id.to_string(),
+        message: None,
+        session_id: None,
+        policy_rule: None,
+        decision: None,
+        button: None,
+    }
+}
+
+#[test]
+fn test_verification_ids_are_unique() {
+    let a = new_verification_id().unwrap();
+    let b = new_verification_id().unwrap();
+    assert!(a.starts_with("v-"));
+    assert_eq!(a.len(), 34);
+    assert_ne!(a, b);
+}
+
+#[test]
+fn test_register_starts_pending() {
+    let pending = PendingVerifications::new();
+    pending.register(
+        "v-1",
+        &create_test_request("rm -rf build/"),
+        ContextKey::agent(3000, "ci"),
+    );
+
+    let state = pending.get("v-1").unwrap();
+    assert!(state.is_pending());
+    assert_eq!(pending.list_pending().len(), 1);
+    assert_eq!(pending.list_pending()[0].request.task_id(), Some("CHASE-1"));
+
+    // Only the port and agent that asked may see the decision
+    assert!(pending.is_owned_by("v-1", &ContextKey::agent(3000, "ci")));
+    assert!(!pending.is_owned_by("v-1", &ContextKey::from(3000)));
+    assert!(!pending.is_owned_by("v-1", &ContextKey::agent(3001, "ci")));
+    assert!(!pending.is_owned_by("v-missing", &ContextKey::agent(3000, "ci")));
+}
+
+#[test]
+fn test_resolve_unknown_id() {
+    let pending = PendingVerifications::new();
+    assert!(!pending.resolve("v-missing", approved("v-missing")));
+    assert!(pending.get("v-missing").is_none());
+}
+
+#[tokio::test]
+async fn test_wait_returns_decision() {
+    let pending = Arc::new(PendingVerifications::new());
+    pending.register("v-2", &create_test_request("deploy"), 3000.into());
+
+    let resolver = pending.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        resolver.resolve("v-2", approved("v-2"));
+    });
+
+    let state = pending.wait("v-2", Duration::from_secs(5)).await.unwrap();
+    assert_eq!(state.status, "approved");
+    assert!(pending.list_pending().is_empty());
+}
+
+#[tokio::test]
+async fn test_wait_times_out_as_pending() {
+    let pending = PendingVerifications::new();
+    pending.register("v-3", &create_test_request("deploy"), 3000.into());
+
+    let state = pending
+        .wait("v-3", Duration::from_millis(20))
+        .await
+        .unwrap();
+    assert!(state.is_pending());
+    assert!(pending.wait("v-unknown", Duration::ZERO).await.is_none());
+}
+
+#[test]
+fn test_parse_wait() {
+    assert_eq!(parse_wait("30s"), Some(Duration::from_secs(30)));
+    assert_eq!(parse_wait("500ms"), Some(Duration::from_millis(500)));
+    assert_eq!(parse_wait("2m"), Some(MAX_WAIT));
+    assert_eq!(parse_wait("15"), Some(Duration::from_secs(15)));
+    assert_eq!(parse_wait("1h"), None);
+    assert_eq!(parse_wait("soon"), None);
+}
+
+fn create_test_record(id: &str, action: &str, status: &str, task_id: &str) -> AuditRecord {
+    let now = chrono::Utc::now();
+    let mut request = create_test_request(action);
+    request.context = Some(serde_json::json!({ "task_id": task_id }));
+    AuditRecord {
+        verification_id: id.to_string(),
+        requested_at: now,
+        decided_at: now,
+        duration_ms: 0,
+        client_addr: Some("127.0.0.1:50000".to_string()),
+        port: 9999,
+        request,
+        chosen_button: Some("Approve Once".to_string()),
+        status: status.to_string(),
+        message: None,
+        session_id: None,
+        policy_rule: None,
+        decision: None,
+        prev_hash: String::new(),
+        hash: String::new(),
+    }
+}
+
+#[test]
+fn test_audit_log_append_and_read() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let log = AuditLog::in_dir(temp_dir.path());
+    assert!(log.read_all()?.is_empty());
+
+    log.append(&create_test_record(
+        "v-1",
+        "rm -rf build/",
+        "approved",
+        "T-1",
+    ))?;
+    log.append(&create_test_record("v-2", "git push", "reject", "T-2"))?;
+
+    let records = log.read_all()?;
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].verification_id, "v-1");
+    assert_eq!(records[1].request.action, "git push");
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(log.path())?.permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_audit_log_filtering() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let log = AuditLog::in_dir(temp_dir.path());
+    log.append(&create_test_record(
+        "v-1",
+        "rm -rf build/",
+        "approved",
+        "T-1",
+    ))?;
+    log.append(&create_test_record("v-2", "git push", "reject", "T-2"))?;
+    log.append(&create_test_record("v-3", "RM old logs", "approved", "T-2"))?;
+
+    let by_status = AuditFilter {
+        status: Some("approved".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(log.query(&by_status)?.len(), 2);
+
+    let by_action = AuditFilter {
+        action: Some("rm".to_string()),
+        task_id: Some("T-2".to_string()),
+        ..Default::default()
+    };
+    let matches = log.query(&by_action)?;
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].verification_id, "v-3");
+
+    let future = AuditFilter {
+        from: Some(chrono::Utc::now() + chrono::Duration::hours(1)),
+        ..Default::default()
+    };
+    assert!(log.query(&future)?.is_empty());
+
+    let latest = AuditFilter {
+        limit: Some(1),
+        ..Default::default()
+    };
+    assert_eq!(log.query(&latest)?[0].verification_id, "v-3");
+    Ok(())
+}
+
+#[test]
+fn test_audit_log_chains_records() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let log = AuditLog::in_dir(temp_dir.path());
+
+    let first = log.append(&create_test_record(
+        "v-1",
+        "rm -rf /tmp/x",
+        "approved",
+        "T-1",
+    ))?;
+    let second = log.append(&create_test_record("v-2", "git push", "reject", "T-1"))?;
+    assert_eq!(first.prev_hash, GENESIS_HASH);
+    assert_eq!(second.prev_hash, first.hash);
+
+    // A fresh handle picks up the chain where the file ends
+    let reopened = AuditLog::in_dir(temp_dir.path());
+    let third = reopened.append(&create_test_record("v-3", "ls", "approved", "T-2"))?;
+    assert_eq!(third.prev_hash, second.hash);
+
+    let report = reopened.verify_chain()?;
+    assert!(report.is_intact());
+    assert_eq!(report.verified, 3);
+    assert_eq!(report.head, third.hash);
+    Ok(())
+}
+
+#[test]
+fn test_audit_log_hashes_are_keyed_over_written_lines() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let log = AuditLog::in_dir(temp_dir.path());
+    log.append(&create_test_record("v-1", "ls", "approved", "T-1"))?;
+    log.append(&create_test_record("v-2", "pwd", "approved", "T-1"))?;
+    assert!(log.verify_chain()?.is_intact());
+
+    let key_path = temp_dir.path().join(AUDIT_KEY_FILE_NAME);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&key_path)?.permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    // The same record reformatted is no longer the line that was sealed
+    let content = std::fs::read_to_string(log.path())?;
+    let lines: Vec<&str> = content.lines().collect();
+    let reformatted = lines[1].replacen("\"status\":", "\"status\": ", 1);
+    std::fs::write(log.path(), format!("{}\n{}\n", lines[0], reformatted))?;
+    let broken = log
+        .verify_chain()?
+        .broken
+        .expect("reformatting changes the bytes");
+    assert_eq!(broken.line, 2);
+
+    // Without the key the chain can't be rebuilt
+    std::fs::write(log.path(), &content)?;
+    std::fs::write(&key_path, "0123456789abcdef\n")?;
+    let broken = log.verify_chain()?.broken.expect("another key fails");
+    assert_eq!(broken.line, 1);
+    Ok(())
+}
+
+#[test]
+fn test_audit_log_verifies_records_without_action_type() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let log = AuditLog::in_dir(temp_dir.path());
+
+    // Written the way builds before `action_type` wrote them, without the key
+    let untyped = log.append(&create_test_record("v-1", "ls", "approved", "T-1"))?;
+    assert!(untyped.request.action_type.is_none());
+    let content = std::fs::read_to_string(log.path())?;
+    assert!(!content.contains("action_type"));
+
+    let mut typed = create_test_record("v-2", "cargo test", "approved", "T-1");
+    typed.request.action_type = Some("run-tests".to_string());
+    log.append(&typed)?;
+
+    let report = log.verify_chain()?;
+    assert!(report.is_intact());
+    assert_eq!(report.verified, 2);
+    Ok(())
+}
+
+#[test]
+fn test_audit_log_detects_tampering() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let log = AuditLog::in_dir(temp_dir.path());
+    for (id, status) in [("v-1", "reject"), ("v-2", "reject"), ("v-3", "approved")] {
+        log.append(&create_test_record(id, "git push", status, "T-1"))?;
+    }
+
+    // Rewrite the second decision in place
+    let content = std::fs::read_to_string(log.path())?;
+    let lines: Vec<&str> = content.lines().collect();
+    let tampered = lines[1].replace("\"status\":\"reject\"", "\"status\":\"approved\"");
+    assert_ne!(tampered, lines[1]);
+    std::fs::write(
+        log.path(),
+        format!("{}\n{}\n{}\n", lines[0], tampered, lines[2]),
+    )?;
+
+    let report = log.verify_chain()?;
+    let broken = report.broken.expect("tampering should be detected");
+    assert_eq!(broken.line, 2);
+    assert_eq!(broken.verification_id.as_deref(), Some("v-2"));
+    assert_eq!(report.verified, 1);
+
+    // Dropping a record breaks the link of the one after it
+    std::fs::write(log.path(), format!("{}\n{}\n", lines[0], lines[2]))?;
+    let broken = log
+        .verify_chain()?
+        .broken
+        .expect("removal should be detected");
+    assert_eq!(broken.line, 2);
+    assert_eq!(broken.verification_id.as_deref(), Some("v-3"));
+    Ok(())
+}
+
+#[test]
+fn test_audit_anchor_catches_truncation_and_resealing() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let log = AuditLog::in_dir(temp_dir.path());
+    for id in ["v-1", "v-2", "v-3"] {
+        log.append(&create_test_record(id, "git push", "reject", "T-1"))?;
+    }
+    let anchor = log.anchor()?;
+    assert_eq!(anchor.records, 3);
+    assert!(log.verify_against(&anchor)?.is_intact());
+
+    // Records written after the anchor was taken are fine
+    let later = log.append(&create_test_record("v-4", "ls", "approved", "T-1"))?;
+    assert_eq!(log.anchor()?.head, later.hash);
+    assert!(log.verify_against(&anchor)?.is_intact());
+
+    // Dropping the last records leaves every link intact, but not the anchor
+    let content = std::fs::read_to_string(log.path())?;
+    let lines: Vec<&str> = content.lines().collect();
+    std::fs::write(log.path(), format!("{}\n{}\n", lines[0], lines[1]))?;
+    assert!(log.verify_chain()?.is_intact());
+    let broken = log
+        .verify_against(&anchor)?
+        .broken
+        .expect("truncation should be detected");
+    assert_eq!(broken.line, 3);
+
+    // A chain rebuilt from scratch with the key is intact on its own, but ends elsewhere
+    std::fs::remove_file(log.path())?;
+    let forger = AuditLog::in_dir(temp_dir.path());
+    for id in ["v-1", "v-2", "v-3"] {
+        forger.append(&create_test_record(id, "git push", "approved", "T-1"))?;
+    }
+    assert!(forger.verify_chain()?.is_intact());
+    let report = forger.verify_against(&anchor)?;
+    assert_eq!(
+        report.broken.expect("re-sealing should be detected").line,
+        3
+    );
+    assert_eq!(report.verified, 2);
+    Ok(())
+}
+
+#[test]
+fn test_audit_log_refuses_malformed_lines() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let log = AuditLog::in_dir(temp_dir.path());
+    log.append(&create_test_record("v-1", "ls", "approved", "T-1"))?;
+
+    let mut content = std::fs::read_to_string(log.path())?;
+    content.push_str("not a record\n");
+    std::fs::write(log.path(), content)?;
+
+    let error = log.read_all().unwrap_err();
+    assert!(error.to_string().contains("line 2"), "{}", error);
+    assert!(log.query(&AuditFilter::default()).is_err());
+    assert!(AuditLog::in_dir(temp_dir.path())
+        .append(&create_test_record("v-2", "ls", "approved", "T-1"))
+        .is_err());
+    Ok(())
+}
+
+fn policy_input<'a>(action: &'a str, task_id: Option<&'a str>, time: &str) -> PolicyInput<'a> {
+    PolicyInput {
+        action,
+        task_id,
+        port: 9999,
+        time: chrono::NaiveTime::parse_from_str(time, "%H:%M").unwrap(),
+    }
+}
+
+#[test]
+fn test_policy_first_matching_rule_wins() -> anyhow::Result<()> {
+    let policy: Policy = toml::from_str(
+        r#"
+[[rules]]
+effect = "ask"
+action = "^git push"
+task_id = "RELEASE-*"
+
+[[rules]]
+name = "git"
+effect = "allow"
+action = "^git "
+
+[[rules]]
+name = "night"
+effect = "deny"
+time = "22:00-06:00"
+"#,
+    )?;
+    policy.validate()?;
+
+    let decision = policy.evaluate(&policy_input("git push", Some("RELEASE-7"), "12:00"));
+    assert_eq!(decision.effect, PolicyEffect::Ask);
+    assert_eq!(decision.rule.as_deref(), Some("#1"));
+
+    let decision = policy.evaluate(&policy_input("git push", Some("CHASE-1"), "23:30"));
+    assert_eq!(decision.effect, PolicyEffect::Allow);
+    assert_eq!(decision.rule.as_deref(), Some("git"));
+
+    let decision = policy.evaluate(&policy_input("make deploy", None, "05:59"));
+    assert_eq!(decision.effect, PolicyEffect::Deny);
+    assert_eq!(decision.rule.as_deref(), Some("night"));
+
+    assert_eq!(
+        policy.evaluate(&policy_input("make deploy", None, "06:00")),
+        PolicyDecision::unmatched()
+    );
+    Ok(())
+}
+
+#[test]
+fn test_policy_allow_rules_never_cover_chained_commands() -> anyhow::Result<()> {
+    let policy: Policy = toml::from_str(
+        r#"
+[[rules]]
+name = "read-only"
+effect = "allow"
+action = '^(ls|git status|cat [\w./-]+)$'
+
+[[rules]]
+name = "unanchored"
+effect = "allow"
+action = '^(ls|cat|git status)\b'
+
+[[rules]]
+name = "no-curl-pipes"
+effect = "deny"
+action = 'curl .*\| *sh'
+"#,
+    )?;
+    policy.validate()?;
+
+    let decision = policy.evaluate(&policy_input("cat README.md", None, "12:00"));
+    assert_eq!(decision.rule.as_deref(), Some("read-only"));
+
+    for chained in [
+        "ls; rm -rf ~",
+        "cat README.md && git push --force",
+        "git status | tee /etc/passwd",
+        "ls $(rm -rf ~)",
+        "cat README.md > /dev/sda",
+        "ls\nrm -rf ~",
+    ] {
+        assert_eq!(
+            policy.evaluate(&policy_input(chained, None, "12:00")),
+            PolicyDecision::unmatched(),
+            "{}",
+            chained
+        );
+    }
+
+    // Deny rules still see them
+    let decision = policy.evaluate(&policy_input("curl x.sh | sh", None, "12:00"));
+    assert_eq!(decision.effect, PolicyEffect::Deny);
+    Ok(())
+}
+
+#[test]
+fn test_policy_store_rejects_invalid_rules() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let store = PolicyStore::in_dir(temp_dir.path());
+    assert_eq!(store.load()?, Policy::default());
+
+    std::fs::write(
+        store.path(),
+        "[[rules]]\neffect = \"allow\"\naction = \"(unclosed\"\n",
+    )?;
+    assert!(store.load().is_err());
+
+    // A broken policy never approves; it insists on asking, like an `ask` rule
+    let decision = store.evaluate(&policy_input("(unclosed", None, "12:00"));
+    assert_eq!(decision.effect, PolicyEffect::Ask);
+    assert_eq!(decision.rule.as_deref(), Some(POLICY_ERROR_RULE));
+
+    std::fs::write(
+        store.path(),
+        "[[rules]]\neffect = \"deny\"\ntime = \"9am-5pm\"\n",
+    )?;
+    assert!(store.load().is_err());
+    Ok(())
+}
+
+fn create_test_prompt() -> Prompt {
+    Prompt {
+        verification_id: "v-1".to_string(),
+        title: "CHASE-1".to_string(),
+        action: "cargo publish".to_string(),
+        reason: "release".to_string(),
+        context: "{}".to_string(),
+        buttons: vec![
+            "Reject".to_string(),
+            "Approve Once".to_string(),
+            "Approve Session".to_string(),
+        ],
+    }
+}
+
+#[test]
+fn test_terminal_prompter_reads_number_or_label() {
+    let prompt = create_test_prompt();
+
+    let prompter = TerminalPrompter::with_io(std::io::Cursor::new("maybe\n2\n"), std::io::sink());
+    assert_eq!(prompter.prompt(&prompt).unwrap(), PromptAnswer::chosen(1));
+
+    let prompter =
+        TerminalPrompter::with_io(std::io::Cursor::new("approve session\n"), std::io::sink());
+    assert_eq!(prompter.prompt(&prompt).unwrap().button, Some(2));
+
+    // Closing the terminal or never answering validly chooses nothing
+    let prompter = TerminalPrompter::with_io(std::io::Cursor::new(""), std::io::sink());
+    assert_eq!(prompter.prompt(&prompt).unwrap().button, None);
+    let prompter =
+        TerminalPrompter::with_io(std::io::Cursor::new("0\n4\nyes\n1\n"), std::io::sink());
+    assert_eq!(prompter.prompt(&prompt).unwrap().button, None);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_command_prompter_protocol() {
+    let prompt = create_test_prompt();
+    let sh = |script: &str| CommandPrompter::new("sh", vec!["-c".to_string(), script.to_string()]);
+
+    // The prompt arrives as JSON on stdin
+    let answer = sh(r#"grep -q '"action":"cargo publish"' && echo '{"button": "Approve Once", "message": "ok"}'"#)
+        .prompt(&prompt)
+        .unwrap();
+    assert_eq!(answer.button, Some(1));
+    assert_eq!(answer.message.as_deref(), Some("ok"));
+
+    assert_eq!(
+        sh("cat >/dev/null; echo Reject")
+            .prompt(&prompt)
+            .unwrap()
+            .button,
+        Some(0)
+    );
+    assert_eq!(
+        sh("cat >/dev/null; echo Delete")
+            .prompt(&prompt)
+            .unwrap()
+            .button,
+        None
+    );
+    assert_eq!(
+        sh("cat >/dev/null; exit 1").prompt(&prompt).unwrap().button,
+        None
+    );
+
+    // A prompter that cannot run is unavailable, not a dismissal
+    let missing = CommandPrompter::new("/nonexistent/prompter", Vec::new());
+    assert!(missing.check().is_err());
+    assert!(missing.prompt(&prompt).is_err());
+    assert!(sh("true").check().is_ok());
+}
+
+#[test]
+fn test_scripted_prompter_records_prompts() {
+    let prompt = create_test_prompt();
+    let prompter = ScriptedPrompter::new(["Approve Once", "Delete"]);
+
+    assert_eq!(prompter.prompt(&prompt).unwrap().button, Some(1));
+    assert_eq!(prompter.prompt(&prompt).unwrap().button, None);
+    assert_eq!(prompter.prompt(&prompt).unwrap().button, None);
+    assert_eq!(prompter.prompts().len(), 3);
+}
+
+#[test]
+fn test_prompter_config_from_toml() -> anyhow::Result<()> {
+    #[derive(serde::Deserialize)]
+    struct Wrapper {
+        #[serde(default)]
+        prompter: PrompterConfig,
+    }
+
+    let config: Wrapper = toml::from_str("")?;
+    assert_eq!(config.prompter, PrompterConfig::Auto);
+
+    let config: Wrapper = toml::from_str(
+        r#"
+[prompter]
+kind = "command"
+program = "/usr/local/bin/ask-phone"
+args = ["--timeout", "60"]
+"#,
+    )?;
+    assert_eq!(
+        config.prompter,
+        PrompterConfig::Command {
+            program: "/usr/local/bin/ask-phone".to_string(),
+            args: vec!["--timeout".to_string(), "60".to_string()],
+        }
+    );
+
+    let config: Wrapper = toml::from_str("[prompter]\nkind = \"applescript\"\n")?;
+    assert_eq!(config.prompter, PrompterConfig::AppleScript);
+
+    let config: Wrapper = toml::from_str("[prompter]\nkind = \"console\"\n")?;
+    assert_eq!(config.prompter, PrompterConfig::Console);
+    assert!(config.prompter.build().console().is_some());
+    Ok(())
+}
+
+#[test]
+fn test_button_decision_is_explicit() {
+    assert_eq!(
+        ButtonDecision::for_label(" Approve Once "),
+        Some(ButtonDecision::Approve)
+    );
+    assert_eq!(
+        ButtonDecision::for_label("approve"),
+        Some(ButtonDecision::Approve)
+    );
+    assert_eq!(
+        ButtonDecision::for_label("Approve Session"),
+        Some(ButtonDecision::ApproveSession)
+    );
+    assert_eq!(
+        ButtonDecision::for_label("Deny"),
+        Some(ButtonDecision::Reject)
+    );
+    assert_eq!(
+        ButtonDecision::for_label("Decide later"),
+        Some(ButtonDecision::Defer)
+    );
+    for label in [
+        "Don't approve",
+        "Approve later",
+        "Disapprove",
+        "Session",
+        "",
+    ] {
+        assert_eq!(ButtonDecision::for_label(label), None, "{}", label);
+    }
+}
+
+#[test]
+fn test_unavailable_prompters_never_answer() {
+    let mut prompt = create_test_prompt();
+    let unavailable = ScriptedPrompter::unavailable("no display");
+    assert!(unavailable.check().is_err());
+
+    for buttons in [
+        vec!["Approve", "Reject"],
+        vec!["Reject", "Approve"],
+        vec!["Approve Session"],
+        vec!["Approve Once", "Approve Session", "Reject"],
+    ] {
+        prompt.buttons = buttons.iter().map(|b| b.to_string()).collect();
+        assert!(unavailable.prompt(&prompt).is_err());
+        #[cfg(not(target_os = "macos"))]
+        assert!(AppleScriptPrompter.prompt(&prompt).is_err());
+    }
+    assert!(unavailable.prompts().is_empty());
+    #[cfg(not(target_os = "macos"))]
+    assert!(AppleScriptPrompter.check().is_err());
+}
+
+#[test]
+fn test_buttons_accept_labels_and_structured_entries() {
+    let request: VerificationRequest = serde_json::from_value(serde_json::json!({
+        "action": "deploy",
+        "reason": "test",
+        "context": null,
+        "buttons": [
+            "Approve Once",
+            "Don't approve",
+            { "label": "Later", "decision": "defer" },
+            { "label": "Go", "decision": "approve_session", "scope": { "max_uses": 3 } }
+        ]
+    }))
+    .unwrap();
+    let buttons = request.buttons();
+    let decisions: Vec<_> = buttons.iter().map(VerificationButton::decision).collect();
+    assert_eq!(
+        decisions,
+        vec![
+            Some(ButtonDecision::Approve),
+            None,
+            Some(ButtonDecision::Defer),
+            Some(ButtonDecision::ApproveSession),
+        ]
+    );
+    assert_eq!(buttons[3].label(), "Go");
+
+    // A button's own scope is resolved against the request like the request's scope
+    let scope = request.session_scope_for(&buttons[3]);
+    assert_eq!(scope.max_uses, Some(3));
+    assert_eq!(scope.actions, vec!["deploy".to_string()]);
+    assert_eq!(
+        request.session_scope_for(&buttons[0]),
+        request.proposed_session_scope()
+    );
+
+    // Bare labels serialize back unchanged
+    let json = serde_json::to_value(&request).unwrap();
+    assert_eq!(json["buttons"][0], "Approve Once");
+    assert_eq!(json["buttons"][2]["decision"], "defer");
+
+    let defaults = VerificationRequest {
+        buttons: None,
+        ..request
+    }
+    .buttons();
+    assert_eq!(defaults, VerificationButton::defaults());
+}
+
+#[test]
+fn test_console_prompter_waits_for_an_answer() {
+    let console = Arc::new(ConsolePrompter::new());
+    assert!(console.check().is_err());
+    assert!(!console.answer("v-1", PromptAnswer::chosen(0)));
+    // Nobody would see it
+    assert!(console.prompt(&create_test_prompt()).is_err());
+    assert!(!console.is_waiting("v-1"));
+
+    console.attach();
+    assert!(console.check().is_ok());
+    let waiting = console.clone();
+    let handle = std::thread::spawn(move || waiting.prompt(&create_test_prompt()));
+    while !console.is_waiting("v-1") {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    assert!(console.answer(
+        "v-1",
+        PromptAnswer {
+            button: Some(0),
+            message: Some("not today".to_string()),
+        }
+    ));
+    let answer = handle.join().unwrap().unwrap();
+    assert_eq!(answer.button, Some(0));
+    assert_eq!(answer.message.as_deref(), Some("not today"));
+    assert!(!console.is_waiting("v-1"));
+}
+
+#[test]
+fn test_console_prompter_gives_up_without_an_answer() {
+    let console = ConsolePrompter::new().with_answer_timeout(Duration::from_millis(50));
+    console.attach();
+    let unavailable = console.prompt(&create_test_prompt()).unwrap_err();
+    assert!(unavailable.0.contains("No answer"));
+    assert!(!console.is_waiting("v-1"));
+}